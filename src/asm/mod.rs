@@ -4,4 +4,5 @@ mod assemble;
 mod parse;
 
 pub use assemble::assemble;
-pub use parse::parse_asm_file;
+pub use parse::{parse_asm_file, parse_asm_program, parse_byte, AsmError, LabelSpan, Program};
+pub(crate) use parse::{parse_int_literal, parse_register};
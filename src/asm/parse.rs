@@ -1,34 +1,240 @@
-use anyhow::{anyhow, Result};
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{anyhow, Context, Result};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{digit1, hex_digit1, line_ending, multispace0, space0, space1},
-    combinator::{all_consuming, eof, value},
-    multi::many0,
-    sequence::{delimited, preceded, separated_pair, terminated, tuple},
-    IResult,
+    character::complete::{
+        alpha1, alphanumeric1, digit1, hex_digit1, line_ending, multispace0, space0, space1,
+    },
+    combinator::{all_consuming, eof, map, recognize, value},
+    multi::{many0, many0_count},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    IResult, Offset,
 };
 
 use crate::yan85::{instruction::Instruction, register::Register};
 
+/// An assembled Yan85 program along with its labels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// The program's instructions.
+    pub instructions: Vec<Instruction>,
+    /// The instruction index that each label refers to.
+    pub labels: BTreeMap<String, u8>,
+    /// The one-based source line of each instruction.
+    pub lines: Vec<usize>,
+    /// Where each label is defined, in source order.
+    pub definitions: Vec<LabelSpan>,
+    /// Where each label is referenced by an `IMM` instruction, in source order.
+    pub references: Vec<LabelSpan>,
+}
+
+/// Where a label's name appears in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSpan {
+    /// The label.
+    pub name: String,
+    /// The one-based line.
+    pub line: usize,
+    /// The byte offset of the name in the line.
+    pub start: usize,
+    /// The byte offset after the name in the line.
+    pub end: usize,
+}
+
+/// An error in Yan85 assembly, along with the line it's on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// The one-based line of the error.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A line of Yan85 assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    /// An instruction.
+    Instruction(Instruction),
+    /// An `IMM` instruction whose value is the index of a label.
+    LabelImm(Register, &'a str),
+    /// A label definition, e.g. `loop:`, referring to the instruction that follows it.
+    Label(&'a str),
+}
+
 /// Parses a file with Yan85 assembly instructions with one instruction per line. A wrapper around
-/// [`parse_asm_instructions`] that accepts an owned type and returns a standard result type.
+/// [`parse_asm_program`] that discards the labels.
 pub fn parse_asm_file(asm: String) -> Result<Vec<Instruction>> {
-    let (_, instructions) = all_consuming(parse_asm_instructions)(&asm)
-        .map_err(|e| anyhow!("Unable to parse assembly file: {}", e))?;
+    Ok(parse_asm_program(asm)?.instructions)
+}
+
+/// Parses a file with Yan85 assembly instructions and label definitions, one per line, resolving
+/// label references. A wrapper around [`parse_asm_lines`] that accepts an owned type and returns a
+/// standard result type.
+///
+/// Errors are [`AsmError`]s, which give the line of the error.
+pub fn parse_asm_program(asm: String) -> Result<Program> {
+    let line_of = |rest: &str| asm[..asm.len() - rest.len()].matches('\n').count() + 1;
+    let error = |line, message| anyhow!(AsmError { line, message });
+    let span_of = |label: &str| {
+        let offset = asm.offset(label);
+        let start = offset - asm[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+        LabelSpan {
+            name: label.to_string(),
+            line: line_of(&asm[offset..]),
+            start,
+            end: start + label.len(),
+        }
+    };
+
+    let (_, lines) = all_consuming(parse_asm_lines)(&asm).map_err(|e| {
+        let rest = match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+            nom::Err::Incomplete(_) => "",
+        };
+        let text = rest.lines().next().unwrap_or_default().trim();
+
+        error(line_of(rest), format!("Unable to parse `{text}`"))
+    })?;
+
+    let lines: Vec<(usize, Line)> = lines
+        .into_iter()
+        .map(|(rest, line)| (line_of(rest), line))
+        .collect();
+
+    let mut labels = BTreeMap::new();
+    let mut definitions = Vec::new();
+    let mut references = Vec::new();
+    let mut index: usize = 0;
+
+    for &(number, line) in &lines {
+        match line {
+            Line::Label(label) => {
+                let index = u8::try_from(index).map_err(|_| {
+                    error(
+                        number,
+                        format!("Label {label} is past the last instruction index"),
+                    )
+                })?;
+
+                if labels.insert(label.to_string(), index).is_some() {
+                    return Err(error(
+                        number,
+                        format!("Label {label} is defined more than once"),
+                    ));
+                }
+                definitions.push(span_of(label));
+            }
+            Line::LabelImm(_, label) => {
+                references.push(span_of(label));
+                index += 1;
+            }
+            Line::Instruction(_) => index += 1,
+        }
+    }
 
-    Ok(instructions)
+    let lines: Vec<(usize, Line)> = lines
+        .into_iter()
+        .filter(|(_, line)| !matches!(line, Line::Label(_)))
+        .collect();
+
+    let instructions = lines
+        .iter()
+        .map(|&(number, line)| match line {
+            Line::Instruction(instruction) => Ok(instruction),
+            Line::LabelImm(register, label) => labels
+                .get(label)
+                .map(|&index| Instruction::IMM(register, index))
+                .ok_or_else(|| error(number, format!("Undefined label: {label}"))),
+            Line::Label(label) => unreachable!("label {label} was filtered out"),
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Program {
+        instructions,
+        labels,
+        lines: lines.into_iter().map(|(line, _)| line).collect(),
+        definitions,
+        references,
+    })
 }
 
-/// Parses a string with Yan85 assembly instructions with one instruction per line.
-fn parse_asm_instructions(asm: &str) -> IResult<&str, Vec<Instruction>> {
+/// Parses a string with Yan85 assembly instructions and label definitions, one per line. Each line
+/// is returned with the input remaining at its start, for finding its line number.
+fn parse_asm_lines(asm: &str) -> IResult<&str, Vec<(&str, Line<'_>)>> {
     delimited(
         multispace0, // leading
-        many0(terminated(parse_asm_instruction, multispace0)),
+        many0(terminated(pair(position, parse_asm_line), multispace0)),
         multispace0, // trailing
     )(asm)
 }
 
+/// Returns the remaining input without consuming it, marking the position of what follows.
+fn position(input: &str) -> IResult<&str, &str> {
+    Ok((input, input))
+}
+
+/// Parses a single line of Yan85 assembly.
+fn parse_asm_line(asm: &str) -> IResult<&str, Line<'_>> {
+    alt((
+        parse_label_definition,
+        parse_label_imm,
+        map(parse_asm_instruction, Line::Instruction),
+    ))(asm)
+}
+
+/// Parses a label definition.
+///
+/// Syntax: `<label>:`
+///
+/// Examples:
+///
+/// - `loop:`
+/// - `check_key:`
+fn parse_label_definition(asm: &str) -> IResult<&str, Line<'_>> {
+    let (remaining, label) = terminated(
+        parse_label,
+        tuple((tag(":"), space0, alt((line_ending, eof)))),
+    )(asm)?;
+
+    Ok((remaining, Line::Label(label)))
+}
+
+/// Parses an `IMM` instruction whose value is a label.
+///
+/// Syntax: `IMM <reg> = <label>`
+///
+/// Examples:
+///
+/// - `IMM d = loop`
+fn parse_label_imm(asm: &str) -> IResult<&str, Line<'_>> {
+    let (remaining, (reg, label)) = delimited(
+        tuple((tag("IMM"), space1)),
+        separated_pair(parse_register, parse_assignment, parse_label),
+        tuple((space0, alt((line_ending, eof)))),
+    )(asm)?;
+
+    Ok((remaining, Line::LabelImm(reg, label)))
+}
+
+/// Parses a label name: a letter or underscore followed by letters, digits, and underscores.
+fn parse_label(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
 /// Parses a single Yan85 assembly instruction.
 fn parse_asm_instruction(asm: &str) -> IResult<&str, Instruction> {
     alt((
@@ -183,7 +389,7 @@ fn parse_sys(asm: &str) -> IResult<&str, Instruction> {
 }
 
 /// Parses a register name, including `NONE`.
-fn parse_register(input: &str) -> IResult<&str, Register> {
+pub(crate) fn parse_register(input: &str) -> IResult<&str, Register> {
     alt((
         value(Register::A, tag("a")),
         value(Register::B, tag("b")),
@@ -209,13 +415,24 @@ fn parse_assignment(input: &str) -> IResult<&str, ()> {
 }
 
 /// Parses a decimal or hexadecimal integer literal.
-fn parse_int_literal(input: &str) -> IResult<&str, u8> {
+pub(crate) fn parse_int_literal(input: &str) -> IResult<&str, u8> {
     alt((
         parse_int_literal_hex, // _hex must come first; _decimal would consume the "0" in "0x"
         parse_int_literal_decimal,
     ))(input)
 }
 
+/// Parses a whole string as a decimal or `0x`-prefixed hexadecimal byte, e.g. a command-line
+/// argument.
+pub fn parse_byte(value: &str) -> Result<u8> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.with_context(|| format!("Invalid byte: {value}"))
+}
+
 /// Parses a decimal integer literal.
 fn parse_int_literal_decimal(input: &str) -> IResult<&str, u8> {
     let (remaining, digits) = digit1(input)?;
@@ -272,6 +489,78 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_parse_labels() {
+        let program = parse_asm_program(
+            r#"
+                IMM d = end
+                loop:
+                IMM a = 0x1
+                JMP 0x1 d
+                end:
+                IMM d = loop
+            "#
+            .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::IMM(Register::D, 3),
+                Instruction::IMM(Register::A, 1),
+                Instruction::JMP(1, Register::D),
+                Instruction::IMM(Register::D, 1),
+            ]
+        );
+        assert_eq!(program.labels["loop"], 1);
+        assert_eq!(program.labels["end"], 3);
+    }
+
+    #[test]
+    fn test_label_spans() {
+        let program = parse_asm_program("IMM d = end\n  end:\nIMM c =  end\n".to_string()).unwrap();
+        let span = |line, start| LabelSpan {
+            name: "end".to_string(),
+            line,
+            start,
+            end: start + 3,
+        };
+
+        assert_eq!(program.definitions, [span(2, 2)]);
+        assert_eq!(program.references, [span(1, 8), span(3, 9)]);
+    }
+
+    #[test]
+    fn test_source_lines() {
+        let program = parse_asm_program("\nIMM a = 1\n\nloop:\nJMP 0x1 d\n".to_string()).unwrap();
+        assert_eq!(program.lines, [2, 5]);
+    }
+
+    /// Returns the error from parsing `asm`.
+    fn asm_error(asm: &str) -> AsmError {
+        let error = parse_asm_program(asm.to_string()).unwrap_err();
+        error.downcast::<AsmError>().unwrap()
+    }
+
+    #[test]
+    fn test_undefined_label() {
+        let error = asm_error("IMM a = 1\nIMM d = nowhere");
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "Undefined label: nowhere");
+    }
+
+    #[test]
+    fn test_duplicate_label() {
+        assert_eq!(asm_error("a:\na:\nIMM a = 1").line, 2);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let error = asm_error("IMM a = 1\n\n  FOO a b\nIMM b = 2");
+        assert_eq!(error.to_string(), "line 3: Unable to parse `FOO a b`");
+    }
+
     #[test]
     fn test_imm() {
         let (_, instruction) = parse_asm_instruction("IMM i = 42").unwrap();
@@ -414,4 +703,12 @@ mod tests {
         // this parses as `0` because when hex parsing fails, it falls back to decimal parsing.
         // i suppose it's not a big deal because the trailing junk will cause a failure eventually.
     }
+
+    #[test]
+    fn test_parse_byte() {
+        assert_eq!(parse_byte("0x4c").unwrap(), 0x4c);
+        assert_eq!(parse_byte("255").unwrap(), 255);
+        assert!(parse_byte("256").is_err());
+        assert!(parse_byte("0x20 ").is_err());
+    }
 }
@@ -0,0 +1,264 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emulator::{jump_taken, Emulator},
+    yan85::instruction::Instruction,
+};
+
+/// How often a `JMP` went each way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JumpCounts {
+    /// The number of times the jump was taken.
+    pub taken: u64,
+    /// The number of times the jump fell through.
+    pub not_taken: u64,
+}
+
+/// Instruction coverage of one or more runs of a program.
+///
+/// Coverage is saved as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    /// The number of times each instruction was executed, keyed by index. Instructions that were
+    /// never executed are left out.
+    pub hits: BTreeMap<u8, u64>,
+    /// Which way each executed `JMP` went, keyed by index.
+    pub jumps: BTreeMap<u8, JumpCounts>,
+}
+
+impl Coverage {
+    /// Records the step that `emulator` just took, which emulated `instruction`.
+    pub fn record(&mut self, emulator: &Emulator, instruction: Instruction) {
+        let index = emulator.last_index();
+        *self.hits.entry(index).or_default() += 1;
+
+        if let Some(taken) = jump_taken(instruction, emulator.last_accesses()) {
+            let counts = self.jumps.entry(index).or_default();
+            if taken {
+                counts.taken += 1;
+            } else {
+                counts.not_taken += 1;
+            }
+        }
+    }
+
+    /// Adds the counts from `other`, e.g. coverage of a run with different input.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&index, &hits) in &other.hits {
+            *self.hits.entry(index).or_default() += hits;
+        }
+
+        for (&index, counts) in &other.jumps {
+            let merged = self.jumps.entry(index).or_default();
+            merged.taken += counts.taken;
+            merged.not_taken += counts.not_taken;
+        }
+    }
+
+    /// Saves the coverage to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    /// Loads coverage from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).context("Unable to parse coverage")
+    }
+
+    /// Returns the disassembly of `instructions` annotated with this coverage, for display.
+    pub fn annotate<'a>(&'a self, instructions: &'a [Instruction]) -> Annotated<'a> {
+        Annotated {
+            coverage: self,
+            instructions,
+        }
+    }
+
+    /// Returns the number of times the instruction at `index` was executed.
+    fn hits_at(&self, index: usize) -> u64 {
+        u8::try_from(index)
+            .ok()
+            .and_then(|index| self.hits.get(&index))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// The disassembly of a program with the number of times each instruction was executed and the
+/// directions each `JMP` went. Runs of instructions that were never executed are marked.
+pub struct Annotated<'a> {
+    /// The coverage.
+    coverage: &'a Coverage,
+    /// The program.
+    instructions: &'a [Instruction],
+}
+
+impl fmt::Display for Annotated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (coverage, instructions) = (self.coverage, self.instructions);
+
+        let executed = (0..instructions.len())
+            .filter(|&index| coverage.hits_at(index) > 0)
+            .count();
+        let directions: usize = coverage
+            .jumps
+            .values()
+            .map(|counts| usize::from(counts.taken > 0) + usize::from(counts.not_taken > 0))
+            .sum();
+        let jumps = instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::JMP(..)))
+            .count();
+
+        writeln!(
+            f,
+            "{executed}/{} instructions executed, {directions}/{} jump directions taken",
+            instructions.len(),
+            jumps * 2
+        )?;
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            let hits = coverage.hits_at(index);
+
+            if hits == 0 && (index == 0 || coverage.hits_at(index - 1) > 0) {
+                let end = (index..instructions.len())
+                    .take_while(|&index| coverage.hits_at(index) == 0)
+                    .last()
+                    .unwrap_or(index);
+
+                if end == index {
+                    writeln!(f, "; never executed: {index:#04x}")?;
+                } else {
+                    writeln!(f, "; never executed: {index:#04x}..={end:#04x}")?;
+                }
+            }
+
+            let count = match hits {
+                0 => "-".to_string(),
+                hits => hits.to_string(),
+            };
+            write!(f, "{count:>8}  {index:#04x}: {instruction}")?;
+
+            if let Instruction::JMP(..) = instruction {
+                let counts = u8::try_from(index)
+                    .ok()
+                    .and_then(|index| coverage.jumps.get(&index))
+                    .copied()
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "  ; taken {}, not taken {}",
+                    counts.taken, counts.not_taken
+                )?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::parse_asm_file,
+        emu::{emulate_until, Limits},
+        emulator::BufferIo,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    /// Exits with 0 if the first byte of input is "K", and 1 otherwise.
+    const CHECK: &str = "
+        IMM a = 0
+        IMM b = 0x40
+        IMM c = 1
+        SYS 0x4 d
+        LDM b = *b
+        IMM c = 0x4b
+        IMM d = 0xa
+        CMP b c
+        JMP 0x4 d
+        IMM a = 1
+        SYS 0x20 NONE
+    ";
+
+    /// Runs `CHECK` with `stdin`, returning its coverage.
+    fn run(stdin: &[u8]) -> (Coverage, Vec<Instruction>) {
+        let instructions = parse_asm_file(CHECK.to_string()).unwrap();
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            instructions.clone(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(stdin)));
+
+        let mut coverage = Coverage::default();
+        emulate_until(
+            &mut emulator,
+            &Limits::default(),
+            |_| false,
+            |emulator, instruction| coverage.record(emulator, instruction),
+        )
+        .unwrap();
+
+        (coverage, instructions)
+    }
+
+    #[test]
+    fn test_record() {
+        let (coverage, _) = run(b"A");
+
+        assert_eq!(coverage.hits.len(), 11);
+        assert_eq!(
+            coverage.jumps[&8],
+            JumpCounts {
+                taken: 0,
+                not_taken: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut coverage, _) = run(b"A");
+        let (other, _) = run(b"K");
+        coverage.merge(&other);
+
+        assert_eq!(coverage.hits[&0], 2);
+        assert_eq!(coverage.hits[&9], 1);
+        assert_eq!(
+            coverage.jumps[&8],
+            JumpCounts {
+                taken: 1,
+                not_taken: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        colored::control::set_override(false);
+        let (coverage, instructions) = run(b"K");
+        let annotated = coverage.annotate(&instructions).to_string();
+
+        assert!(annotated.starts_with("10/11 instructions executed, 1/2 jump directions taken\n"));
+        assert!(annotated.contains("; never executed: 0x09\n"));
+        assert!(annotated.contains("  ; taken 1, not taken 0\n"));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let (coverage, _) = run(b"A");
+
+        let json = serde_json::to_string(&coverage).unwrap();
+        assert_eq!(serde_json::from_str::<Coverage>(&json).unwrap(), coverage);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    emu::{emulate_until, Limits, StopReason},
+    emulator::{BufferIo, Emulator, FaultPolicy},
+    yan85::{constants::Constants, instruction::Instruction, memory::Memory, register::Register},
+};
+
+/// How candidate inputs are ranked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Metric {
+    /// Prefer inputs that execute more instructions.
+    #[default]
+    Steps,
+    /// Prefer inputs that reach a higher instruction index.
+    HighestIndex,
+}
+
+/// Options for [`crack`].
+#[derive(Debug, Clone)]
+pub struct CrackOptions {
+    /// The bytes to try at each position of the input.
+    pub alphabet: Vec<u8>,
+    /// The length of the input, including bytes that haven't been recovered yet.
+    pub length: usize,
+    /// The byte used for positions that haven't been recovered yet.
+    pub padding: u8,
+    /// The index of an instruction that's only reached with the correct input, such as the `OPEN`
+    /// of `/flag`. Cracking stops as soon as it's reached.
+    pub target: Option<u8>,
+    /// How candidate inputs are ranked.
+    pub metric: Metric,
+    /// Limits on each run. A run that reaches a limit ends like one that exits.
+    pub limits: Limits,
+    /// How to handle guest faults, which end a run.
+    pub fault_policy: FaultPolicy,
+}
+
+impl Default for CrackOptions {
+    fn default() -> Self {
+        Self {
+            alphabet: (0x20..=0x7e).collect(),
+            length: 16,
+            padding: b'A',
+            target: None,
+            metric: Metric::default(),
+            limits: Limits {
+                max_steps: Some(1_000_000),
+                ..Limits::default()
+            },
+            fault_policy: FaultPolicy::default(),
+        }
+    }
+}
+
+/// The result of [`crack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crack {
+    /// The recovered prefix of the input.
+    pub input: Vec<u8>,
+    /// Whether the recovered input reaches the target instruction.
+    pub reached_target: bool,
+}
+
+/// The outcome of running the program with one candidate input.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    /// The number of instructions executed.
+    steps: u64,
+    /// The highest instruction index reached.
+    highest_index: u8,
+    /// Whether the target instruction was reached.
+    reached_target: bool,
+}
+
+impl Run {
+    /// Returns the run's score under `metric`, where higher is better.
+    fn score(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::Steps => self.steps,
+            Metric::HighestIndex => self.highest_index.into(),
+        }
+    }
+}
+
+/// Recovers the standard input that a program checks byte by byte, using the number of
+/// instructions executed as a side channel.
+///
+/// Each position of the input is tried with every byte in the alphabet, keeping the byte whose run
+/// scores highest. Cracking stops when the target instruction is reached, when the whole input has
+/// been recovered, or when every byte at a position scores the same. `progress` is called with the
+/// recovered prefix each time it grows.
+pub fn crack(
+    constants: Constants,
+    instructions: &[Instruction],
+    memory: &Memory,
+    options: &CrackOptions,
+    mut progress: impl FnMut(&[u8]),
+) -> Crack {
+    let try_input = |input: &[u8]| run(constants, instructions, memory, options, input);
+    let mut known = Vec::new();
+
+    while known.len() < options.length {
+        let mut best: Option<(u8, u64)> = None;
+        let mut distinguished = false;
+
+        for &byte in &options.alphabet {
+            known.push(byte);
+            let result = try_input(&known);
+            known.pop();
+
+            if result.reached_target {
+                known.push(byte);
+                progress(&known);
+
+                return Crack {
+                    input: known,
+                    reached_target: true,
+                };
+            }
+
+            let score = result.score(options.metric);
+            match best {
+                None => best = Some((byte, score)),
+                Some((_, best_score)) if score > best_score => {
+                    best = Some((byte, score));
+                    distinguished = true;
+                }
+                Some((_, best_score)) => distinguished |= score != best_score,
+            }
+        }
+
+        match best {
+            Some((byte, _)) if distinguished => known.push(byte),
+            _ => break,
+        }
+        progress(&known);
+    }
+
+    let reached_target = try_input(&known).reached_target;
+
+    Crack {
+        input: known,
+        reached_target,
+    }
+}
+
+/// Runs the program with `prefix`, padded to the input length, as standard input.
+fn run(
+    constants: Constants,
+    instructions: &[Instruction],
+    memory: &Memory,
+    options: &CrackOptions,
+    prefix: &[u8],
+) -> Run {
+    let mut input = prefix.to_vec();
+    input.resize(options.length.max(prefix.len()), options.padding);
+
+    let mut emulator = Emulator::new(constants, instructions.to_vec(), memory.clone());
+    emulator.set_io(Box::new(BufferIo::new(input)));
+    emulator.set_fault_policy(options.fault_policy);
+
+    let mut run = Run {
+        steps: 0,
+        highest_index: 0,
+        reached_target: false,
+    };
+
+    let result = emulate_until(
+        &mut emulator,
+        &options.limits,
+        |emulator| Some(emulator.registers()[Register::I]) == options.target,
+        |emulator, _| {
+            run.steps += 1;
+            run.highest_index = run.highest_index.max(emulator.last_index());
+        },
+    );
+
+    // a fault ends the run like an exit does
+    run.reached_target = matches!(result, Ok(StopReason::Stopped));
+
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::parse_asm_program;
+
+    /// Reads three bytes and checks them against "key" one at a time, exiting early on the first
+    /// wrong byte.
+    const CHECK: &str = "
+        IMM a = 0
+        IMM b = 0x40
+        IMM c = 3
+        SYS 0x4 d
+        IMM d = check
+        CMP a a
+        JMP 0x4 d
+        fail:
+        IMM a = 1
+        SYS 0x20 NONE
+        check:
+        IMM d = fail
+        IMM a = 0x40
+        LDM b = *a
+        IMM c = 0x6b
+        CMP b c
+        JMP 0x8 d
+        IMM a = 0x41
+        LDM b = *a
+        IMM c = 0x65
+        CMP b c
+        JMP 0x8 d
+        IMM a = 0x42
+        LDM b = *a
+        IMM c = 0x79
+        CMP b c
+        JMP 0x8 d
+        win:
+        IMM a = 0
+        SYS 0x20 NONE
+    ";
+
+    /// Cracks `CHECK` with the given target and metric.
+    fn crack_check(target: bool, metric: Metric) -> (Crack, Vec<Vec<u8>>) {
+        let program = parse_asm_program(CHECK.to_string()).unwrap();
+        let options = CrackOptions {
+            alphabet: b"abcdefghijklmnopqrstuvwxyz".to_vec(),
+            length: 3,
+            target: target.then(|| program.labels["win"]),
+            metric,
+            ..CrackOptions::default()
+        };
+
+        let mut prefixes = Vec::new();
+        let crack = crack(
+            Constants::default(),
+            &program.instructions,
+            &Memory::default(),
+            &options,
+            |prefix| prefixes.push(prefix.to_vec()),
+        );
+
+        (crack, prefixes)
+    }
+
+    #[test]
+    fn test_crack_to_target() {
+        let (crack, prefixes) = crack_check(true, Metric::Steps);
+
+        assert_eq!(crack.input, b"key");
+        assert!(crack.reached_target);
+        assert_eq!(prefixes, [b"k".to_vec(), b"ke".to_vec(), b"key".to_vec()]);
+    }
+
+    #[test]
+    fn test_crack_highest_index() {
+        let (crack, _) = crack_check(false, Metric::HighestIndex);
+
+        assert_eq!(crack.input, b"key");
+        assert!(!crack.reached_target);
+    }
+
+    #[test]
+    fn test_crack_stops_when_undistinguished() {
+        // without a target, the last byte doesn't change the instruction count
+        let (crack, _) = crack_check(false, Metric::Steps);
+
+        assert_eq!(crack.input, b"ke");
+        assert!(!crack.reached_target);
+    }
+
+    #[test]
+    fn test_crack_detect_loops() {
+        // wrong bytes hang, which only loop detection ends
+        let program = parse_asm_program(
+            "IMM a = 0
+            IMM b = 0x40
+            IMM c = 1
+            SYS 0x4 d
+            LDM b = *b
+            IMM c = 0x6b
+            CMP b c
+            IMM d = hang
+            hang:
+            JMP 0x8 d
+            win:
+            SYS 0x20 NONE
+            "
+            .to_string(),
+        )
+        .unwrap();
+        let options = CrackOptions {
+            alphabet: b"abcdefghijklmnopqrstuvwxyz".to_vec(),
+            length: 1,
+            target: Some(program.labels["win"]),
+            limits: Limits {
+                detect_loops: true,
+                ..Limits::default()
+            },
+            ..CrackOptions::default()
+        };
+
+        let crack = crack(
+            Constants::default(),
+            &program.instructions,
+            &Memory::default(),
+            &options,
+            |_| {},
+        );
+
+        assert_eq!(crack.input, b"k");
+        assert!(crack.reached_target);
+    }
+}
@@ -0,0 +1,701 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{BufRead, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    asm::{parse_asm_program, Program},
+    emu::{emulate_until, Limits, StopReason},
+    emulator::{BufferIo, Emulator, SharedBuffer},
+    framing::{read_message, write_message},
+    yan85::{constants::Constants, register::Register, registers::Registers},
+};
+
+/// The only thread, since Yan85 programs are single-threaded.
+const THREAD_ID: u64 = 1;
+
+/// The variables reference of the registers scope.
+const REGISTERS: u64 = 1;
+/// The variables reference of the memory scope.
+const MEMORY: u64 = 2;
+/// The variables reference of the stack scope.
+const STACK: u64 = 3;
+
+/// The number of bytes shown in each variable of the memory and stack scopes.
+const ROW: usize = 16;
+
+/// The number of steps a running program takes between checks for requests like `pause`.
+const SLICE: u64 = 10_000;
+
+/// A program launched by the client.
+struct Session {
+    /// The emulator running the program.
+    emulator: Emulator,
+    /// The assembled program.
+    program: Program,
+    /// The path of the program's assembly source.
+    source: PathBuf,
+    /// The instruction indices of breakpoints.
+    breakpoints: BTreeSet<u8>,
+    /// The guest's standard output that hasn't been sent to the client yet.
+    stdout: SharedBuffer,
+    /// Whether to wait for the client instead of running the program once it's configured.
+    stop_on_entry: bool,
+    /// Whether the program is running, i.e. continued and not yet stopped.
+    running: bool,
+    /// The instruction the program was stopped at when it was last continued, whose breakpoint
+    /// is stepped over once.
+    resumed_from: Option<u8>,
+}
+
+/// Why execution stopped.
+enum Stop {
+    /// The program stopped at a breakpoint, after a step, or on a fault, for the given reason.
+    Stopped(&'static str, Option<String>),
+    /// The program exited with the given exit code.
+    Exited(u8),
+}
+
+/// Serves the Debug Adapter Protocol, letting editors like VS Code debug Yan85 assembly.
+///
+/// A `launch` request assembles the `.asm` file in its `program` argument, which is then debugged
+/// at the source level. Guest standard input comes from the `stdin` string or `stdinFile` path
+/// arguments, and guest output is shown in the debug console. Registers, memory, and the stack
+/// are shown as variables.
+pub struct DapServer<R, W> {
+    /// The source of client messages, until the server starts reading them.
+    input: Option<R>,
+    /// The destination of server messages.
+    output: W,
+    /// The sequence number of the next server message.
+    seq: u64,
+    /// The encoding constants used when launching programs.
+    constants: Constants,
+    /// The launched program.
+    session: Option<Session>,
+}
+
+impl<R: BufRead + Send + 'static, W: Write> DapServer<R, W> {
+    /// Constructs a server that reads requests from `input`, writes to `output`, and launches
+    /// programs with `constants`.
+    pub fn new(input: R, output: W, constants: Constants) -> Self {
+        Self {
+            input: Some(input),
+            output,
+            seq: 1,
+            constants,
+            session: None,
+        }
+    }
+
+    /// Serves requests until the client disconnects.
+    ///
+    /// Requests are read on a separate thread, so that a running program can be paused. Running
+    /// programs take 10,000 steps at a time, checking for requests in between.
+    pub fn run(&mut self) -> Result<()> {
+        let input = self.input.take().context("The server has already run")?;
+        let requests = spawn_reader(input);
+
+        loop {
+            if self.running() {
+                if let Some(stop) = self.session()?.run_slice() {
+                    self.report(stop)?;
+                }
+            }
+
+            let message = match self.running() {
+                true => match requests.try_recv() {
+                    Ok(message) => message,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                },
+                false => match requests.recv() {
+                    Ok(message) => message,
+                    Err(_) => return Ok(()),
+                },
+            };
+            let Some(request) = message? else {
+                return Ok(());
+            };
+            if request["type"] != "request" {
+                continue;
+            }
+
+            let command = request["command"].as_str().unwrap_or_default().to_string();
+            let arguments = request.get("arguments").cloned().unwrap_or(Value::Null);
+
+            match self.handle(&command, &arguments) {
+                Ok((body, stop)) => {
+                    self.respond(&request, Ok(body))?;
+
+                    match command.as_str() {
+                        "initialize" => self.event("initialized", Value::Null)?,
+                        "disconnect" => return Ok(()),
+                        _ => {}
+                    }
+
+                    if let Some(stop) = stop {
+                        self.report(stop)?;
+                    }
+                }
+                Err(e) => self.respond(&request, Err(e))?,
+            }
+        }
+    }
+
+    /// Handles a request, returning the response body and, for requests that run the program, why
+    /// it stopped.
+    fn handle(&mut self, command: &str, arguments: &Value) -> Result<(Value, Option<Stop>)> {
+        let body = match command {
+            "initialize" => json!({
+                "supportsConfigurationDoneRequest": true,
+            }),
+            "launch" => {
+                let session = self.launch(arguments)?;
+                let stop = session
+                    .stop_on_entry
+                    .then_some(Stop::Stopped("entry", None));
+
+                return Ok((Value::Null, stop));
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments)?,
+            "configurationDone" => {
+                let session = self.session()?;
+                session.running = !session.stop_on_entry;
+                Value::Null
+            }
+            "threads" => json!({ "threads": [{ "id": THREAD_ID, "name": "yan85" }] }),
+            "stackTrace" => self.stack_trace()?,
+            "scopes" => json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                    { "name": "Memory", "variablesReference": MEMORY, "expensive": false },
+                    { "name": "Stack", "variablesReference": STACK, "expensive": false },
+                ]
+            }),
+            "variables" => self.variables(arguments)?,
+            "continue" => {
+                let session = self.session()?;
+                session.running = true;
+                session.resumed_from = Some(session.emulator.registers()[Register::I]);
+                json!({ "allThreadsContinued": true })
+            }
+            "next" | "stepIn" | "stepOut" => {
+                let session = self.session()?;
+                session.running = false;
+                return Ok((Value::Null, Some(session.step())));
+            }
+            "pause" => {
+                let session = self.session()?;
+                let stop = session.running.then_some(Stop::Stopped("pause", None));
+                session.running = false;
+
+                return Ok((Value::Null, stop));
+            }
+            "disconnect" => Value::Null,
+            _ => bail!("Unsupported request: {command}"),
+        };
+
+        Ok((body, None))
+    }
+
+    /// Assembles and loads the program named by the `launch` request's arguments.
+    fn launch(&mut self, arguments: &Value) -> Result<&Session> {
+        let source = PathBuf::from(
+            arguments["program"]
+                .as_str()
+                .context("Missing program argument")?,
+        );
+        let program = parse_asm_program(fs::read_to_string(&source)?)?;
+
+        let stdin = match (arguments["stdin"].as_str(), arguments["stdinFile"].as_str()) {
+            (_, Some(path)) => fs::read(path)?,
+            (Some(stdin), None) => stdin.as_bytes().to_vec(),
+            (None, None) => Vec::new(),
+        };
+        let io = BufferIo::new(stdin);
+        let stdout = io.stdout();
+
+        let mut emulator = Emulator::new(
+            self.constants,
+            program.instructions.clone(),
+            Default::default(),
+        );
+        emulator.set_io(Box::new(io));
+
+        Ok(self.session.insert(Session {
+            emulator,
+            program,
+            source,
+            breakpoints: BTreeSet::new(),
+            stdout,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            running: false,
+            resumed_from: None,
+        }))
+    }
+
+    /// Replaces the breakpoints, moving each to the first instruction at or after its line.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value> {
+        let session = self.session()?;
+        session.breakpoints.clear();
+
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let mut breakpoints = Vec::new();
+
+        for breakpoint in requested {
+            let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+            let found = session
+                .program
+                .lines
+                .iter()
+                .enumerate()
+                .find(|&(_, &instruction_line)| instruction_line >= line);
+
+            match found.and_then(|(index, &line)| Some((u8::try_from(index).ok()?, line))) {
+                Some((index, line)) => {
+                    session.breakpoints.insert(index);
+                    breakpoints.push(json!({ "verified": true, "line": line }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "message": "No instruction at or after this line",
+                })),
+            }
+        }
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Describes the current instruction as the only stack frame.
+    fn stack_trace(&mut self) -> Result<Value> {
+        let session = self.session()?;
+        let index = session.emulator.registers()[Register::I];
+        let line = session.program.lines.get(usize::from(index)).copied();
+
+        let name = match session.program.instructions.get(usize::from(index)) {
+            Some(instruction) => format!("{index:#04x}: {}", instruction.mnemonic()),
+            None => format!("{index:#04x}"),
+        };
+
+        Ok(json!({
+            "stackFrames": [{
+                "id": 0,
+                "name": name,
+                "source": { "path": session.source },
+                "line": line.unwrap_or(0),
+                "column": 1,
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    /// Lists the variables in a scope.
+    fn variables(&mut self, arguments: &Value) -> Result<Value> {
+        let emulator = &self.session()?.emulator;
+
+        let variables: Vec<Value> = match arguments["variablesReference"].as_u64() {
+            Some(REGISTERS) => Registers::ALL
+                .iter()
+                .map(|&register| variable(register.name(), emulator.registers()[register]))
+                .collect(),
+            Some(MEMORY) => rows(|address| emulator.memory()[address]),
+            Some(STACK) => rows(|slot| emulator.stack()[slot]),
+            reference => return Err(anyhow!("Unknown variables reference: {reference:?}")),
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Whether a launched program is running.
+    fn running(&self) -> bool {
+        self.session.as_ref().is_some_and(|session| session.running)
+    }
+
+    /// Returns the launched program.
+    fn session(&mut self) -> Result<&mut Session> {
+        self.session
+            .as_mut()
+            .context("No program has been launched")
+    }
+
+    /// Sends the program's new output and the events for `stop`.
+    fn report(&mut self, stop: Stop) -> Result<()> {
+        let output = self.session()?.stdout.take();
+        if !output.is_empty() {
+            let output = String::from_utf8_lossy(&output).into_owned();
+            self.event("output", json!({ "category": "stdout", "output": output }))?;
+        }
+
+        match stop {
+            Stop::Stopped(reason, text) => {
+                let mut body = json!({ "reason": reason, "threadId": THREAD_ID });
+                if let Some(text) = text {
+                    body["text"] = json!(text);
+                }
+
+                self.event("stopped", body)
+            }
+            Stop::Exited(exit_code) => {
+                self.event("exited", json!({ "exitCode": exit_code }))?;
+                self.event("terminated", Value::Null)
+            }
+        }
+    }
+
+    /// Sends a response to `request`.
+    fn respond(&mut self, request: &Value, body: Result<Value>) -> Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+
+        match body {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(e) => response["message"] = json!(format!("{e:#}")),
+        }
+
+        self.send(response)
+    }
+
+    /// Sends an event.
+    fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+
+        self.send(message)
+    }
+
+    /// Sends a message, assigning it the next sequence number.
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+
+        write_message(&mut self.output, &message)
+    }
+}
+
+impl Session {
+    /// Steps through one instruction.
+    fn step(&mut self) -> Stop {
+        if let Some(exit_code) = self.emulator.exit_code() {
+            return Stop::Exited(exit_code);
+        }
+
+        match self.emulator.step() {
+            Ok(_) => match self.emulator.exit_code() {
+                Some(exit_code) => Stop::Exited(exit_code),
+                None => Stop::Stopped("step", None),
+            },
+            Err(e) => Stop::Stopped("exception", Some(format!("{e:#}"))),
+        }
+    }
+
+    /// Runs up to [`SLICE`] steps, returning why the program stopped if it reached a breakpoint,
+    /// exited, or faulted. The breakpoint the program was continued from, if any, is stepped over.
+    fn run_slice(&mut self) -> Option<Stop> {
+        let limits = Limits {
+            max_steps: Some(SLICE),
+            ..Limits::default()
+        };
+        let mut resumed_from = self.resumed_from.take();
+        let breakpoints = &self.breakpoints;
+        let at_breakpoint = |emulator: &Emulator| {
+            let index = emulator.registers()[Register::I];
+            resumed_from.take() != Some(index) && breakpoints.contains(&index)
+        };
+
+        let stop = match emulate_until(&mut self.emulator, &limits, at_breakpoint, |_, _| {}) {
+            Ok(StopReason::MaxSteps) => return None,
+            Ok(StopReason::Exited(exit_code)) => Stop::Exited(exit_code),
+            Ok(_) => Stop::Stopped("breakpoint", None),
+            Err(e) => Stop::Stopped("exception", Some(format!("{e:#}"))),
+        };
+        self.running = false;
+
+        Some(stop)
+    }
+}
+
+/// Reads messages from `input` on a new thread, sending each one, or the error that ended reading,
+/// down the returned channel. `None` is sent when the client disconnects.
+fn spawn_reader<R: BufRead + Send + 'static>(mut input: R) -> Receiver<Result<Option<Value>>> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || loop {
+        let message = read_message(&mut input);
+        let done = !matches!(message, Ok(Some(_)));
+        if sender.send(message).is_err() || done {
+            return;
+        }
+    });
+
+    receiver
+}
+
+/// Describes a byte as a variable.
+fn variable(name: &str, value: u8) -> Value {
+    json!({ "name": name, "value": format!("{value:#04x}"), "variablesReference": 0 })
+}
+
+/// Describes 256 bytes as rows of hex, named by their first index.
+fn rows(byte: impl Fn(u8) -> u8) -> Vec<Value> {
+    (0..=u8::MAX)
+        .step_by(ROW)
+        .map(|start| {
+            let bytes: Vec<String> = (start..=start.saturating_add(ROW as u8 - 1))
+                .map(|index| format!("{:02x}", byte(index)))
+                .collect();
+
+            json!({
+                "name": format!("{start:#04x}"),
+                "value": bytes.join(" "),
+                "variablesReference": 0,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Writes "hi" and exits.
+    const HELLO: &str = "IMM a = 0x40
+IMM b = 0x68
+STM *a = b
+IMM a = 0x41
+IMM b = 0x69
+STM *a = b
+IMM a = 1
+IMM b = 0x40
+IMM c = 2
+SYS 0x8 NONE
+IMM a = 0
+SYS 0x20 NONE
+";
+
+    /// Frames `requests` as client messages.
+    fn requests(requests: &[Value]) -> Cursor<Vec<u8>> {
+        let mut input = Vec::new();
+        for (seq, request) in requests.iter().enumerate() {
+            let mut request = request.clone();
+            request["seq"] = json!(seq + 1);
+            request["type"] = json!("request");
+            write_message(&mut input, &request).unwrap();
+        }
+
+        Cursor::new(input)
+    }
+
+    /// Runs a session named `name` with `requests` for `HELLO`, returning the messages sent.
+    fn session(name: &str, requests: impl Fn(&str) -> Vec<Value>) -> Vec<Value> {
+        session_for(name, HELLO, requests)
+    }
+
+    /// Runs a session named `name` with `requests` for the program in `source`, returning the
+    /// messages sent.
+    fn session_for(name: &str, source: &str, requests: impl Fn(&str) -> Vec<Value>) -> Vec<Value> {
+        let path = std::env::temp_dir().join(format!("gyan85-{name}-{}.asm", std::process::id()));
+        fs::write(&path, source).unwrap();
+
+        let input = self::requests(&requests(path.to_str().unwrap()));
+        let mut output = Vec::new();
+        DapServer::new(input, &mut output, Constants::default())
+            .run()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+
+        messages
+    }
+
+    /// Returns the names of the events in `messages`.
+    fn events(messages: &[Value]) -> Vec<&str> {
+        messages
+            .iter()
+            .filter_map(|message| message["event"].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_run_to_breakpoint() {
+        let messages = session("dap-breakpoint", |path| {
+            vec![
+                json!({ "command": "initialize" }),
+                json!({ "command": "launch", "arguments": { "program": path } }),
+                json!({
+                    "command": "setBreakpoints",
+                    "arguments": { "source": { "path": path }, "breakpoints": [{ "line": 10 }] }
+                }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+                json!({ "command": "variables", "arguments": { "variablesReference": REGISTERS } }),
+                json!({ "command": "next", "arguments": { "threadId": 1 } }),
+                json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+                json!({ "command": "disconnect" }),
+            ]
+        });
+
+        assert_eq!(
+            events(&messages),
+            [
+                "initialized",
+                "stopped",
+                "output",
+                "stopped",
+                "exited",
+                "terminated"
+            ]
+        );
+
+        let breakpoints = &messages[3]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0], json!({ "verified": true, "line": 10 }));
+
+        let stopped = messages.iter().find(|m| m["event"] == "stopped").unwrap();
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+
+        let trace = messages
+            .iter()
+            .find(|m| m["command"] == "stackTrace")
+            .unwrap();
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 10);
+        assert_eq!(trace["body"]["stackFrames"][0]["name"], "0x09: SYS");
+
+        let variables = messages
+            .iter()
+            .find(|m| m["command"] == "variables")
+            .unwrap();
+        assert_eq!(
+            variables["body"]["variables"][2],
+            json!({ "name": "c", "value": "0x02", "variablesReference": 0 })
+        );
+
+        let output = messages.iter().find(|m| m["event"] == "output").unwrap();
+        assert_eq!(output["body"]["output"], "hi");
+
+        let exited = messages.iter().find(|m| m["event"] == "exited").unwrap();
+        assert_eq!(exited["body"]["exitCode"], 0);
+    }
+
+    #[test]
+    fn test_breakpoint_on_first_line() {
+        let messages = session("dap-first-line", |path| {
+            vec![
+                json!({ "command": "initialize" }),
+                json!({ "command": "launch", "arguments": { "program": path } }),
+                json!({
+                    "command": "setBreakpoints",
+                    "arguments": { "source": { "path": path }, "breakpoints": [{ "line": 1 }] }
+                }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+                json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+                json!({ "command": "disconnect" }),
+            ]
+        });
+
+        assert_eq!(
+            events(&messages),
+            ["initialized", "stopped", "output", "exited", "terminated"]
+        );
+
+        let trace = messages
+            .iter()
+            .find(|m| m["command"] == "stackTrace")
+            .unwrap();
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 1);
+    }
+
+    #[test]
+    fn test_stop_on_entry_and_memory() {
+        let messages = session("dap-entry", |path| {
+            vec![
+                json!({
+                    "command": "launch",
+                    "arguments": { "program": path, "stopOnEntry": true }
+                }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "next", "arguments": { "threadId": 1 } }),
+                json!({ "command": "next", "arguments": { "threadId": 1 } }),
+                json!({ "command": "next", "arguments": { "threadId": 1 } }),
+                json!({ "command": "variables", "arguments": { "variablesReference": MEMORY } }),
+            ]
+        });
+
+        let stopped: Vec<&Value> = messages
+            .iter()
+            .filter(|m| m["event"] == "stopped")
+            .map(|m| &m["body"]["reason"])
+            .collect();
+        assert_eq!(stopped, ["entry", "step", "step", "step"]);
+
+        let variables = messages
+            .iter()
+            .find(|m| m["command"] == "variables")
+            .unwrap();
+        let row = &variables["body"]["variables"][4];
+        assert_eq!(row["name"], "0x40");
+        assert!(row["value"].as_str().unwrap().starts_with("68 00"));
+    }
+
+    #[test]
+    fn test_pause_infinite_loop() {
+        let source = "IMM f = 0x1f
+IMM b = 1
+JMP 0x1f b
+";
+        let messages = session_for("dap-loop", source, |path| {
+            vec![
+                json!({ "command": "launch", "arguments": { "program": path } }),
+                json!({ "command": "configurationDone" }),
+                json!({ "command": "threads" }),
+                json!({ "command": "pause", "arguments": { "threadId": 1 } }),
+                json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+                json!({ "command": "disconnect" }),
+            ]
+        });
+
+        assert_eq!(events(&messages), ["stopped"]);
+        let stopped = messages.iter().find(|m| m["event"] == "stopped").unwrap();
+        assert_eq!(stopped["body"]["reason"], "pause");
+
+        let trace = messages
+            .iter()
+            .find(|m| m["command"] == "stackTrace")
+            .unwrap();
+        assert_eq!(trace["success"], true);
+    }
+
+    #[test]
+    fn test_request_before_launch() {
+        let messages = session("dap-unlaunched", |_| {
+            vec![json!({ "command": "stackTrace" })]
+        });
+
+        assert_eq!(messages[0]["success"], false);
+        assert_eq!(messages[0]["message"], "No program has been launched");
+    }
+}
@@ -0,0 +1,646 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    asm::parse_byte,
+    emulator::{Condition, Emulator, WatchKind, WatchTarget, Watchpoint},
+    yan85::{register::Register, registers::Registers},
+};
+
+/// The number of instructions `disas` shows by default.
+const DISAS_COUNT: u8 = 8;
+
+/// The number of bytes `mem` shows by default.
+const MEM_LEN: u8 = 16;
+
+/// The commands understood by the debugger, shown by `help`.
+const HELP: &str = "\
+break                   list breakpoints and watchpoints
+break <index|label>     set a breakpoint
+break <index|label> if <condition>
+                        set a breakpoint that stops only if condition holds
+break if <condition>    stop at any instruction where condition holds, e.g. `a == 0x7f && i == 3`
+watch <target> [kind]   stop when target is accessed, e.g. `watch mem[0x40..0x50]` or
+                        `watch c change`; kind is read, write (default), or change
+delete <n>              delete breakpoint or watchpoint number n
+step [n]                execute n instructions (default 1)
+next                    execute until the following instruction is reached
+continue                execute until a breakpoint is reached or the program exits
+reverse-step [n]        undo n instructions (default 1)
+reverse-continue        undo instructions until a breakpoint is reached or the history runs out
+last-write <target>     find the most recent write to target, e.g. `last-write mem[0x40]`
+regs                    show the registers
+mem <addr> [len]        show len bytes of memory starting at addr (default 16)
+stack                   show the values on the stack
+set <reg>=<value>       assign value to a register, e.g. `set a=0x41`
+disas [index] [count]   disassemble count instructions from index (default: current)
+help                    show this message
+quit                    exit the debugger";
+
+/// Whether the debugger should keep reading commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Read the next command.
+    Continue,
+    /// Exit the debugger.
+    Quit,
+}
+
+/// A reason for the debugger to stop.
+#[derive(Debug, Clone)]
+enum Trap {
+    /// Stop before an instruction, if a condition holds. At least one of the two is present.
+    Breakpoint {
+        /// The index of the instruction to stop at, or `None` to stop at any instruction.
+        index: Option<u8>,
+        /// The condition under which to stop, or `None` to stop unconditionally.
+        condition: Option<Condition>,
+    },
+    /// Stop after an instruction accesses watched state.
+    Watchpoint(Watchpoint),
+}
+
+/// An interactive debugger for a Yan85 program loaded in an [`Emulator`].
+pub struct Debugger {
+    /// The emulator running the program being debugged.
+    emulator: Emulator,
+    /// The instruction index that each label refers to.
+    labels: BTreeMap<String, u8>,
+    /// Breakpoints and watchpoints, keyed by number.
+    traps: BTreeMap<usize, Trap>,
+    /// The number of the next breakpoint or watchpoint.
+    next_trap: usize,
+}
+
+impl Debugger {
+    /// Constructs a debugger for the program loaded in `emulator`, resolving `labels` in commands.
+    pub fn new(emulator: Emulator, labels: BTreeMap<String, u8>) -> Self {
+        Self {
+            emulator,
+            labels,
+            traps: BTreeMap::new(),
+            next_trap: 1,
+        }
+    }
+
+    /// Returns the emulator running the program being debugged.
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Reads commands from `input` and executes them until `quit` or the end of input, writing
+    /// responses to `output`.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        self.show_current(&mut output)?;
+        write!(output, "(gyan) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            match self.execute(&line?, &mut output) {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Quit) => return Ok(()),
+                Err(e) => writeln!(output, "error: {e:#}")?,
+            }
+
+            write!(output, "(gyan) ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)?;
+        Ok(())
+    }
+
+    /// Executes a single debugger command, writing its response to `output`.
+    pub fn execute(&mut self, command: &str, output: &mut impl Write) -> Result<Flow> {
+        let mut words = command.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(Flow::Continue);
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (name, args.as_slice()) {
+            ("break" | "b" | "watch" | "w", []) => {
+                for (&number, trap) in &self.traps {
+                    writeln!(output, "{}", self.describe_trap(number, trap))?;
+                }
+            }
+            ("break" | "b", _) => {
+                let trap = self.parse_breakpoint(&args.join(" "))?;
+                self.add_trap(output, trap)?;
+            }
+            ("watch" | "w", [target]) => {
+                let trap = Trap::Watchpoint(Watchpoint {
+                    target: target.parse()?,
+                    kind: WatchKind::Write,
+                });
+                self.add_trap(output, trap)?;
+            }
+            ("watch" | "w", [target, kind]) => {
+                let trap = Trap::Watchpoint(Watchpoint {
+                    target: target.parse()?,
+                    kind: kind.parse()?,
+                });
+                self.add_trap(output, trap)?;
+            }
+            ("delete" | "d", [number]) => {
+                let number: usize = number.parse().context("Invalid breakpoint number")?;
+                if self.traps.remove(&number).is_none() {
+                    bail!("No breakpoint or watchpoint number {number}");
+                }
+            }
+            ("step" | "s", []) => self.resume(output, |_, steps| steps == 1)?,
+            ("step" | "s", [n]) => {
+                let n: u64 = n.parse().context("Invalid step count")?;
+                self.resume(output, |_, steps| steps >= n)?;
+            }
+            ("next" | "n", []) => {
+                let following = self.current_index().wrapping_add(1);
+                self.resume(output, |emulator, _| {
+                    emulator.registers()[Register::I] == following
+                })?;
+            }
+            ("continue" | "c", []) => self.resume(output, |_, _| false)?,
+            ("reverse-step" | "rs", []) => self.reverse(output, |steps| steps == 1)?,
+            ("reverse-step" | "rs", [n]) => {
+                let n: u64 = n.parse().context("Invalid step count")?;
+                self.reverse(output, |steps| steps >= n)?;
+            }
+            ("reverse-continue" | "rc", []) => self.reverse(output, |_| false)?,
+            ("last-write" | "lw", [target]) => {
+                let target: WatchTarget = target.parse()?;
+                match self.emulator.last_write(&target) {
+                    Some(past) => writeln!(
+                        output,
+                        "{} steps ago at {}: {}",
+                        past.steps_ago,
+                        self.describe(past.index),
+                        past.access
+                    )?,
+                    None => writeln!(output, "no recorded write to {target}")?,
+                }
+            }
+            ("regs" | "r", []) => writeln!(output, "{}", self.emulator.registers())?,
+            ("mem" | "m", [address]) => self.show_memory(output, parse_byte(address)?, MEM_LEN)?,
+            ("mem" | "m", [address, len]) => {
+                self.show_memory(output, parse_byte(address)?, parse_byte(len)?)?
+            }
+            ("stack", []) => self.show_stack(output)?,
+            ("set", assignment) => {
+                let assignment = assignment.concat();
+                let (register, value) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected an assignment like `a=0x41`"))?;
+
+                self.emulator.registers_mut()[Registers::by_name(register)?] = parse_byte(value)?;
+            }
+            ("disas" | "x", []) => self.disassemble(output, self.current_index(), DISAS_COUNT)?,
+            ("disas" | "x", [location]) => {
+                self.disassemble(output, self.parse_location(location)?, DISAS_COUNT)?
+            }
+            ("disas" | "x", [location, count]) => {
+                self.disassemble(output, self.parse_location(location)?, parse_byte(count)?)?
+            }
+            ("help" | "h", []) => writeln!(output, "{HELP}")?,
+            ("quit" | "q", []) => return Ok(Flow::Quit),
+            _ => bail!("Unknown command or wrong arguments: {command} (try `help`)"),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Steps the emulator until `done` returns true, a breakpoint is reached, or the program exits
+    /// or faults. `done` receives the emulator and the number of instructions executed so far.
+    fn resume(
+        &mut self,
+        output: &mut impl Write,
+        mut done: impl FnMut(&Emulator, u64) -> bool,
+    ) -> Result<()> {
+        if let Some(exit_code) = self.emulator.exit_code() {
+            bail!("The program has already exited with code {exit_code}");
+        }
+
+        let mut steps = 0;
+
+        loop {
+            if let Err(e) = self.emulator.step() {
+                writeln!(output, "{e:#}")?;
+                return Ok(());
+            }
+            steps += 1;
+
+            if let Some(exit_code) = self.emulator.exit_code() {
+                writeln!(output, "program exited with code {exit_code}")?;
+                return Ok(());
+            }
+
+            if done(&self.emulator, steps) | self.check_traps(output)? {
+                break;
+            }
+        }
+
+        self.show_current(output)
+    }
+
+    /// Undoes steps until `done` returns true, a breakpoint is reached, or there are no recorded steps
+    /// left. `done` receives the number of instructions undone so far. Watchpoints trigger on the
+    /// accesses of undone instructions.
+    fn reverse(
+        &mut self,
+        output: &mut impl Write,
+        mut done: impl FnMut(u64) -> bool,
+    ) -> Result<()> {
+        let mut steps = 0;
+
+        loop {
+            if !self.emulator.step_back()? {
+                writeln!(output, "reached the start of the recorded history")?;
+                break;
+            }
+            steps += 1;
+
+            if done(steps) | self.check_traps(output)? {
+                break;
+            }
+        }
+
+        self.show_current(output)
+    }
+
+    /// Reports the breakpoints and watchpoints triggered by the most recent step, returning whether
+    /// there were any.
+    fn check_traps(&self, output: &mut impl Write) -> Result<bool> {
+        let index = self.current_index();
+        let mut triggered = false;
+
+        for (&number, trap) in &self.traps {
+            match trap {
+                Trap::Breakpoint {
+                    index: breakpoint,
+                    condition,
+                } => {
+                    let at_index = breakpoint.is_none_or(|breakpoint| breakpoint == index);
+                    let holds = condition
+                        .as_ref()
+                        .is_none_or(|condition| condition.evaluate(&self.emulator));
+
+                    if at_index && holds {
+                        writeln!(output, "hit {}", self.describe_trap(number, trap))?;
+                        triggered = true;
+                    }
+                }
+                Trap::Watchpoint(watchpoint) => {
+                    let accesses = self.emulator.last_accesses();
+                    if let Some(access) = watchpoint.triggered_by(accesses) {
+                        let description = self.describe_trap(number, trap);
+                        writeln!(output, "hit {description}: {access}")?;
+                        triggered = true;
+                    }
+                }
+            }
+        }
+
+        Ok(triggered)
+    }
+
+    /// Parses the arguments to `break`: a location, a location and a condition, or a condition.
+    fn parse_breakpoint(&self, args: &str) -> Result<Trap> {
+        let (location, condition) = match args.strip_prefix("if ") {
+            Some(condition) => (None, Some(condition)),
+            None => match args.split_once(" if ") {
+                Some((location, condition)) => (Some(location), Some(condition)),
+                None => (Some(args), None),
+            },
+        };
+
+        Ok(Trap::Breakpoint {
+            index: location
+                .map(|location| self.parse_location(location.trim()))
+                .transpose()?,
+            condition: condition.map(str::parse).transpose()?,
+        })
+    }
+
+    /// Adds a breakpoint or watchpoint, reporting its number.
+    fn add_trap(&mut self, output: &mut impl Write, trap: Trap) -> Result<()> {
+        let number = self.next_trap;
+        self.next_trap += 1;
+
+        writeln!(output, "{}", self.describe_trap(number, &trap))?;
+        self.traps.insert(number, trap);
+
+        Ok(())
+    }
+
+    /// Describes breakpoint or watchpoint number `number`.
+    fn describe_trap(&self, number: usize, trap: &Trap) -> String {
+        match trap {
+            Trap::Breakpoint { index, condition } => {
+                let mut description = format!("breakpoint {number}");
+                if let Some(index) = index {
+                    description += &format!(" at {}", self.describe(*index));
+                }
+                if let Some(condition) = condition {
+                    description += &format!(" if {condition}");
+                }
+
+                description
+            }
+            Trap::Watchpoint(watchpoint) => format!("watchpoint {number} on {watchpoint}"),
+        }
+    }
+
+    /// Shows the instruction that will execute next.
+    fn show_current(&self, output: &mut impl Write) -> Result<()> {
+        self.disassemble(output, self.current_index(), 1)
+    }
+
+    /// Disassembles `count` instructions starting at `start`, marking the current instruction and
+    /// breakpoints.
+    fn disassemble(&self, output: &mut impl Write, start: u8, count: u8) -> Result<()> {
+        let current = self.current_index();
+
+        for index in (start..=u8::MAX).take(count as usize) {
+            let Some(instruction) = self.emulator.instructions().get(index as usize) else {
+                if index == start {
+                    writeln!(output, "=> {index:#04x}: <past the end of the program>")?;
+                }
+                break;
+            };
+
+            for (label, _) in self.labels.iter().filter(|(_, &i)| i == index) {
+                writeln!(output, "{label}:")?;
+            }
+
+            let has_breakpoint = self
+                .traps
+                .values()
+                .any(|trap| matches!(trap, Trap::Breakpoint { index: Some(i), .. } if *i == index));
+
+            let marker = match (index == current, has_breakpoint) {
+                (true, _) => "=>",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            writeln!(output, "{marker} {index:#04x}: {instruction}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows `len` bytes of memory starting at `start` as a hexdump.
+    fn show_memory(&self, output: &mut impl Write, start: u8, len: u8) -> Result<()> {
+        write!(output, "{}", self.emulator.memory().hexdump(start, len))?;
+        Ok(())
+    }
+
+    /// Shows the values on the stack, from the bottom up.
+    fn show_stack(&self, output: &mut impl Write) -> Result<()> {
+        let sp = self.emulator.registers()[Register::S];
+        writeln!(output, "{} values", sp)?;
+
+        for slot in 0..sp {
+            writeln!(output, "{slot:#04x}: {:#04x}", self.emulator.stack()[slot])?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the index of the instruction that will execute next.
+    fn current_index(&self) -> u8 {
+        self.emulator.registers()[Register::I]
+    }
+
+    /// Parses an instruction index or a label name.
+    fn parse_location(&self, location: &str) -> Result<u8> {
+        match self.labels.get(location) {
+            Some(&index) => Ok(index),
+            None => parse_byte(location).map_err(|_| anyhow!("Unknown label: {location}")),
+        }
+    }
+
+    /// Describes the instruction index `index`, including its labels.
+    fn describe(&self, index: u8) -> String {
+        let labels: Vec<&str> = self
+            .labels
+            .iter()
+            .filter(|(_, &i)| i == index)
+            .map(|(label, _)| label.as_str())
+            .collect();
+
+        match labels.as_slice() {
+            [] => format!("{index:#04x}"),
+            _ => format!("{index:#04x} <{}>", labels.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        asm::parse_asm_program,
+        emulator::BufferIo,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    /// Loads `asm` into a debugger whose guest reads `stdin`.
+    fn debugger(asm: &str, stdin: &[u8]) -> Debugger {
+        let program = parse_asm_program(asm.to_string()).unwrap();
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            program.instructions,
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(stdin)));
+        emulator.set_history_size(1000);
+
+        Debugger::new(emulator, program.labels)
+    }
+
+    /// Executes `commands` and returns the debugger's output.
+    fn run(debugger: &mut Debugger, commands: &str) -> String {
+        let mut output = Vec::new();
+        debugger.run(Cursor::new(commands), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    const COUNTER: &str = "
+        IMM a = 0
+        IMM b = 1
+        IMM c = 3
+        loop:
+        ADD a b
+        CMP a c
+        IMM d = loop
+        JMP 0x1 d
+        IMM a = 7
+        SYS 0x20 NONE
+    ";
+
+    #[test]
+    fn test_step() {
+        let mut debugger = debugger(COUNTER, b"");
+        run(&mut debugger, "step\nstep 2\n");
+
+        assert_eq!(debugger.emulator().registers()[Register::I], 3);
+        assert_eq!(debugger.emulator().registers()[Register::C], 3);
+    }
+
+    #[test]
+    fn test_break_on_label() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "break loop\ncontinue\ncontinue\n");
+
+        assert!(output.contains("breakpoint 1 at 0x03 <loop>"));
+        assert!(output.contains("hit breakpoint 1 at 0x03 <loop>"));
+        assert_eq!(debugger.emulator().registers()[Register::A], 1);
+    }
+
+    #[test]
+    fn test_continue_to_exit() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "c\nc\n");
+
+        assert!(output.contains("program exited with code 7"));
+        assert!(output.contains("error: The program has already exited with code 7"));
+    }
+
+    #[test]
+    fn test_next_steps_over_taken_jump() {
+        let mut debugger = debugger(COUNTER, b"");
+        run(&mut debugger, "break 6\nc\ndelete 1\nn\n");
+
+        assert_eq!(debugger.emulator().registers()[Register::I], 7);
+        assert_eq!(debugger.emulator().registers()[Register::A], 3);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "break loop if a == 2\nc\n");
+
+        assert!(output.contains("hit breakpoint 1 at 0x03 <loop> if a == 2"));
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+    }
+
+    #[test]
+    fn test_condition_only_breakpoint() {
+        let mut debugger = debugger(COUNTER, b"");
+        run(&mut debugger, "break if a == 2 && i == 5\nc\n");
+
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+        assert_eq!(debugger.emulator().registers()[Register::I], 5);
+    }
+
+    #[test]
+    fn test_watch_register_change() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "watch c change\nc\nc\n");
+
+        assert!(output.contains("hit watchpoint 1 on c change: write c: 0x00 -> 0x03"));
+        assert!(output.contains("program exited"));
+    }
+
+    #[test]
+    fn test_watch_memory_write() {
+        let mut debugger = debugger(
+            "IMM a = 0\nIMM b = 0x44\nIMM c = 4\nSYS 0x4 d\nIMM a = 0\n",
+            b"AB",
+        );
+        let output = run(&mut debugger, "watch mem[0x40..0x50]\nc\n");
+
+        assert!(output.contains("hit watchpoint 1 on mem[0x40..=0x4f] write"));
+        assert_eq!(debugger.emulator().registers()[Register::I], 4);
+    }
+
+    #[test]
+    fn test_reverse_step() {
+        let mut debugger = debugger(COUNTER, b"");
+        run(&mut debugger, "s 5\nrs 2\n");
+
+        assert_eq!(debugger.emulator().registers()[Register::I], 3);
+        assert_eq!(debugger.emulator().registers()[Register::A], 0);
+    }
+
+    #[test]
+    fn test_reverse_continue_to_breakpoint() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "c\nbreak loop\nrc\n");
+
+        assert!(output.contains("hit breakpoint 1 at 0x03 <loop>"));
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+        assert_eq!(debugger.emulator().exit_code(), None);
+    }
+
+    #[test]
+    fn test_reverse_continue_to_start() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "s 4\nrc\n");
+
+        assert!(output.contains("reached the start of the recorded history"));
+        assert_eq!(debugger.emulator().registers()[Register::I], 0);
+    }
+
+    #[test]
+    fn test_last_write() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "c\nlast-write c\nlast-write stack[0]\n");
+
+        assert!(output.contains("15 steps ago at 0x02: write c: 0x00 -> 0x03"));
+        assert!(output.contains("no recorded write to stack[0x00]"));
+    }
+
+    #[test]
+    fn test_set_register() {
+        let mut debugger = debugger(COUNTER, b"");
+        run(&mut debugger, "set d = 0x41\nset i=2\n");
+
+        assert_eq!(debugger.emulator().registers()[Register::D], 0x41);
+        assert_eq!(debugger.emulator().registers()[Register::I], 2);
+    }
+
+    #[test]
+    fn test_mem() {
+        let mut debugger = debugger(
+            "IMM a = 0\nIMM b = 0x40\nIMM c = 4\nSYS 0x4 d\n",
+            b"AB\x00C",
+        );
+        let output = run(&mut debugger, "step 4\nmem 0x40 4\n");
+
+        assert!(output.contains("0x40: 41 42 00 43"));
+        assert!(output.contains("|AB.C|"));
+    }
+
+    #[test]
+    fn test_stack() {
+        let mut debugger = debugger("IMM a = 0x41\nSTK NONE a\nSTK NONE a\n", b"");
+        let output = run(&mut debugger, "s 3\nstack\n");
+
+        assert!(output.contains("2 values"));
+        assert!(output.contains("0x01: 0x41"));
+    }
+
+    #[test]
+    fn test_fault_stops() {
+        let mut debugger = debugger("STK a NONE\n", b"");
+        let output = run(&mut debugger, "c\n");
+
+        assert!(output.contains("stack underflow"));
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let mut debugger = debugger(COUNTER, b"");
+        let output = run(&mut debugger, "frobnicate\nbreak nowhere\n");
+
+        assert!(output.contains("error: Unknown command"));
+        assert!(output.contains("error: Unknown label: nowhere"));
+    }
+}
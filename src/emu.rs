@@ -1,14 +1,317 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
 
-use crate::emulator::Emulator;
+use crate::{
+    emulator::Emulator,
+    yan85::{
+        instruction::Instruction, memory::Memory, register::Register, registers::Registers,
+        stack::Stack,
+    },
+};
 
-/// Emulates the Yan85 program loaded in `emulator`.
-pub fn emulate(emulator: &mut Emulator, show_disassembly: bool) -> Result<()> {
-    loop {
-        let instruction = emulator.step()?;
+/// Limits on how long [`emulate_until`] runs a program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of steps to emulate.
+    pub max_steps: Option<u64>,
+    /// The maximum wall-clock time to emulate for.
+    pub timeout: Option<Duration>,
+    /// Whether to stop when the program is stuck in an infinite loop.
+    pub detect_loops: bool,
+}
 
+/// Why [`emulate_until`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The program exited with the given exit code.
+    Exited(u8),
+    /// The caller asked to stop.
+    Stopped,
+    /// [`Limits::max_steps`] steps were emulated.
+    MaxSteps,
+    /// [`Limits::timeout`] elapsed.
+    Timeout,
+    /// The machine returned to an earlier state without making a syscall in between, so it will
+    /// repeat the same steps forever.
+    Loop {
+        /// The lowest instruction index in the loop.
+        start: u8,
+        /// The highest instruction index in the loop.
+        end: u8,
+        /// The number of steps in one iteration of the loop.
+        length: u64,
+    },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Exited(exit_code) => write!(f, "exited with code {exit_code}"),
+            StopReason::Stopped => write!(f, "was stopped"),
+            StopReason::MaxSteps => write!(f, "reached the step limit"),
+            StopReason::Timeout => write!(f, "timed out"),
+            StopReason::Loop { start, end, length } => write!(
+                f,
+                "got stuck in an infinite loop of {length} steps between {start:#04x} and {end:#04x}"
+            ),
+        }
+    }
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits, returning its exit code.
+pub fn emulate(emulator: &mut Emulator, show_disassembly: bool) -> Result<u8> {
+    let print = |_: &Emulator, instruction: Instruction| {
         if show_disassembly {
             println!("{instruction}");
         }
+    };
+
+    match emulate_until(emulator, &Limits::default(), |_| false, print)? {
+        StopReason::Exited(exit_code) => Ok(exit_code),
+        reason => unreachable!("emulation without limits {reason}"),
+    }
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits, a limit is reached, or `stop`
+/// returns `true` before a step. `on_step` is called with each instruction after it's emulated.
+pub fn emulate_until(
+    emulator: &mut Emulator,
+    limits: &Limits,
+    mut stop: impl FnMut(&Emulator) -> bool,
+    mut on_step: impl FnMut(&Emulator, Instruction),
+) -> Result<StopReason> {
+    if let Some(exit_code) = emulator.exit_code() {
+        return Ok(StopReason::Exited(exit_code));
+    }
+
+    let start = Instant::now();
+    let mut steps = 0;
+    let mut loops = LoopDetector::default();
+
+    loop {
+        if stop(emulator) {
+            return Ok(StopReason::Stopped);
+        }
+        if limits.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            return Ok(StopReason::MaxSteps);
+        }
+        if limits
+            .timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            return Ok(StopReason::Timeout);
+        }
+
+        let instruction = emulator.step()?;
+        steps += 1;
+        on_step(emulator, instruction);
+
+        if let Some(exit_code) = emulator.exit_code() {
+            return Ok(StopReason::Exited(exit_code));
+        }
+
+        if limits.detect_loops {
+            if let Some(reason) = loops.observe(emulator) {
+                return Ok(reason);
+            }
+        }
+    }
+}
+
+/// The machine state that determines every future step of a program that makes no syscalls.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    /// The registers, including the instruction pointer.
+    registers: Registers,
+    /// The memory.
+    memory: Memory,
+    /// The stack.
+    stack: Stack,
+}
+
+impl State {
+    /// Captures the state of `emulator`.
+    fn of(emulator: &Emulator) -> Self {
+        Self {
+            registers: emulator.registers().clone(),
+            memory: emulator.memory().clone(),
+            stack: emulator.stack().clone(),
+        }
+    }
+
+    /// Hashes the state.
+    fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(self, &mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Finds repeated machine states with Brent's cycle detection, which only keeps one saved state.
+///
+/// The saved state is replaced each time the number of steps since it was saved reaches a power of
+/// two, so a loop is found within a few iterations of the program entering it. Syscalls can make
+/// a repeated state behave differently, so they reset detection.
+#[derive(Debug, Default)]
+struct LoopDetector {
+    /// The saved state and its hash.
+    saved: Option<(u64, State)>,
+    /// The instruction indices executed since the state was saved.
+    indices: Vec<u8>,
+    /// The number of steps after which the saved state is replaced.
+    power: usize,
+}
+
+impl LoopDetector {
+    /// Checks the state after a step, returning the loop if the state repeated.
+    fn observe(&mut self, emulator: &Emulator) -> Option<StopReason> {
+        if emulator.last_syscall().is_some() {
+            self.saved = None;
+            return None;
+        }
+
+        let state = State::of(emulator);
+        let hash = state.hash();
+        self.indices.push(state.registers[Register::I]);
+
+        match &self.saved {
+            Some((saved_hash, saved)) if *saved_hash == hash && *saved == state => {
+                let start = self.indices.iter().copied().min()?;
+                let end = self.indices.iter().copied().max()?;
+
+                Some(StopReason::Loop {
+                    start,
+                    end,
+                    length: self.indices.len() as u64,
+                })
+            }
+            Some(_) if self.indices.len() < self.power => None,
+            _ => {
+                self.power = if self.saved.is_some() {
+                    self.power * 2
+                } else {
+                    1
+                };
+                self.saved = Some((hash, state));
+                self.indices.clear();
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::parse_asm_file, emulator::BufferIo, yan85::constants::Constants};
+
+    /// Constructs an emulator for `asm`.
+    fn emulator(asm: &str) -> Emulator {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file(asm.to_string()).unwrap(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(b"")));
+
+        emulator
+    }
+
+    /// Counts `a` up to 0x10, then exits.
+    const COUNT: &str = "
+        IMM b = 1
+        IMM c = 0x10
+        IMM d = 3
+        ADD a b
+        CMP a c
+        JMP 0x8 d
+        IMM a = 0
+        SYS 0x20 NONE
+    ";
+
+    /// Spins between two instructions forever, with a counter that keeps changing for a while
+    /// before the loop starts.
+    const SPIN: &str = "
+        IMM b = 1
+        IMM c = 0x40
+        IMM d = 3
+        ADD a b
+        CMP a c
+        JMP 0x8 d
+        IMM d = 7
+        IMM a = 1
+        JMP 0x1f d
+    ";
+
+    #[test]
+    fn test_exits() {
+        let reason = emulate_until(
+            &mut emulator(COUNT),
+            &Limits::default(),
+            |_| false,
+            |_, _| (),
+        )
+        .unwrap();
+        assert_eq!(reason, StopReason::Exited(0));
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let limits = Limits {
+            max_steps: Some(5),
+            ..Limits::default()
+        };
+        let mut emulator = emulator(COUNT);
+
+        let reason = emulate_until(&mut emulator, &limits, |_| false, |_, _| ()).unwrap();
+        assert_eq!(reason, StopReason::MaxSteps);
+        assert_eq!(emulator.steps(), 5);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let limits = Limits {
+            timeout: Some(Duration::ZERO),
+            ..Limits::default()
+        };
+
+        let reason = emulate_until(&mut emulator(SPIN), &limits, |_| false, |_, _| ()).unwrap();
+        assert_eq!(reason, StopReason::Timeout);
+    }
+
+    #[test]
+    fn test_loop() {
+        let limits = Limits {
+            detect_loops: true,
+            ..Limits::default()
+        };
+
+        let reason = emulate_until(&mut emulator(SPIN), &limits, |_| false, |_, _| ()).unwrap();
+        assert_eq!(
+            reason,
+            StopReason::Loop {
+                start: 7,
+                end: 8,
+                length: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_counting_isnt_a_loop() {
+        let limits = Limits {
+            detect_loops: true,
+            ..Limits::default()
+        };
+
+        let reason = emulate_until(&mut emulator(COUNT), &limits, |_| false, |_, _| ()).unwrap();
+        assert_eq!(reason, StopReason::Exited(0));
     }
 }
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::yan85::{instruction::Instruction, register::Register};
+
+/// A location in the Yan85 machine state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    /// A register.
+    Register(Register),
+    /// A memory address.
+    Memory(u8),
+    /// A stack slot.
+    Stack(u8),
+}
+
+/// A read or write of guest state made while emulating an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Access {
+    /// `value` was read from `location`.
+    Read {
+        /// The location read.
+        location: Location,
+        /// The value read.
+        value: u8,
+    },
+    /// `location` was changed from `old` to `new`.
+    Write {
+        /// The location written.
+        location: Location,
+        /// The value before the write.
+        old: u8,
+        /// The value after the write.
+        new: u8,
+    },
+}
+
+impl Access {
+    /// Returns the location accessed.
+    pub fn location(&self) -> Location {
+        match *self {
+            Access::Read { location, .. } | Access::Write { location, .. } => location,
+        }
+    }
+}
+
+/// Returns whether a `JMP` was taken, given the accesses made while emulating it, or `None` if
+/// `instruction` isn't a `JMP`.
+pub fn jump_taken(instruction: Instruction, accesses: &[Access]) -> Option<bool> {
+    matches!(instruction, Instruction::JMP(..)).then(|| {
+        accesses.iter().any(|access| {
+            matches!(access, Access::Write { .. })
+                && access.location() == Location::Register(Register::I)
+        })
+    })
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Register(register) => write!(f, "{}", register.name()),
+            Location::Memory(address) => write!(f, "mem[{address:#04x}]"),
+            Location::Stack(slot) => write!(f, "stack[{slot:#04x}]"),
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read { location, value } => write!(f, "read {location} = {value:#04x}"),
+            Access::Write { location, old, new } => {
+                write!(f, "write {location}: {old:#04x} -> {new:#04x}")
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    emulator::{Emulator, FaultPolicy, GuestIo, Region},
+    yan85::{
+        constants::Constants, instruction::Instruction, memory::Memory, register::Register,
+        registers::Registers, stack::Stack,
+    },
+};
+
+/// Constructs an [`Emulator`] with a chosen initial state.
+///
+/// ```
+/// use gyan85::{
+///     emulator::EmulatorBuilder,
+///     yan85::{constants::Constants, instruction::Instruction, register::Register},
+/// };
+///
+/// let emulator = EmulatorBuilder::new(Constants::default())
+///     .code(vec![Instruction::ADD(Register::A, Register::B)])
+///     .register(Register::A, 0x10)
+///     .register(Register::B, 0x20)
+///     .build();
+///
+/// assert_eq!(emulator.registers()[Register::A], 0x10);
+/// ```
+pub struct EmulatorBuilder {
+    /// The emulator being built.
+    emulator: Emulator,
+}
+
+impl EmulatorBuilder {
+    /// Starts building an emulator with no code and zeroed registers, memory, and stack.
+    pub fn new(constants: Constants) -> Self {
+        Self {
+            emulator: Emulator::new(constants, Vec::new(), Memory::default()),
+        }
+    }
+
+    /// Sets the program to emulate.
+    pub fn code(mut self, instructions: Vec<Instruction>) -> Self {
+        self.emulator.instructions = instructions;
+        self
+    }
+
+    /// Sets the initial registers.
+    pub fn registers(mut self, registers: Registers) -> Self {
+        self.emulator.registers = registers;
+        self
+    }
+
+    /// Sets the initial value of one register. [`Register::None`] holds no value, so setting it does
+    /// nothing.
+    pub fn register(mut self, register: Register, value: u8) -> Self {
+        if let Some(slot) = self.emulator.registers.get_mut(register) {
+            *slot = value;
+        }
+        self
+    }
+
+    /// Sets the initial memory.
+    pub fn memory(mut self, memory: Memory) -> Self {
+        self.emulator.memory = memory;
+        self
+    }
+
+    /// Sets the initial stack.
+    pub fn stack(mut self, stack: Stack) -> Self {
+        self.emulator.stack = stack;
+        self
+    }
+
+    /// Sets the guest's standard streams. See [`Emulator::set_io`].
+    pub fn io(mut self, io: Box<dyn GuestIo>) -> Self {
+        self.emulator.set_io(io);
+        self
+    }
+
+    /// Sets how guest faults are handled. See [`Emulator::set_fault_policy`].
+    pub fn fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.emulator.set_fault_policy(policy);
+        self
+    }
+
+    /// Protects a region of memory. See [`Emulator::protect`].
+    pub fn protect(mut self, region: Region) -> Self {
+        self.emulator.protect(region);
+        self
+    }
+
+    /// Sets the number of steps recorded for reverse execution. See
+    /// [`Emulator::set_history_size`].
+    pub fn history_size(mut self, size: usize) -> Self {
+        self.emulator.set_history_size(size);
+        self
+    }
+
+    /// Finishes building the emulator.
+    pub fn build(self) -> Emulator {
+        self.emulator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_state() {
+        let mut memory = Memory::default();
+        memory[0x40] = 0x11;
+        let mut stack = Stack::default();
+        stack[0] = 0x22;
+
+        let mut emulator = EmulatorBuilder::new(Constants::default())
+            .code(vec![
+                Instruction::LDM(Register::B, Register::A),
+                Instruction::STK(Register::C, Register::None),
+            ])
+            .register(Register::A, 0x40)
+            .register(Register::S, 1)
+            .register(Register::None, 0xff)
+            .memory(memory)
+            .stack(stack)
+            .build();
+
+        emulator.step().unwrap();
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.registers()[Register::B], 0x11);
+        assert_eq!(emulator.registers()[Register::C], 0x22);
+        assert_eq!(emulator.registers()[Register::S], 0);
+    }
+}
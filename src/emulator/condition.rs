@@ -0,0 +1,294 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::space0,
+    combinator::{all_consuming, map, opt, value, verify},
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+use crate::{
+    asm::{parse_int_literal, parse_register},
+    emulator::Emulator,
+    yan85::register::Register,
+};
+
+/// A boolean condition on the state of an [`Emulator`], used for conditional breakpoints.
+///
+/// Conditions compare values with `==`, `!=`, `<`, `<=`, `>`, and `>=`, and are combined with
+/// `&&`, `||`, `!`, and parentheses. Values are integer literals, register names, or memory and
+/// stack lookups like `mem[0x40]` and `stack[s]`. A lone value is true if it's nonzero.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::emulator::Condition;
+///
+/// let condition: Condition = "a == 0x7f && i == 0x1c".parse().unwrap();
+/// let condition: Condition = "mem[b] != 0 || !(stack[0] < 3)".parse().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// The condition as written.
+    source: String,
+    /// The parsed condition.
+    expression: Expression,
+}
+
+/// A boolean expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    /// A comparison of two values.
+    Compare(Value, Comparison, Value),
+    /// Whether a value is nonzero.
+    Nonzero(Value),
+    /// Logical negation.
+    Not(Box<Expression>),
+    /// Logical conjunction.
+    And(Box<Expression>, Box<Expression>),
+    /// Logical disjunction.
+    Or(Box<Expression>, Box<Expression>),
+}
+
+/// A comparison operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterEqual,
+}
+
+/// An 8-bit value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    /// An integer literal.
+    Literal(u8),
+    /// The value of a register.
+    Register(Register),
+    /// The value at a memory address.
+    Memory(Box<Value>),
+    /// The value in a stack slot.
+    Stack(Box<Value>),
+}
+
+impl Condition {
+    /// Evaluates the condition against the current state of `emulator`.
+    pub fn evaluate(&self, emulator: &Emulator) -> bool {
+        self.expression.evaluate(emulator)
+    }
+}
+
+impl Expression {
+    /// Evaluates the expression against the current state of `emulator`.
+    fn evaluate(&self, emulator: &Emulator) -> bool {
+        match self {
+            Expression::Compare(a, comparison, b) => {
+                let (a, b) = (a.evaluate(emulator), b.evaluate(emulator));
+
+                match comparison {
+                    Comparison::Equal => a == b,
+                    Comparison::NotEqual => a != b,
+                    Comparison::Less => a < b,
+                    Comparison::LessEqual => a <= b,
+                    Comparison::Greater => a > b,
+                    Comparison::GreaterEqual => a >= b,
+                }
+            }
+            Expression::Nonzero(value) => value.evaluate(emulator) != 0,
+            Expression::Not(expression) => !expression.evaluate(emulator),
+            Expression::And(a, b) => a.evaluate(emulator) && b.evaluate(emulator),
+            Expression::Or(a, b) => a.evaluate(emulator) || b.evaluate(emulator),
+        }
+    }
+}
+
+impl Value {
+    /// Evaluates the value against the current state of `emulator`.
+    fn evaluate(&self, emulator: &Emulator) -> u8 {
+        match self {
+            Value::Literal(value) => *value,
+            Value::Register(register) => emulator.registers()[*register],
+            Value::Memory(address) => emulator.memory()[address.evaluate(emulator)],
+            Value::Stack(slot) => emulator.stack()[slot.evaluate(emulator)],
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let (_, expression) = all_consuming(delimited(space0, parse_or, space0))(source)
+            .map_err(|e| anyhow!("Unable to parse condition: {e}"))?;
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            expression,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Parses `parser`, ignoring surrounding spaces.
+fn spaced<'a, O>(
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    delimited(space0, parser, space0)
+}
+
+/// Parses a disjunction of conjunctions.
+fn parse_or(input: &str) -> IResult<&str, Expression> {
+    let (remaining, (first, rest)) = pair(parse_and, many0(preceded(tag("||"), parse_and)))(input)?;
+
+    let expression = rest
+        .into_iter()
+        .fold(first, |a, b| Expression::Or(Box::new(a), Box::new(b)));
+
+    Ok((remaining, expression))
+}
+
+/// Parses a conjunction of negations.
+fn parse_and(input: &str) -> IResult<&str, Expression> {
+    let (remaining, (first, rest)) = pair(parse_not, many0(preceded(tag("&&"), parse_not)))(input)?;
+
+    let expression = rest
+        .into_iter()
+        .fold(first, |a, b| Expression::And(Box::new(a), Box::new(b)));
+
+    Ok((remaining, expression))
+}
+
+/// Parses a possibly negated comparison or parenthesized expression.
+fn parse_not(input: &str) -> IResult<&str, Expression> {
+    spaced(alt((
+        map(preceded(tag("!"), parse_not), |expression| {
+            Expression::Not(Box::new(expression))
+        }),
+        delimited(tag("("), parse_or, tag(")")),
+        parse_comparison,
+    )))(input)
+}
+
+/// Parses a comparison of two values, or a lone value.
+fn parse_comparison(input: &str) -> IResult<&str, Expression> {
+    let (remaining, (a, comparison)) = pair(
+        spaced(parse_value),
+        opt(pair(spaced(parse_comparison_operator), spaced(parse_value))),
+    )(input)?;
+
+    let expression = match comparison {
+        Some((comparison, b)) => Expression::Compare(a, comparison, b),
+        None => Expression::Nonzero(a),
+    };
+
+    Ok((remaining, expression))
+}
+
+/// Parses a comparison operator.
+fn parse_comparison_operator(input: &str) -> IResult<&str, Comparison> {
+    alt((
+        value(Comparison::Equal, tag("==")),
+        value(Comparison::NotEqual, tag("!=")),
+        value(Comparison::LessEqual, tag("<=")),
+        value(Comparison::GreaterEqual, tag(">=")),
+        value(Comparison::Less, tag("<")),
+        value(Comparison::Greater, tag(">")),
+    ))(input)
+}
+
+/// Parses an integer literal, register, or memory or stack lookup.
+fn parse_value(input: &str) -> IResult<&str, Value> {
+    alt((
+        map(
+            tuple((tag("mem"), spaced(tag("[")), parse_value, spaced(tag("]")))),
+            |(_, _, address, _)| Value::Memory(Box::new(address)),
+        ),
+        map(
+            tuple((
+                tag("stack"),
+                spaced(tag("[")),
+                parse_value,
+                spaced(tag("]")),
+            )),
+            |(_, _, slot, _)| Value::Stack(Box::new(slot)),
+        ),
+        map(parse_int_literal, Value::Literal),
+        map(
+            verify(parse_register, |register| *register != Register::None),
+            Value::Register,
+        ),
+    ))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yan85::{constants::Constants, memory::Memory};
+
+    /// Evaluates `condition` against an emulator in the given state.
+    fn evaluate(condition: &str, a: u8, memory: &[(u8, u8)]) -> bool {
+        let mut emulator = Emulator::new(Constants::default(), vec![], Memory::default());
+        emulator.registers_mut()[Register::A] = a;
+        emulator.registers_mut()[Register::I] = 0x1c;
+
+        for &(address, value) in memory {
+            emulator.memory_mut()[address] = value;
+        }
+
+        condition.parse::<Condition>().unwrap().evaluate(&emulator)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(evaluate("a == 0x7f", 0x7f, &[]));
+        assert!(evaluate("a != 0x7f", 0x7e, &[]));
+        assert!(evaluate("a < 3", 2, &[]));
+        assert!(evaluate("a <= 3", 3, &[]));
+        assert!(evaluate("a > 3", 4, &[]));
+        assert!(!evaluate("a >= 3", 2, &[]));
+    }
+
+    #[test]
+    fn test_logic() {
+        assert!(evaluate("a == 0x7f && i == 0x1c", 0x7f, &[]));
+        assert!(!evaluate("a == 0x7f && i == 0x1d", 0x7f, &[]));
+        assert!(evaluate("a == 1 || i == 0x1c", 0, &[]));
+        assert!(evaluate("!(a == 1)", 0, &[]));
+        assert!(evaluate("a", 1, &[]));
+        assert!(!evaluate("!a", 1, &[]));
+    }
+
+    #[test]
+    fn test_memory() {
+        assert!(evaluate("mem[0x40] == 0x41", 0, &[(0x40, 0x41)]));
+        assert!(evaluate("mem[a] == 0x41", 0x40, &[(0x40, 0x41)]));
+        assert!(evaluate("mem[mem[0]] == 7", 0, &[(0, 9), (9, 7)]));
+        assert!(evaluate("stack[0] == 0", 0, &[]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!("a ==".parse::<Condition>().is_err());
+        assert!("a = 1".parse::<Condition>().is_err());
+        assert!("NONE == 1".parse::<Condition>().is_err());
+        assert!("(a == 1".parse::<Condition>().is_err());
+    }
+}
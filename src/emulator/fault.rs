@@ -0,0 +1,103 @@
+use std::{error, fmt};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{emulator::Protection, yan85::registers::Registers};
+
+/// A guest behavior that the emulator can't carry out.
+///
+/// Faults are returned as [`anyhow::Error`]s and can be recovered with
+/// [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    /// The index of the faulting instruction.
+    pub index: u8,
+    /// The registers at the time of the fault.
+    pub registers: Registers,
+    /// What went wrong.
+    pub kind: FaultKind,
+}
+
+/// The kinds of [`Fault`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// A `SYS` instruction with a syscall number that is neither built in nor registered.
+    UnknownSyscall(u8),
+    /// Execution ran past the last instruction of the program.
+    InstructionOutOfBounds,
+    /// A push onto a full stack.
+    StackOverflow,
+    /// A pop from an empty stack.
+    StackUnderflow,
+    /// A syscall accessed memory past its end.
+    MemoryOutOfBounds {
+        /// The start of the accessed range.
+        start: u8,
+        /// The size of the accessed range.
+        size: u8,
+    },
+    /// [`Register::None`](crate::yan85::register::Register::None) was used outside of a `STK`
+    /// instruction.
+    InvalidRegister,
+    /// An access to a protected region of memory that its protection forbids.
+    ProtectionViolation {
+        /// The first address accessed in violation of the protection.
+        address: u8,
+        /// Whether the access was a write.
+        write: bool,
+        /// The protection violated.
+        protection: Protection,
+    },
+}
+
+/// How the emulator handles guest behaviors that have no sensible meaning, such as popping from an
+/// empty stack.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FaultPolicy {
+    /// Wrap around like the reference VM does.
+    Wrap,
+    /// Stop emulating and return a [`Fault`].
+    #[default]
+    Stop,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "fault at instruction {:#04x}: {}", self.index, self.kind)?;
+        write!(f, "registers: {}", self.registers)
+    }
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaultKind::UnknownSyscall(syscall) => write!(f, "unknown syscall {syscall:#04x}"),
+            FaultKind::InstructionOutOfBounds => write!(f, "instruction index out of bounds"),
+            FaultKind::StackOverflow => write!(f, "stack overflow"),
+            FaultKind::StackUnderflow => write!(f, "stack underflow"),
+            FaultKind::MemoryOutOfBounds { start, size } => {
+                write!(
+                    f,
+                    "memory access of {size} bytes at {start:#04x} out of bounds"
+                )
+            }
+            FaultKind::InvalidRegister => write!(f, "invalid use of the NONE register"),
+            FaultKind::ProtectionViolation {
+                address,
+                write,
+                protection,
+            } => {
+                let access = match (write, protection) {
+                    (true, Protection::WriteOnce) => "second write to",
+                    (true, _) => "write to",
+                    (false, _) => "read of",
+                };
+                write!(f, "{access} {protection} memory at {address:#04x}")
+            }
+        }
+    }
+}
+
+impl error::Error for Fault {}
@@ -0,0 +1,352 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Flags passed to the `OPEN` syscall, interpreted like those of Linux's `open(2)`.
+///
+/// Yan85 registers are 8 bits wide, so a guest can only ever set the low byte. Flags like
+/// [`OpenFlags::TRUNC`] and [`OpenFlags::APPEND`] are still decoded so that the flags can be
+/// constructed from the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenFlags(pub u32);
+
+impl OpenFlags {
+    /// Open for reading only.
+    pub const RDONLY: u32 = 0o0;
+    /// Open for writing only.
+    pub const WRONLY: u32 = 0o1;
+    /// Open for reading and writing.
+    pub const RDWR: u32 = 0o2;
+    /// Mask of the access mode bits.
+    pub const ACCMODE: u32 = 0o3;
+    /// Create the file if it doesn't exist.
+    pub const CREAT: u32 = 0o100;
+    /// Together with [`OpenFlags::CREAT`], fail if the file already exists.
+    pub const EXCL: u32 = 0o200;
+    /// Truncate the file to length zero.
+    pub const TRUNC: u32 = 0o1000;
+    /// Append all writes to the end of the file.
+    pub const APPEND: u32 = 0o2000;
+
+    /// The non-access-mode flags that are understood, along with their names.
+    const NAMED: [(u32, &'static str); 4] = [
+        (Self::CREAT, "O_CREAT"),
+        (Self::EXCL, "O_EXCL"),
+        (Self::TRUNC, "O_TRUNC"),
+        (Self::APPEND, "O_APPEND"),
+    ];
+
+    /// Whether `flag` is set.
+    pub fn contains(self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// Converts the flags and file creation `mode` into host [`OpenOptions`].
+    ///
+    /// The access mode selects read and write access, and every other flag is passed to the host's
+    /// `open(2)` as is, so combinations like `O_RDONLY|O_CREAT` behave like they do on Linux.
+    pub fn options(self, mode: u32) -> io::Result<OpenOptions> {
+        let (read, write) = match self.0 & Self::ACCMODE {
+            Self::RDONLY => (true, false),
+            Self::WRONLY => (false, true),
+            Self::RDWR => (true, true),
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        let mut options = OpenOptions::new();
+        options
+            .read(read)
+            .write(write)
+            .custom_flags((self.0 & !Self::ACCMODE) as i32)
+            .mode(mode);
+
+        Ok(options)
+    }
+}
+
+impl fmt::Display for OpenFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 & Self::ACCMODE {
+            Self::RDONLY => write!(f, "O_RDONLY")?,
+            Self::WRONLY => write!(f, "O_WRONLY")?,
+            Self::RDWR => write!(f, "O_RDWR")?,
+            mode => write!(f, "{mode:#x}")?,
+        }
+
+        let mut unknown = self.0 & !Self::ACCMODE;
+        for (flag, name) in Self::NAMED {
+            if self.contains(flag) {
+                write!(f, "|{name}")?;
+                unknown &= !flag;
+            }
+        }
+
+        if unknown != 0 {
+            write!(f, "|{unknown:#x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A file opened by the guest.
+#[derive(Debug)]
+struct OpenFile {
+    /// The host file.
+    file: File,
+    /// The path the file was opened with.
+    path: PathBuf,
+    /// The flags the file was opened with.
+    flags: OpenFlags,
+}
+
+/// The serializable state of a file opened by the guest, as saved in a
+/// [`Snapshot`](super::Snapshot).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    /// The file descriptor.
+    pub fd: u8,
+    /// The path the file was opened with.
+    pub path: PathBuf,
+    /// The flags the file was opened with.
+    pub flags: OpenFlags,
+    /// The file offset.
+    pub offset: u64,
+}
+
+/// The guest's file descriptor table.
+///
+/// File descriptors 0, 1, and 2 are reserved for the guest's standard streams, which are handled by
+/// [`GuestIo`](super::GuestIo). Files opened by the guest are assigned the lowest free descriptor,
+/// like on Linux.
+#[derive(Debug, Default)]
+pub(crate) struct FileTable {
+    /// Files opened by the guest, keyed by file descriptor.
+    files: BTreeMap<u8, OpenFile>,
+}
+
+impl FileTable {
+    /// The first file descriptor that isn't a standard stream.
+    const FIRST_FD: u8 = 3;
+
+    /// Opens the file at `path` and returns its new file descriptor.
+    pub fn open(&mut self, path: &Path, flags: OpenFlags, mode: u32) -> io::Result<u8> {
+        let fd = (Self::FIRST_FD..=u8::MAX)
+            .find(|fd| !self.files.contains_key(fd))
+            .ok_or_else(|| io::Error::other("too many open files"))?;
+
+        let file = flags.options(mode)?.open(path)?;
+        self.files.insert(
+            fd,
+            OpenFile {
+                file,
+                path: path.to_path_buf(),
+                flags,
+            },
+        );
+
+        Ok(fd)
+    }
+
+    /// Reads from the file with descriptor `fd` into `buffer`.
+    pub fn read(&mut self, fd: u8, buffer: &mut [u8]) -> io::Result<usize> {
+        self.file(fd)?.read(buffer)
+    }
+
+    /// Writes `buffer` to the file with descriptor `fd`.
+    pub fn write(&mut self, fd: u8, buffer: &[u8]) -> io::Result<usize> {
+        self.file(fd)?.write(buffer)
+    }
+
+    /// Closes the file with descriptor `fd`.
+    pub fn close(&mut self, fd: u8) -> io::Result<()> {
+        self.files.remove(&fd).map(drop).ok_or_else(|| bad_fd(fd))
+    }
+
+    /// Moves the offset of the file with descriptor `fd` back by `len` bytes.
+    pub fn rewind(&mut self, fd: u8, len: usize) -> io::Result<()> {
+        let len = i64::try_from(len).map_err(io::Error::other)?;
+        self.file(fd)?.seek(SeekFrom::Current(-len))?;
+
+        Ok(())
+    }
+
+    /// Returns the state of each open file.
+    pub fn states(&self) -> io::Result<Vec<FileState>> {
+        self.files
+            .iter()
+            .map(|(&fd, open)| {
+                Ok(FileState {
+                    fd,
+                    path: open.path.clone(),
+                    flags: open.flags,
+                    offset: (&open.file).stream_position()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Reopens the files described by `states`.
+    ///
+    /// The files are reopened without [`OpenFlags::CREAT`], [`OpenFlags::EXCL`], and
+    /// [`OpenFlags::TRUNC`], so they must still exist, and their contents are left as is.
+    pub fn restore(states: &[FileState]) -> io::Result<Self> {
+        let mut files = BTreeMap::new();
+
+        for state in states {
+            let creation = OpenFlags::CREAT | OpenFlags::EXCL | OpenFlags::TRUNC;
+            let flags = OpenFlags(state.flags.0 & !creation);
+
+            let mut file = flags.options(0)?.open(&state.path)?;
+            file.seek(SeekFrom::Start(state.offset))?;
+
+            files.insert(
+                state.fd,
+                OpenFile {
+                    file,
+                    path: state.path.clone(),
+                    flags: state.flags,
+                },
+            );
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Looks up the file opened with descriptor `fd`.
+    fn file(&mut self, fd: u8) -> io::Result<&mut File> {
+        self.files
+            .get_mut(&fd)
+            .map(|open| &mut open.file)
+            .ok_or_else(|| bad_fd(fd))
+    }
+}
+
+/// Constructs the error for an unknown file descriptor.
+fn bad_fd(fd: u8) -> io::Error {
+    io::Error::other(format!("bad file descriptor: {fd}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_rdonly() {
+        assert_eq!(OpenFlags(0).to_string(), "O_RDONLY");
+    }
+
+    #[test]
+    fn test_display_flags() {
+        let flags = OpenFlags(OpenFlags::WRONLY | OpenFlags::CREAT | OpenFlags::TRUNC);
+        assert_eq!(flags.to_string(), "O_WRONLY|O_CREAT|O_TRUNC");
+    }
+
+    #[test]
+    fn test_display_unknown_bits() {
+        assert_eq!(OpenFlags(OpenFlags::RDWR | 0x4).to_string(), "O_RDWR|0x4");
+    }
+
+    #[test]
+    fn test_invalid_access_mode() {
+        assert!(OpenFlags(OpenFlags::ACCMODE).options(0).is_err());
+    }
+
+    /// Returns a path in the temporary directory that doesn't exist yet.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("gyan85-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        path
+    }
+
+    #[test]
+    fn test_rdonly_creat() {
+        let path = temp_path("rdonly-creat");
+        let mut files = FileTable::default();
+
+        let flags = OpenFlags(OpenFlags::RDONLY | OpenFlags::CREAT);
+        let fd = files.open(&path, flags, 0o644).unwrap();
+        assert!(path.exists());
+        assert!(files.write(fd, b"x").is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rdonly_append() {
+        let path = temp_path("rdonly-append");
+        std::fs::write(&path, b"hi").unwrap();
+        let mut files = FileTable::default();
+
+        let flags = OpenFlags(OpenFlags::RDONLY | OpenFlags::APPEND);
+        let fd = files.open(&path, flags, 0).unwrap();
+        assert!(files.write(fd, b"x").is_err());
+
+        let mut buffer = [0; 2];
+        files.read(fd, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"hi");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_lowest_free_fd() {
+        let mut files = FileTable::default();
+        let path = Path::new("constants.yml");
+
+        assert_eq!(files.open(path, OpenFlags(0), 0).unwrap(), 3);
+        assert_eq!(files.open(path, OpenFlags(0), 0).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_close_and_rewind() {
+        let mut files = FileTable::default();
+        let fd = files
+            .open(Path::new("constants.yml"), OpenFlags(0), 0)
+            .unwrap();
+
+        let (mut first, mut again) = ([0; 4], [0; 4]);
+        files.read(fd, &mut first).unwrap();
+        files.rewind(fd, 4).unwrap();
+        files.read(fd, &mut again).unwrap();
+        assert_eq!(first, again);
+
+        files.close(fd).unwrap();
+        assert!(files.read(fd, &mut again).is_err());
+        assert!(files.close(fd).is_err());
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut files = FileTable::default();
+        let fd = files
+            .open(Path::new("constants.yml"), OpenFlags(0), 0)
+            .unwrap();
+        files.read(fd, &mut [0; 4]).unwrap();
+
+        let states = files.states().unwrap();
+        assert_eq!(states[0].offset, 4);
+
+        let mut restored = FileTable::restore(&states).unwrap();
+        let (mut expected, mut actual) = ([0; 4], [0; 4]);
+        files.read(fd, &mut expected).unwrap();
+        restored.read(fd, &mut actual).unwrap();
+
+        assert_eq!(actual, expected);
+        assert_eq!(restored.states().unwrap(), files.states().unwrap());
+    }
+
+    #[test]
+    fn test_bad_fd() {
+        let mut files = FileTable::default();
+        assert!(files.write(7, b"hello").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    cell::RefCell,
+    io::{self, Cursor, Read, Write},
+    rc::Rc,
+};
+
+/// The guest's standard streams, i.e. file descriptors 0, 1, and 2.
+pub trait GuestIo {
+    /// Reads from the guest's standard input into `buffer`, returning the number of bytes read.
+    fn read_stdin(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+
+    /// Writes `buffer` to the guest's standard output, returning the number of bytes written.
+    fn write_stdout(&mut self, buffer: &[u8]) -> io::Result<usize>;
+
+    /// Writes `buffer` to the guest's standard error, returning the number of bytes written.
+    /// Defaults to the host's standard error.
+    fn write_stderr(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        write_flushed(io::stderr(), buffer)
+    }
+}
+
+/// The host terminal's standard streams.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HostIo;
+
+impl GuestIo for HostIo {
+    fn read_stdin(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        io::stdin().read(buffer)
+    }
+
+    fn write_stdout(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        write_flushed(io::stdout(), buffer)
+    }
+}
+
+/// Scripted standard input and captured standard output, both held in memory.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::emulator::BufferIo;
+///
+/// let io = BufferIo::new(b"AAAA".to_vec());
+/// let stdout = io.stdout();
+///
+/// // hand `io` to an emulator and run it, then inspect `stdout.contents()`
+/// assert!(stdout.contents().is_empty());
+/// ```
+#[derive(Debug, Default)]
+pub struct BufferIo {
+    /// The remaining standard input.
+    stdin: Cursor<Vec<u8>>,
+    /// Everything written to standard output so far.
+    stdout: SharedBuffer,
+}
+
+impl BufferIo {
+    /// Constructs buffered I/O that supplies `stdin` as the guest's standard input.
+    pub fn new(stdin: impl Into<Vec<u8>>) -> Self {
+        Self {
+            stdin: Cursor::new(stdin.into()),
+            stdout: SharedBuffer::default(),
+        }
+    }
+
+    /// Returns a handle to the captured standard output that remains valid after `self` is moved
+    /// into an emulator.
+    pub fn stdout(&self) -> SharedBuffer {
+        self.stdout.clone()
+    }
+}
+
+impl GuestIo for BufferIo {
+    fn read_stdin(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buffer)
+    }
+
+    fn write_stdout(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buffer)
+    }
+}
+
+/// Standard streams backed by arbitrary readers and writers, such as files.
+#[derive(Debug)]
+pub struct StreamIo<R, W> {
+    /// The source of standard input.
+    stdin: R,
+    /// The destination of standard output.
+    stdout: W,
+}
+
+impl<R: Read, W: Write> StreamIo<R, W> {
+    /// Constructs stream-backed I/O reading from `stdin` and writing to `stdout`.
+    pub fn new(stdin: R, stdout: W) -> Self {
+        Self { stdin, stdout }
+    }
+}
+
+impl<R: Read, W: Write> GuestIo for StreamIo<R, W> {
+    fn read_stdin(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buffer)
+    }
+
+    fn write_stdout(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        write_flushed(&mut self.stdout, buffer)
+    }
+}
+
+/// A byte buffer that can be shared between an emulator and its owner.
+#[derive(Debug, Default, Clone)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Returns a copy of the bytes written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    /// Removes and returns the bytes written so far.
+    pub fn take(&self) -> Vec<u8> {
+        self.0.take()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes `buffer` to `stream`, flushing immediately so that output interleaves with reads.
+fn write_flushed(mut stream: impl Write, buffer: &[u8]) -> io::Result<usize> {
+    let n = stream.write(buffer)?;
+    stream.flush()?;
+
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_io_stdin() {
+        let mut io = BufferIo::new(b"abc".to_vec());
+        let mut buffer = [0; 2];
+
+        assert_eq!(io.read_stdin(&mut buffer).unwrap(), 2);
+        assert_eq!(&buffer, b"ab");
+        assert_eq!(io.read_stdin(&mut buffer).unwrap(), 1);
+        assert_eq!(io.read_stdin(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_buffer_io_stdout() {
+        let mut io = BufferIo::default();
+        let stdout = io.stdout();
+
+        io.write_stdout(b"hello ").unwrap();
+        io.write_stdout(b"world").unwrap();
+
+        assert_eq!(stdout.contents(), b"hello world");
+    }
+
+    #[test]
+    fn test_stream_io() {
+        let mut io = StreamIo::new(&b"xyz"[..], Vec::new());
+        let mut buffer = [0; 3];
+
+        io.read_stdin(&mut buffer).unwrap();
+        io.write_stdout(&buffer).unwrap();
+
+        assert_eq!(io.stdout, b"xyz");
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use crate::emulator::{Access, WatchTarget};
+
+/// An effect of a step on the guest's file descriptors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FdEffect {
+    /// Bytes were consumed from standard input.
+    Stdin(Vec<u8>),
+    /// Bytes were written to standard output or standard error.
+    Output {
+        /// The file descriptor written to.
+        fd: u8,
+        /// The bytes written.
+        bytes: Vec<u8>,
+    },
+    /// A file was opened with the given file descriptor.
+    Open(u8),
+    /// The offset of the file with descriptor `fd` advanced by `len` bytes.
+    Seek {
+        /// The file descriptor read or written.
+        fd: u8,
+        /// The number of bytes read or written.
+        len: usize,
+    },
+}
+
+/// The changes made by a single step, recorded so that the step can be undone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Delta {
+    /// The index of the instruction executed, i.e. the value of register I before the step.
+    pub index: u8,
+    /// The registers, memory, and stack accessed by the step, in order.
+    pub accesses: Vec<Access>,
+    /// The step's effects on file descriptors, in order.
+    pub fd_effects: Vec<FdEffect>,
+    /// The program's exit code before the step.
+    pub exit_code: Option<u8>,
+    /// Whether execution had fallen through from index 0xff before the step.
+    pub fell_through: bool,
+}
+
+/// A past write found by [`Emulator::last_write`](super::Emulator::last_write).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PastWrite {
+    /// How many steps ago the write happened, where 1 is the most recent step.
+    pub steps_ago: usize,
+    /// The index of the instruction that made the write.
+    pub index: u8,
+    /// The write itself.
+    pub access: Access,
+}
+
+/// A bounded record of the most recent steps, oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    /// The recorded steps.
+    deltas: VecDeque<Delta>,
+    /// The maximum number of steps recorded. Zero disables recording.
+    capacity: usize,
+}
+
+impl History {
+    /// Returns the maximum number of steps recorded.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the maximum number of steps recorded, forgetting the oldest steps if necessary.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    /// Returns the number of steps recorded.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Forgets every recorded step.
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+    }
+
+    /// Records a step, forgetting the oldest step if the history is full.
+    pub fn push(&mut self, delta: Delta) {
+        if self.capacity > 0 {
+            self.deltas.push_back(delta);
+            self.truncate();
+        }
+    }
+
+    /// Removes and returns the most recent step.
+    pub fn pop(&mut self) -> Option<Delta> {
+        self.deltas.pop_back()
+    }
+
+    /// Finds the most recent write to a location in `target`.
+    pub fn last_write(&self, target: &WatchTarget) -> Option<PastWrite> {
+        self.deltas
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(steps_ago, delta)| {
+                let access = delta.accesses.iter().rev().find(|access| {
+                    matches!(access, Access::Write { .. }) && target.contains(access.location())
+                })?;
+
+                Some(PastWrite {
+                    steps_ago: steps_ago + 1,
+                    index: delta.index,
+                    access: *access,
+                })
+            })
+    }
+
+    /// Forgets the oldest steps until the history fits its capacity.
+    fn truncate(&mut self) {
+        while self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emulator::Location, yan85::register::Register};
+
+    /// Constructs a step at `index` that writes `new` to `location`.
+    fn write(index: u8, location: Location, new: u8) -> Delta {
+        Delta {
+            index,
+            accesses: vec![Access::Write {
+                location,
+                old: 0,
+                new,
+            }],
+            fd_effects: vec![],
+            exit_code: None,
+            fell_through: false,
+        }
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut history = History::default();
+        history.set_capacity(2);
+
+        for index in 0..5 {
+            history.push(write(index, Location::Register(Register::A), index));
+        }
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.pop().unwrap().index, 4);
+        assert_eq!(history.pop().unwrap().index, 3);
+        assert!(history.pop().is_none());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut history = History::default();
+        history.push(write(0, Location::Register(Register::A), 1));
+
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn test_last_write() {
+        let mut history = History::default();
+        history.set_capacity(10);
+
+        history.push(write(0, Location::Memory(0x40), 1));
+        history.push(write(1, Location::Memory(0x41), 2));
+        history.push(write(2, Location::Register(Register::A), 3));
+
+        let target = WatchTarget::Memory(0x40..=0x4f);
+        let past = history.last_write(&target).unwrap();
+
+        assert_eq!(past.steps_ago, 2);
+        assert_eq!(past.index, 1);
+        assert_eq!(past.access.location(), Location::Memory(0x41));
+
+        assert!(history.last_write(&WatchTarget::Stack(0..=0)).is_none());
+    }
+}
@@ -0,0 +1,1706 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsStr,
+    io::{self, Read},
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    asm::assemble,
+    disasm::disassemble,
+    yan85::{
+        constants::Constants, instruction::Instruction, memory::Memory, register::Register,
+        registers::Registers, stack::Stack,
+    },
+};
+
+/// Records of the guest state accessed by each instruction.
+mod access;
+/// Construction of emulators with a chosen initial state.
+mod builder;
+/// Boolean conditions on the machine state.
+mod condition;
+/// Errors caused by guest behavior.
+mod fault;
+/// The guest's file layer.
+mod files;
+/// The guest's standard streams.
+mod guest_io;
+/// Records of past steps for reverse execution.
+mod history;
+/// Protected regions of memory.
+mod protect;
+/// Saved emulator state.
+mod snapshot;
+/// User-supplied syscalls.
+mod syscall;
+/// Helpers for tests that run assembly programs.
+#[cfg(test)]
+pub(crate) mod testing;
+/// Watchpoints on guest state accesses.
+mod watch;
+
+pub use access::{jump_taken, Access, Location};
+pub use builder::EmulatorBuilder;
+pub use condition::Condition;
+pub use fault::{Fault, FaultKind, FaultPolicy};
+use files::FileTable;
+pub use files::{FileState, OpenFlags};
+pub use guest_io::{BufferIo, GuestIo, HostIo, SharedBuffer, StreamIo};
+pub use history::PastWrite;
+use history::{Delta, FdEffect, History};
+use protect::Protections;
+pub use protect::{Protection, Region};
+pub use snapshot::Snapshot;
+pub use syscall::{Syscall, SyscallHandler};
+pub use watch::{WatchKind, WatchTarget, Watchpoint};
+
+/// The value a syscall returns upon failure, i.e. -1 as an unsigned byte.
+pub const SYSCALL_FAILURE: u8 = u8::MAX;
+
+/// A Yan85 emulator.
+pub struct Emulator {
+    /// Encoding constants.
+    constants: Constants,
+    /// Instructions to emulate.
+    instructions: Vec<Instruction>,
+    /// The Yan85 registers.
+    registers: Registers,
+    /// The Yan85 stack.
+    stack: Stack,
+    /// The Yan85 memory.
+    memory: Memory,
+    /// Files opened by the guest.
+    files: FileTable,
+    /// The guest's standard streams.
+    io: Box<dyn GuestIo>,
+    /// The exit code passed to the `EXIT` syscall, once the program has exited.
+    exit_code: Option<u8>,
+    /// User-supplied syscalls, keyed by syscall number.
+    syscall_handlers: HashMap<u8, Box<dyn SyscallHandler>>,
+    /// The index of the instruction being emulated.
+    index: u8,
+    /// The number of steps taken.
+    steps: u64,
+    /// How to handle guest behaviors that have no sensible meaning.
+    fault_policy: FaultPolicy,
+    /// The guest state accessed by the most recent step.
+    accesses: Vec<Access>,
+    /// The effects of the most recent step on file descriptors.
+    fd_effects: Vec<FdEffect>,
+    /// Recent steps, for undoing them.
+    history: History,
+    /// Standard input consumed by undone steps, to be read again before any new input.
+    unread_stdin: VecDeque<u8>,
+    /// The syscall made by the most recent step, if any.
+    syscall: Option<Syscall>,
+    /// Output written by undone steps, most recently undone last. Re-executing a step suppresses
+    /// the output it already wrote.
+    rewound_output: Vec<(u8, Vec<u8>)>,
+    /// Protected regions of memory.
+    protections: Protections,
+    /// The wall-clock time the most recent step spent in host I/O.
+    io_time: Duration,
+    /// Whether the most recent step ran the instruction at index 0xff without jumping, so that
+    /// execution wrapped around to index 0.
+    fell_through: bool,
+}
+
+impl Emulator {
+    /// Constructs a new emulator instance.
+    pub fn new(constants: Constants, instructions: Vec<Instruction>, memory: Memory) -> Self {
+        Self {
+            constants,
+            instructions,
+            registers: Registers::default(),
+            stack: Stack::default(),
+            memory,
+            files: FileTable::default(),
+            io: Box::new(HostIo),
+            exit_code: None,
+            syscall_handlers: HashMap::new(),
+            index: 0,
+            steps: 0,
+            fault_policy: FaultPolicy::default(),
+            accesses: Vec::new(),
+            fd_effects: Vec::new(),
+            history: History::default(),
+            unread_stdin: VecDeque::new(),
+            rewound_output: Vec::new(),
+            syscall: None,
+            protections: Protections::default(),
+            io_time: Duration::ZERO,
+            fell_through: false,
+        }
+    }
+
+    /// Registers `handler` as the implementation of syscall number `syscall`, replacing the
+    /// built-in syscall with that number if there is one.
+    pub fn register_syscall(&mut self, syscall: u8, handler: Box<dyn SyscallHandler>) {
+        self.syscall_handlers.insert(syscall, handler);
+    }
+
+    /// Replaces the guest's standard streams, which default to those of the host terminal.
+    pub fn set_io(&mut self, io: Box<dyn GuestIo>) {
+        self.io = io;
+    }
+
+    /// Returns the program's exit code if it has exited.
+    pub fn exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    /// Returns the encoding constants.
+    pub fn constants(&self) -> Constants {
+        self.constants
+    }
+
+    /// Returns the instructions being emulated.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the Yan85 registers.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Returns the Yan85 registers for modification.
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    /// Returns the Yan85 stack.
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// Returns the Yan85 stack for modification.
+    pub fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.stack
+    }
+
+    /// Returns the Yan85 memory.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Returns the Yan85 memory for modification.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Returns the index of the instruction emulated by the most recent step.
+    pub fn last_index(&self) -> u8 {
+        self.index
+    }
+
+    /// Returns the number of steps taken, including any that faulted.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Captures the machine state: registers, memory, stack, code, open files, protected regions,
+    /// fault policy, and step count.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            registers: self.registers.clone(),
+            memory: self.memory.clone(),
+            stack: self.stack.clone(),
+            code: assemble(self.constants, &self.instructions),
+            constants: self.constants,
+            files: self.files.states()?,
+            index: self.index,
+            fell_through: self.fell_through,
+            fault_policy: self.fault_policy,
+            steps: self.steps,
+            exit_code: self.exit_code,
+            regions: self.protections.regions().to_vec(),
+            written: self.protections.written(),
+        })
+    }
+
+    /// Restores the machine state captured by [`Emulator::snapshot`], reopening the files the guest
+    /// had open. The protected regions are replaced by the snapshot's, and the recorded history is
+    /// discarded.
+    ///
+    /// Fails if the snapshot was taken by an emulator with different constants, since its code would
+    /// decode to a different program.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if snapshot.constants != self.constants {
+            bail!("Snapshot was taken with different constants");
+        }
+
+        self.instructions = disassemble(self.constants, snapshot.code.clone())?;
+        self.files = FileTable::restore(&snapshot.files)?;
+        self.registers = snapshot.registers.clone();
+        self.memory = snapshot.memory.clone();
+        self.stack = snapshot.stack.clone();
+        self.index = snapshot.index;
+        self.fell_through = snapshot.fell_through;
+        self.fault_policy = snapshot.fault_policy;
+        self.steps = snapshot.steps;
+        self.exit_code = snapshot.exit_code;
+
+        self.history.clear();
+        self.accesses.clear();
+        self.fd_effects.clear();
+        self.syscall = None;
+        self.unread_stdin.clear();
+        self.rewound_output.clear();
+        self.protections = Protections::restore(snapshot.regions.clone(), &snapshot.written);
+
+        Ok(())
+    }
+
+    /// Returns the registers, memory, and stack accessed by the most recent step, in order.
+    ///
+    /// The implicit increment of [`Register::I`] before each instruction isn't included.
+    pub fn last_accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    /// Returns the wall-clock time the most recent step spent opening, reading, and writing host
+    /// files and streams.
+    pub fn last_io_time(&self) -> Duration {
+        self.io_time
+    }
+
+    /// Returns the syscall made by the most recent step, if it made one that returned.
+    pub fn last_syscall(&self) -> Option<Syscall> {
+        self.syscall
+    }
+
+    /// Sets how guest behaviors that have no sensible meaning in the emulator are handled. Defaults
+    /// to [`FaultPolicy::Stop`].
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+    }
+
+    /// Protects `region` of memory. Accesses that violate the protection fault with
+    /// [`FaultKind::ProtectionViolation`] before they take effect.
+    ///
+    /// Only the guest's accesses through `STM`, `LDM`, `READ_MEMORY`, and `WRITE` are checked.
+    pub fn protect(&mut self, region: Region) {
+        self.protections.add(region);
+    }
+
+    /// Returns the protected regions of memory.
+    pub fn protected_regions(&self) -> &[Region] {
+        self.protections.regions()
+    }
+
+    /// Sets the number of recent steps recorded for [`Emulator::step_back`]. Defaults to zero,
+    /// which disables recording.
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history.set_capacity(size);
+    }
+
+    /// Returns the number of recorded steps that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Finds the most recent recorded write to a register, memory address, or stack slot in
+    /// `target`.
+    pub fn last_write(&self, target: &WatchTarget) -> Option<PastWrite> {
+        self.history.last_write(target)
+    }
+
+    /// Steps through the next instruction.
+    ///
+    /// Under [`FaultPolicy::Wrap`], instruction indices past the end of the program hold no-ops, as
+    /// in the reference VM's zero-filled code segment.
+    pub fn step(&mut self) -> Result<Instruction> {
+        self.accesses.clear();
+        self.fd_effects.clear();
+        self.syscall = None;
+        self.io_time = Duration::ZERO;
+        self.index = self.registers[Register::I];
+        let exit_code = self.exit_code;
+        let fell_through = self.fell_through;
+
+        let result = self.execute_next();
+        self.steps += 1;
+
+        if self.history.capacity() > 0 {
+            self.history.push(Delta {
+                index: self.index,
+                accesses: self.accesses.clone(),
+                fd_effects: mem::take(&mut self.fd_effects),
+                exit_code,
+                fell_through,
+            });
+        }
+
+        result
+    }
+
+    /// Undoes the most recent recorded step, including one that faulted. Returns `false` if there
+    /// are no recorded steps left. Afterwards, [`Emulator::last_accesses`] returns the accesses made
+    /// by the undone step.
+    ///
+    /// Standard input consumed by the step is read again by later steps, and files it opened are
+    /// closed. Output can't be taken back, so re-executing the step doesn't repeat output it
+    /// already wrote. Changes made through [`Emulator::registers_mut`] and
+    /// [`Emulator::memory_mut`] aren't recorded, so they aren't undone.
+    pub fn step_back(&mut self) -> Result<bool> {
+        let Some(delta) = self.history.pop() else {
+            return Ok(false);
+        };
+
+        for access in delta.accesses.iter().rev() {
+            if let Access::Write { location, old, .. } = *access {
+                *self.slot(location) = old;
+            }
+        }
+        self.registers[Register::I] = delta.index;
+
+        for effect in delta.fd_effects.into_iter().rev() {
+            match effect {
+                FdEffect::Stdin(bytes) => {
+                    for &byte in bytes.iter().rev() {
+                        self.unread_stdin.push_front(byte);
+                    }
+                }
+                FdEffect::Output { fd, bytes } => self.rewound_output.push((fd, bytes)),
+                FdEffect::Open(fd) => self.files.close(fd)?,
+                FdEffect::Seek { fd, len } => self.files.rewind(fd, len)?,
+            }
+        }
+
+        self.exit_code = delta.exit_code;
+        self.fell_through = delta.fell_through;
+        self.accesses = delta.accesses;
+        self.steps -= 1;
+        self.protections.undo(self.steps);
+
+        Ok(true)
+    }
+
+    /// Fetches and emulates the instruction at the current index.
+    ///
+    /// Running off the end of the program faults under [`FaultPolicy::Stop`], whether by fetching
+    /// past the last instruction or by falling through from index 0xff back to 0.
+    fn execute_next(&mut self) -> Result<Instruction> {
+        if self.fell_through {
+            self.fault_or_wrap(FaultKind::InstructionOutOfBounds)?;
+        }
+
+        let instruction = match self.instructions.get(self.index as usize) {
+            Some(&instruction) => instruction,
+            None => {
+                self.fault_or_wrap(FaultKind::InstructionOutOfBounds)?;
+                Instruction::STK(Register::None, Register::None)
+            }
+        };
+
+        self.fell_through = false;
+        self.registers[Register::I] = self.index.wrapping_add(1);
+
+        self.emulate_instruction(instruction)?;
+
+        let jumped = self.accesses.iter().any(|access| {
+            matches!(
+                access,
+                Access::Write {
+                    location: Location::Register(Register::I),
+                    ..
+                }
+            )
+        });
+        self.fell_through = self.index == u8::MAX && !jumped;
+
+        Ok(instruction)
+    }
+
+    /// Emulates a Yan85 instruction.
+    fn emulate_instruction(&mut self, instruction: Instruction) -> Result<()> {
+        match instruction {
+            Instruction::IMM(register, value) => self.emulate_imm(register, value),
+            Instruction::ADD(a, b) => self.emulate_add(a, b),
+            Instruction::STK(pop, push) => self.emulate_stk(pop, push),
+            Instruction::STM(a, b) => self.emulate_stm(a, b),
+            Instruction::LDM(a, b) => self.emulate_ldm(a, b),
+            Instruction::CMP(a, b) => self.emulate_cmp(a, b),
+            Instruction::JMP(condition, register) => self.emulate_jmp(condition, register),
+            Instruction::SYS(syscall, register) => self.emulate_sys(syscall, register),
+        }
+    }
+
+    /// Emulates an `IMM` instruction, assigning `value` to `register`.
+    fn emulate_imm(&mut self, register: Register, value: u8) -> Result<()> {
+        self.write_register(register, value)
+    }
+
+    /// Emulates an `ADD` instruction, adding the value of `b` to that of `a`, storing the result in
+    /// `a`. Overflows wrap around.
+    fn emulate_add(&mut self, a: Register, b: Register) -> Result<()> {
+        let sum = self.read_register(a)?.wrapping_add(self.read_register(b)?);
+        self.write_register(a, sum)
+    }
+
+    /// Emulates a `STK` instruction, pushing `push`, and popping `pop` unless either
+    /// [`Register::None`].
+    fn emulate_stk(&mut self, pop: Register, push: Register) -> Result<()> {
+        if push != Register::None {
+            let sp = self.read_register(Register::S)?;
+            if sp == u8::MAX {
+                self.fault_or_wrap(FaultKind::StackOverflow)?;
+            }
+
+            let value = self.read_register(push)?;
+            self.write_location(Location::Stack(sp), value);
+            self.write_register(Register::S, sp.wrapping_add(1))?;
+        }
+
+        if pop != Register::None {
+            let sp = self.read_register(Register::S)?;
+            if sp == 0 {
+                self.fault_or_wrap(FaultKind::StackUnderflow)?;
+            }
+
+            self.write_register(Register::S, sp.wrapping_sub(1))?;
+            let value = self.read_location(Location::Stack(sp.wrapping_sub(1)));
+            self.write_register(pop, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Emulates a `STM` instruction, assigning the value of `b` to the location referenced by `a`.
+    /// In other words, it performs `*a = b`.
+    fn emulate_stm(&mut self, a: Register, b: Register) -> Result<()> {
+        let address = self.read_register(a)?;
+        let value = self.read_register(b)?;
+        self.check_protection(address, 1, true)?;
+        self.write_location(Location::Memory(address), value);
+        Ok(())
+    }
+
+    /// Emulates a `LDM` instruction, assigning the value at the location referenced by `b` to `a`.
+    /// In other words, it performs `a = *b`.
+    fn emulate_ldm(&mut self, a: Register, b: Register) -> Result<()> {
+        let address = self.read_register(b)?;
+        self.check_protection(address, 1, false)?;
+        let value = self.read_location(Location::Memory(address));
+        self.write_register(a, value)
+    }
+
+    /// Emulates a `CMP` instruction, comparing `a` and `b` and assigning a representation of their
+    /// relationship to register F.
+    fn emulate_cmp(&mut self, a: Register, b: Register) -> Result<()> {
+        let a = self.read_register(a)?;
+        let b = self.read_register(b)?;
+
+        let flags = self.constants.flag.compare(a, b);
+        self.write_register(Register::F, flags)
+    }
+
+    /// Emulates a `JMP` instruction, comparing the conditions encoded in `condition` to those in
+    /// register F, jumping to the instruction referenced by `register` if any of the conditions
+    /// match.
+    fn emulate_jmp(&mut self, condition: u8, register: Register) -> Result<()> {
+        if self.read_register(Register::F)? & condition != 0 {
+            let target = self.read_register(register)?;
+            self.write_register(Register::I, target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Emulates a `SYS` instruction, performing a Yan85 system call and placing the return value in
+    /// `register`. The return value is discarded if `register` is [`Register::None`].
+    fn emulate_sys(&mut self, syscall: u8, register: Register) -> Result<()> {
+        let Constants { syscall: s, .. } = self.constants;
+
+        if let Some(handler) = self.syscall_handlers.get_mut(&syscall) {
+            let registers = self.registers.clone();
+            let memory = self.memory.clone();
+
+            let return_value = handler.call(&mut self.registers, &mut self.memory)?;
+            self.record_handler_writes(&registers, &memory);
+
+            self.syscall = Some(Syscall {
+                number: syscall,
+                args: [Register::A, Register::B, Register::C].map(|r| registers[r]),
+                result: return_value,
+            });
+            return self.write_return_value(register, return_value);
+        }
+
+        let arg_count = match syscall {
+            _ if syscall == s.SLEEP || syscall == s.EXIT => 1,
+            _ if [s.OPEN, s.READ_CODE, s.READ_MEMORY, s.WRITE].contains(&syscall) => 3,
+            _ => 0,
+        };
+        let [a, b, c] = self.read_syscall_args(arg_count)?;
+
+        let return_value = match syscall {
+            _ if syscall == s.OPEN => self.syscall_open(a, b, c),
+            _ if syscall == s.READ_CODE => self.syscall_read_code(a, b, c),
+            _ if syscall == s.READ_MEMORY => self.syscall_read_memory(a, b, c),
+            _ if syscall == s.WRITE => self.syscall_write(a, b, c),
+            _ if syscall == s.SLEEP => self.syscall_sleep(a),
+            _ if syscall == s.EXIT => Ok(self.syscall_exit(a)),
+            _ => Err(self.fault(FaultKind::UnknownSyscall(syscall))),
+        }?;
+
+        self.syscall = Some(Syscall {
+            number: syscall,
+            args: [a, b, c],
+            result: return_value,
+        });
+        self.write_return_value(register, return_value)
+    }
+
+    /// Returns the values of registers a, b, and c as syscall arguments, recording reads of only the
+    /// first `count`, which the syscall uses.
+    fn read_syscall_args(&mut self, count: usize) -> Result<[u8; 3]> {
+        let mut args = [Register::A, Register::B, Register::C].map(|r| self.registers[r]);
+        for (arg, register) in args
+            .iter_mut()
+            .zip([Register::A, Register::B, Register::C])
+            .take(count)
+        {
+            *arg = self.read_register(register)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Places a syscall's return value in `register` unless it's [`Register::None`].
+    fn write_return_value(&mut self, register: Register, value: u8) -> Result<()> {
+        match register {
+            Register::None => Ok(()),
+            _ => self.write_register(register, value),
+        }
+    }
+
+    /// Opens the file on the host system with the path pointed to by `path_address`, interpreting
+    /// `flags` and `mode` like `open(2)` does. Returns the new file descriptor, or
+    /// [`SYSCALL_FAILURE`] if the file couldn't be opened.
+    ///
+    /// Since `mode` is a single byte, files created by the guest never have the owner read bit set,
+    /// just like on the reference VM.
+    fn syscall_open(&mut self, path_address: u8, flags: u8, mode: u8) -> Result<u8> {
+        let path_bytes: Vec<u8> = (path_address..=u8::MAX)
+            .map(|address| self.read_location(Location::Memory(address)))
+            .take_while(|&b| b != 0)
+            .collect();
+        let path = Path::new(OsStr::from_bytes(&path_bytes));
+
+        let flags = OpenFlags(flags.into());
+        match self.timed(|emulator| emulator.files.open(path, flags, mode.into())) {
+            Ok(fd) => {
+                self.fd_effects.push(FdEffect::Open(fd));
+                Ok(fd)
+            }
+            Err(_) => Ok(SYSCALL_FAILURE),
+        }
+    }
+
+    /// Reads up to `num_bytes` bytes from the file with file descriptor `fd` into Yan85
+    /// instructions, starting at instruction index `start`.
+    fn syscall_read_code(&mut self, fd: u8, start: u8, num_bytes: u8) -> Result<u8> {
+        bail!("Unsupported syscall read_code({fd}, {start:#04x}, {num_bytes:#04x})");
+    }
+
+    /// Reads up to `num_bytes` bytes from the file with file descriptor `fd` into memory, starting
+    /// at the memory location `start`. Returns the number of bytes read, or [`SYSCALL_FAILURE`].
+    fn syscall_read_memory(&mut self, fd: u8, start: u8, num_bytes: u8) -> Result<u8> {
+        self.check_memory_range(start, num_bytes)?;
+        self.check_protection(start, num_bytes, true)?;
+
+        let mut buffer = vec![0u8; num_bytes as usize];
+
+        let bytes_read = match self.timed(|emulator| emulator.read_fd(fd, &mut buffer)) {
+            Ok(n) => n,
+            Err(_) => return Ok(SYSCALL_FAILURE),
+        };
+
+        for (address, &byte) in memory_range(start).zip(&buffer[..bytes_read]) {
+            self.write_location(Location::Memory(address), byte);
+        }
+
+        Ok(u8::try_from(bytes_read).expect("the buffer size is a u8"))
+    }
+
+    /// Writes up to `size` bytes from memory starting at the memory location `start` to the file
+    /// with file descriptor `fd`. Returns the number of bytes written, or [`SYSCALL_FAILURE`].
+    fn syscall_write(&mut self, fd: u8, start: u8, size: u8) -> Result<u8> {
+        self.check_memory_range(start, size)?;
+        self.check_protection(start, size, false)?;
+
+        let buffer: Vec<u8> = memory_range(start)
+            .take(size as usize)
+            .map(|address| self.read_location(Location::Memory(address)))
+            .collect();
+        let bytes_written = self.timed(|emulator| emulator.write_fd(fd, &buffer));
+
+        Ok(to_return_value(bytes_written))
+    }
+
+    /// Sleeps for `duration` seconds.
+    fn syscall_sleep(&mut self, duration: u8) -> Result<u8> {
+        bail!("Unsupported syscall sleep({duration})");
+    }
+
+    /// Terminates the Yan85 virtual machine, recording `exit_code`. Returns `exit_code` for the
+    /// benefit of the return value register.
+    fn syscall_exit(&mut self, exit_code: u8) -> u8 {
+        self.exit_code = Some(exit_code);
+        exit_code
+    }
+
+    /// Returns the value of `register`. Reading [`Register::None`] faults, or yields zero under
+    /// [`FaultPolicy::Wrap`].
+    fn read_register(&mut self, register: Register) -> Result<u8> {
+        if register == Register::None {
+            self.fault_or_wrap(FaultKind::InvalidRegister)?;
+            return Ok(0);
+        }
+
+        Ok(self.read_location(Location::Register(register)))
+    }
+
+    /// Assigns `value` to `register`. Writing [`Register::None`] faults, or is discarded under
+    /// [`FaultPolicy::Wrap`].
+    fn write_register(&mut self, register: Register, value: u8) -> Result<()> {
+        if register == Register::None {
+            return self.fault_or_wrap(FaultKind::InvalidRegister);
+        }
+
+        self.write_location(Location::Register(register), value);
+        Ok(())
+    }
+
+    /// Returns the value at `location`, recording the access.
+    fn read_location(&mut self, location: Location) -> u8 {
+        let value = *self.slot(location);
+        self.accesses.push(Access::Read { location, value });
+
+        value
+    }
+
+    /// Assigns `value` to `location`, recording the access.
+    fn write_location(&mut self, location: Location, value: u8) {
+        if let Location::Memory(address) = location {
+            self.protections.record_write(address, self.steps);
+        }
+
+        let slot = self.slot(location);
+        let old = *slot;
+        *slot = value;
+
+        self.accesses.push(Access::Write {
+            location,
+            old,
+            new: value,
+        });
+    }
+
+    /// Returns a mutable reference to the value at `location`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `location` is [`Register::None`].
+    fn slot(&mut self, location: Location) -> &mut u8 {
+        match location {
+            Location::Register(register) => &mut self.registers[register],
+            Location::Memory(address) => &mut self.memory[address],
+            Location::Stack(slot) => &mut self.stack[slot],
+        }
+    }
+
+    /// Records the registers and memory changed by a user-supplied syscall handler, given their
+    /// values before the handler ran.
+    fn record_handler_writes(&mut self, registers: &Registers, memory: &Memory) {
+        for register in Registers::ALL {
+            let (old, new) = (registers[register], self.registers[register]);
+            if old != new {
+                self.accesses.push(Access::Write {
+                    location: Location::Register(register),
+                    old,
+                    new,
+                });
+            }
+        }
+
+        for address in 0..=u8::MAX {
+            let (old, new) = (memory[address], self.memory[address]);
+            if old != new {
+                self.accesses.push(Access::Write {
+                    location: Location::Memory(address),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
+    /// Checks that the `size`-byte memory range starting at `start` doesn't extend past the end of
+    /// memory. Under [`FaultPolicy::Wrap`], such ranges wrap around to the start of memory instead.
+    fn check_memory_range(&self, start: u8, size: u8) -> Result<()> {
+        if start as usize + size as usize > 256 {
+            self.fault_or_wrap(FaultKind::MemoryOutOfBounds { start, size })?;
+        }
+
+        Ok(())
+    }
+
+    /// Faults if a read, or a write if `write`, of the `size`-byte memory range starting at `start`
+    /// violates the protection of a region. Protections apply under any fault policy.
+    fn check_protection(&self, start: u8, size: u8, write: bool) -> Result<()> {
+        for address in memory_range(start).take(size as usize) {
+            if let Some(protection) = self.protections.violated_by(address, write) {
+                return Err(self.fault(FaultKind::ProtectionViolation {
+                    address,
+                    write,
+                    protection,
+                }));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Faults with `kind` unless the fault policy is to wrap around.
+    fn fault_or_wrap(&self, kind: FaultKind) -> Result<()> {
+        match self.fault_policy {
+            FaultPolicy::Wrap => Ok(()),
+            FaultPolicy::Stop => Err(self.fault(kind)),
+        }
+    }
+
+    /// Constructs a fault of the given kind at the current instruction.
+    fn fault(&self, kind: FaultKind) -> anyhow::Error {
+        Fault {
+            index: self.index,
+            registers: self.registers.clone(),
+            kind,
+        }
+        .into()
+    }
+
+    /// Runs `io`, adding the time it takes to the step's time spent in host I/O.
+    fn timed<T>(&mut self, io: impl FnOnce(&mut Self) -> T) -> T {
+        let start = Instant::now();
+        let result = io(self);
+        self.io_time += start.elapsed();
+
+        result
+    }
+
+    /// Reads from the guest file descriptor `fd` into `buffer`.
+    fn read_fd(&mut self, fd: u8, buffer: &mut [u8]) -> io::Result<usize> {
+        let (n, effect) = match fd {
+            0 => {
+                let n = match self.unread_stdin.is_empty() {
+                    true => self.io.read_stdin(buffer)?,
+                    false => self.unread_stdin.read(buffer)?,
+                };
+
+                (n, FdEffect::Stdin(buffer[..n].to_vec()))
+            }
+            1 | 2 => return Err(io::Error::other(format!("fd {fd} isn't readable"))),
+            _ => {
+                let n = self.files.read(fd, buffer)?;
+                (n, FdEffect::Seek { fd, len: n })
+            }
+        };
+
+        self.fd_effects.push(effect);
+        Ok(n)
+    }
+
+    /// Writes `buffer` to the guest file descriptor `fd`.
+    fn write_fd(&mut self, fd: u8, buffer: &[u8]) -> io::Result<usize> {
+        let n = match fd {
+            0 => return Err(io::Error::other("fd 0 isn't writable")),
+            1 | 2 if self.replay_output(fd, buffer) => buffer.len(),
+            1 => self.io.write_stdout(buffer)?,
+            2 => self.io.write_stderr(buffer)?,
+            _ => {
+                let n = self.files.write(fd, buffer)?;
+                self.fd_effects.push(FdEffect::Seek { fd, len: n });
+                return Ok(n);
+            }
+        };
+
+        self.fd_effects.push(FdEffect::Output {
+            fd,
+            bytes: buffer[..n].to_vec(),
+        });
+        Ok(n)
+    }
+
+    /// Checks whether writing `buffer` to `fd` re-executes the most recently undone write, which
+    /// has already been output. Any other write discards the record of undone writes.
+    fn replay_output(&mut self, fd: u8, buffer: &[u8]) -> bool {
+        match self.rewound_output.pop() {
+            Some((rewound_fd, bytes)) if rewound_fd == fd && bytes == buffer => true,
+            _ => {
+                self.rewound_output.clear();
+                false
+            }
+        }
+    }
+}
+
+/// Returns the memory addresses starting at `start`, wrapping around at the end of memory.
+fn memory_range(start: u8) -> impl Iterator<Item = u8> {
+    (0..=u8::MAX).map(move |offset| start.wrapping_add(offset))
+}
+
+/// Converts the result of a host I/O operation into a syscall return value.
+fn to_return_value(result: io::Result<usize>) -> u8 {
+    result.map_or(SYSCALL_FAILURE, |n| {
+        u8::try_from(n).expect("transfers are at most 255 bytes")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn test_imm() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::IMM(Register::A, 42)],
+            Memory::default(),
+        );
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::A], 42);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::ADD(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 42;
+        emulator.registers[Register::B] = 24;
+
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.registers[Register::A], 66);
+    }
+
+    #[test]
+    fn test_stk_push() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::None, Register::C)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::C] = 42;
+
+        let sp_pre = emulator.registers[Register::S];
+        emulator.step().unwrap();
+        let sp_post = emulator.registers[Register::S];
+
+        assert_eq!(sp_post, sp_pre + 1);
+        assert_eq!(emulator.stack[emulator.registers[Register::S] - 1], 42);
+        assert_eq!(emulator.registers[Register::C], 42);
+    }
+
+    #[test]
+    fn test_stk_pop() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::B, Register::None)],
+            Memory::default(),
+        );
+
+        emulator.stack[emulator.registers[Register::S]] = 42;
+        emulator.registers[Register::S] += 1;
+
+        let sp_pre = emulator.registers[Register::S];
+        emulator.step().unwrap();
+        let sp_post = emulator.registers[Register::S];
+
+        assert_eq!(sp_post, sp_pre - 1);
+        assert_eq!(emulator.registers[Register::B], 42);
+    }
+
+    #[test]
+    fn test_stk_push_pop() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::B, Register::C)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::C] = 42;
+
+        let sp_pre = emulator.registers[Register::S];
+        emulator.step().unwrap();
+        let sp_post = emulator.registers[Register::S];
+
+        assert_eq!(sp_post, sp_pre);
+        assert_eq!(emulator.registers[Register::B], 42);
+        assert_eq!(emulator.registers[Register::C], 42);
+    }
+
+    #[test]
+    fn test_stm() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STM(Register::A, Register::D)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 0x20;
+        emulator.registers[Register::D] = 42;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.memory[0x20], 42);
+    }
+
+    #[test]
+    fn test_ldm() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::LDM(Register::A, Register::D)],
+            Memory::default(),
+        );
+
+        emulator.memory[0x20] = 42;
+        emulator.registers[Register::D] = 0x20;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::A], 42);
+    }
+
+    #[test]
+    fn test_cmp_less() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::CMP(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 1;
+        emulator.registers[Register::B] = 2;
+
+        emulator.step().unwrap();
+
+        assert_ne!(emulator.registers[Register::F] & f.L, 0);
+        assert_ne!(emulator.registers[Register::F] & f.N, 0);
+
+        assert_eq!(emulator.registers[Register::F] & f.G, 0);
+        assert_eq!(emulator.registers[Register::F] & f.E, 0);
+        assert_eq!(emulator.registers[Register::F] & f.Z, 0);
+    }
+
+    #[test]
+    fn test_cmp_greater() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::CMP(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 2;
+        emulator.registers[Register::B] = 1;
+
+        emulator.step().unwrap();
+
+        assert_ne!(emulator.registers[Register::F] & f.G, 0);
+        assert_ne!(emulator.registers[Register::F] & f.N, 0);
+
+        assert_eq!(emulator.registers[Register::F] & f.L, 0);
+        assert_eq!(emulator.registers[Register::F] & f.E, 0);
+        assert_eq!(emulator.registers[Register::F] & f.Z, 0);
+    }
+
+    #[test]
+    fn test_cmp_equal() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::CMP(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 1;
+        emulator.registers[Register::B] = 1;
+
+        emulator.step().unwrap();
+
+        assert_ne!(emulator.registers[Register::F] & f.E, 0);
+
+        assert_eq!(emulator.registers[Register::F] & f.L, 0);
+        assert_eq!(emulator.registers[Register::F] & f.G, 0);
+        assert_eq!(emulator.registers[Register::F] & f.N, 0);
+        assert_eq!(emulator.registers[Register::F] & f.Z, 0);
+    }
+
+    #[test]
+    fn test_cmp_zeroes() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::CMP(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 0;
+        emulator.registers[Register::B] = 0;
+
+        emulator.step().unwrap();
+
+        assert_ne!(emulator.registers[Register::F] & f.E, 0);
+        assert_ne!(emulator.registers[Register::F] & f.Z, 0);
+
+        assert_eq!(emulator.registers[Register::F] & f.L, 0);
+        assert_eq!(emulator.registers[Register::F] & f.G, 0);
+        assert_eq!(emulator.registers[Register::F] & f.N, 0);
+    }
+
+    #[test]
+    fn test_cmp_just_one_zero() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::CMP(Register::A, Register::B)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 0;
+        emulator.registers[Register::B] = 1;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::F] & f.Z, 0);
+    }
+
+    #[test]
+    fn test_jmp_taken() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::JMP(f.L, Register::A),
+                Instruction::ADD(Register::C, Register::C),
+                Instruction::ADD(Register::C, Register::C),
+            ],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::F] = f.L | f.N;
+        emulator.registers[Register::A] = 2;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::I], 2);
+    }
+
+    #[test]
+    fn test_jmp_not_taken() {
+        let consts = Constants::default();
+        let Constants { flag: f, .. } = consts;
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::JMP(f.L, Register::A),
+                Instruction::ADD(Register::C, Register::C),
+                Instruction::ADD(Register::C, Register::C),
+            ],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::F] = f.G | f.N;
+        emulator.registers[Register::A] = 2;
+
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.registers[Register::I], 1);
+        assert_ne!(emulator.registers[Register::I], 2);
+    }
+
+    /// Stores `path` as a null-terminated string at memory location `address`.
+    fn store_path(emulator: &mut Emulator, address: u8, path: &Path) {
+        let bytes = path.as_os_str().as_bytes();
+        let end = address + u8::try_from(bytes.len()).unwrap();
+
+        emulator.memory[address..end].copy_from_slice(bytes);
+        emulator.memory[end] = 0;
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::SYS(consts.syscall.OPEN, Register::D)],
+            Memory::default(),
+        );
+
+        store_path(&mut emulator, 0x10, Path::new("/nonexistent/gyan85"));
+        emulator.registers[Register::A] = 0x10;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::D], SYSCALL_FAILURE);
+    }
+
+    #[test]
+    fn test_open_create_and_write() {
+        let consts = Constants::default();
+        let path = std::env::temp_dir().join(format!("gyan85-open-{}", std::process::id()));
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::SYS(consts.syscall.OPEN, Register::D),
+                Instruction::SYS(consts.syscall.WRITE, Register::C),
+            ],
+            Memory::default(),
+        );
+
+        store_path(&mut emulator, 0x80, &path);
+        emulator.memory[0x00..0x02].copy_from_slice(b"hi");
+        emulator.registers[Register::A] = 0x80;
+        emulator.registers[Register::B] = (OpenFlags::WRONLY | OpenFlags::CREAT) as u8;
+        emulator.registers[Register::C] = 0o200;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::D], 3);
+
+        emulator.registers[Register::A] = 3;
+        emulator.registers[Register::B] = 0x00;
+        emulator.registers[Register::C] = 2;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::C], 2);
+
+        // the guest can't express the owner read bit in an 8-bit mode
+        std::fs::set_permissions(&path, PermissionsExt::from_mode(0o600)).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hi");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_echo_stdin_to_stdout() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x30),
+                Instruction::IMM(Register::C, 0x10),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Register::C),
+                Instruction::IMM(Register::A, 1),
+                Instruction::SYS(consts.syscall.WRITE, Register::D),
+            ],
+            Memory::default(),
+        );
+
+        let io = BufferIo::new(b"hello".to_vec());
+        let stdout = io.stdout();
+        emulator.set_io(Box::new(io));
+
+        for _ in 0..6 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(emulator.registers[Register::C], 5);
+        assert_eq!(emulator.registers[Register::D], 5);
+        assert_eq!(stdout.contents(), b"hello");
+    }
+
+    #[test]
+    fn test_unknown_syscall() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::SYS(0x40, Register::D),
+            ],
+            Memory::default(),
+        );
+
+        emulator.step().unwrap();
+        let error = emulator.step().unwrap_err();
+        let fault = error.downcast_ref::<Fault>().unwrap();
+
+        assert_eq!(fault.index, 1);
+        assert_eq!(fault.kind, FaultKind::UnknownSyscall(0x40));
+        assert_eq!(fault.registers[Register::I], 2);
+    }
+
+    /// Steps `emulator` once, returning the kind of fault raised.
+    fn step_fault(emulator: &mut Emulator) -> FaultKind {
+        let error = emulator.step().unwrap_err();
+        error.downcast_ref::<Fault>().unwrap().kind
+    }
+
+    #[test]
+    fn test_instruction_out_of_bounds() {
+        let mut emulator = Emulator::new(Constants::default(), vec![], Memory::default());
+        assert_eq!(step_fault(&mut emulator), FaultKind::InstructionOutOfBounds);
+    }
+
+    #[test]
+    fn test_instruction_out_of_bounds_wrap() {
+        let mut emulator = Emulator::new(Constants::default(), vec![], Memory::default());
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+        emulator.registers[Register::I] = u8::MAX;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::I], 0);
+    }
+
+    #[test]
+    fn test_last_instruction() {
+        let constants = Constants::default();
+        let mut instructions = vec![Instruction::IMM(Register::A, 0); 255];
+        instructions.push(Instruction::SYS(constants.syscall.EXIT, Register::None));
+
+        let mut emulator = Emulator::new(constants, instructions, Memory::default());
+        emulator.registers[Register::I] = u8::MAX;
+        emulator.step().unwrap();
+        assert_eq!(emulator.exit_code(), Some(0));
+    }
+
+    #[test]
+    fn test_fall_through_last_instruction() {
+        let instructions = vec![Instruction::IMM(Register::A, 7); 256];
+        let mut emulator = Emulator::new(Constants::default(), instructions, Memory::default());
+        emulator.registers[Register::I] = u8::MAX;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::I], 0);
+        assert_eq!(step_fault(&mut emulator), FaultKind::InstructionOutOfBounds);
+
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::I], 1);
+    }
+
+    #[test]
+    fn test_jump_from_last_instruction() {
+        let mut instructions = vec![Instruction::IMM(Register::A, 7); 255];
+        instructions.push(Instruction::IMM(Register::I, 0));
+
+        let mut emulator = Emulator::new(Constants::default(), instructions, Memory::default());
+        emulator.registers[Register::I] = u8::MAX;
+        emulator.step().unwrap();
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::I], 1);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::None, Register::A)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::S] = u8::MAX;
+        assert_eq!(step_fault(&mut emulator), FaultKind::StackOverflow);
+    }
+
+    #[test]
+    fn test_stack_overflow_wrap() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::None, Register::A)],
+            Memory::default(),
+        );
+
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+        emulator.registers[Register::S] = u8::MAX;
+        emulator.registers[Register::A] = 42;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::S], 0);
+        assert_eq!(emulator.stack[u8::MAX], 42);
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::STK(Register::A, Register::None)],
+            Memory::default(),
+        );
+
+        assert_eq!(step_fault(&mut emulator), FaultKind::StackUnderflow);
+    }
+
+    #[test]
+    fn test_invalid_register() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::ADD(Register::A, Register::None)],
+            Memory::default(),
+        );
+
+        assert_eq!(step_fault(&mut emulator), FaultKind::InvalidRegister);
+    }
+
+    #[test]
+    fn test_invalid_register_wrap() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::ADD(Register::A, Register::None)],
+            Memory::default(),
+        );
+
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+        emulator.registers[Register::A] = 42;
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.registers[Register::A], 42);
+    }
+
+    #[test]
+    fn test_sys_discards_none_return_value() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::SYS(consts.syscall.EXIT, Register::None)],
+            Memory::default(),
+        );
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.exit_code(), Some(0));
+    }
+
+    #[test]
+    fn test_write_out_of_bounds() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::SYS(consts.syscall.WRITE, Register::D)],
+            Memory::default(),
+        );
+
+        emulator.registers[Register::A] = 1;
+        emulator.registers[Register::B] = 0xf0;
+        emulator.registers[Register::C] = 0x20;
+
+        assert_eq!(
+            step_fault(&mut emulator),
+            FaultKind::MemoryOutOfBounds {
+                start: 0xf0,
+                size: 0x20
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_out_of_bounds_wrap() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::SYS(consts.syscall.WRITE, Register::D)],
+            Memory::default(),
+        );
+
+        let io = BufferIo::default();
+        let stdout = io.stdout();
+        emulator.set_io(Box::new(io));
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+
+        emulator.memory[0xff] = b'a';
+        emulator.memory[0x00] = b'b';
+        emulator.registers[Register::A] = 1;
+        emulator.registers[Register::B] = 0xff;
+        emulator.registers[Register::C] = 2;
+
+        emulator.step().unwrap();
+        assert_eq!(stdout.contents(), b"ab");
+    }
+
+    #[test]
+    fn test_custom_syscall() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            vec![Instruction::SYS(0x40, Register::D)],
+            Memory::default(),
+        );
+
+        emulator.register_syscall(
+            0x40,
+            Box::new(|registers: &mut Registers, memory: &mut Memory| {
+                memory[0x10] = registers[Register::A];
+                registers[Register::B] = 7;
+                Ok(registers[Register::A] + 1)
+            }),
+        );
+
+        emulator.registers[Register::A] = 41;
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.memory[0x10], 41);
+        assert_eq!(emulator.registers[Register::B], 7);
+        assert_eq!(emulator.registers[Register::D], 42);
+    }
+
+    #[test]
+    fn test_override_builtin_syscall() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![Instruction::SYS(consts.syscall.EXIT, Register::D)],
+            Memory::default(),
+        );
+
+        emulator.register_syscall(consts.syscall.EXIT, Box::new(|_: &mut _, _: &mut _| Ok(0)));
+        emulator.step().unwrap();
+
+        assert_eq!(emulator.exit_code(), None);
+    }
+
+    #[test]
+    fn test_exit() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 42),
+                Instruction::SYS(consts.syscall.EXIT, Register::D),
+            ],
+            Memory::default(),
+        );
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.exit_code(), None);
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.exit_code(), Some(42));
+    }
+
+    #[test]
+    fn test_open_read_only_rejects_writes() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::SYS(consts.syscall.OPEN, Register::D),
+                Instruction::SYS(consts.syscall.WRITE, Register::C),
+            ],
+            Memory::default(),
+        );
+
+        store_path(&mut emulator, 0x80, Path::new("constants.yml"));
+        emulator.registers[Register::A] = 0x80;
+        emulator.registers[Register::B] = OpenFlags::RDONLY as u8;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::D], 3);
+
+        emulator.registers[Register::A] = 3;
+        emulator.registers[Register::B] = 0x00;
+        emulator.registers[Register::C] = 1;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::C], SYSCALL_FAILURE);
+    }
+
+    /// Constructs an emulator for the echo program that records `history_size` steps.
+    fn echo_emulator(stdin: &[u8], history_size: usize) -> (Emulator, SharedBuffer) {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::new(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x30),
+                Instruction::IMM(Register::C, 0x10),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Register::C),
+                Instruction::IMM(Register::A, 1),
+                Instruction::SYS(consts.syscall.WRITE, Register::D),
+                Instruction::STK(Register::None, Register::D),
+                Instruction::SYS(consts.syscall.EXIT, Register::None),
+            ],
+            Memory::default(),
+        );
+
+        let io = BufferIo::new(stdin);
+        let stdout = io.stdout();
+        emulator.set_io(Box::new(io));
+        emulator.set_history_size(history_size);
+
+        (emulator, stdout)
+    }
+
+    #[test]
+    fn test_step_back_restores_state() {
+        let (mut emulator, _) = echo_emulator(b"hello", 16);
+
+        for _ in 0..8 {
+            emulator.step().unwrap();
+        }
+        assert_eq!(emulator.exit_code(), Some(1));
+
+        for _ in 0..8 {
+            assert!(emulator.step_back().unwrap());
+        }
+        assert!(!emulator.step_back().unwrap());
+
+        assert_eq!(emulator.registers, Registers::default());
+        assert_eq!(emulator.memory, Memory::default());
+        assert_eq!(emulator.stack[0], 0);
+        assert_eq!(emulator.exit_code(), None);
+    }
+
+    #[test]
+    fn test_step_back_rereads_stdin_without_repeating_output() {
+        let (mut emulator, stdout) = echo_emulator(b"hello", 16);
+
+        for _ in 0..6 {
+            emulator.step().unwrap();
+        }
+        for _ in 0..3 {
+            emulator.step_back().unwrap();
+        }
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(&emulator.memory[0x30..0x35], b"hello");
+        assert_eq!(emulator.registers[Register::D], 5);
+        assert_eq!(stdout.contents(), b"hello");
+    }
+
+    #[test]
+    fn test_step_back_diverging_output() {
+        let (mut emulator, stdout) = echo_emulator(b"hello", 16);
+
+        for _ in 0..6 {
+            emulator.step().unwrap();
+        }
+        emulator.step_back().unwrap();
+        emulator.memory[0x30] = b'j';
+        emulator.step().unwrap();
+
+        assert_eq!(stdout.contents(), b"hellojello");
+    }
+
+    #[test]
+    fn test_history_size() {
+        let (mut emulator, _) = echo_emulator(b"hello", 2);
+
+        for _ in 0..5 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(emulator.history_len(), 2);
+        assert!(emulator.step_back().unwrap());
+        assert!(emulator.step_back().unwrap());
+        assert!(!emulator.step_back().unwrap());
+        assert_eq!(emulator.registers[Register::I], 3);
+    }
+
+    #[test]
+    fn test_last_write() {
+        let (mut emulator, _) = echo_emulator(b"hello", 16);
+
+        for _ in 0..7 {
+            emulator.step().unwrap();
+        }
+
+        let past = emulator
+            .last_write(&WatchTarget::Memory(0x30..=0x3f))
+            .unwrap();
+        assert_eq!(past.index, 3);
+        assert_eq!(past.steps_ago, 4);
+
+        let past = emulator.last_write(&WatchTarget::Stack(0..=0)).unwrap();
+        assert_eq!(past.index, 6);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let (mut emulator, _) = echo_emulator(b"hello", 16);
+        for _ in 0..4 {
+            emulator.step().unwrap();
+        }
+        let snapshot = emulator.snapshot().unwrap();
+        assert_eq!(snapshot.steps, 4);
+
+        let (mut restored, stdout) = echo_emulator(b"", 16);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.steps(), 4);
+        assert_eq!(restored.history_len(), 0);
+
+        while restored.exit_code().is_none() {
+            restored.step().unwrap();
+        }
+        assert_eq!(stdout.contents(), b"hello");
+        assert_eq!(restored.steps(), 8);
+        assert_eq!(restored.exit_code(), Some(1));
+    }
+
+    #[test]
+    fn test_snapshot_restore_state() {
+        let (mut emulator, _) = echo_emulator(b"hello", 0);
+        emulator.set_fault_policy(FaultPolicy::Wrap);
+        for _ in 0..4 {
+            emulator.step().unwrap();
+        }
+        let snapshot = emulator.snapshot().unwrap();
+
+        let (mut restored, _) = echo_emulator(b"", 0);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.last_index(), emulator.last_index());
+        assert_eq!(restored.fault_policy, FaultPolicy::Wrap);
+
+        let mut constants = Constants::default();
+        constants.syscall.EXIT = 0x40;
+        let mut other = Emulator::new(constants, Vec::new(), Memory::default());
+        assert!(other.restore(&snapshot).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_json_roundtrip() {
+        let (mut emulator, _) = echo_emulator(b"hello", 0);
+        for _ in 0..4 {
+            emulator.step().unwrap();
+        }
+        let snapshot = emulator.snapshot().unwrap();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fmt, ops::RangeInclusive, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::WatchTarget;
+
+/// How a [`Region`] of memory is protected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protection {
+    /// Reads are allowed, writes fault.
+    ReadOnly,
+    /// Reads and writes fault.
+    NoAccess,
+    /// Reads and the first write to each address are allowed, later writes fault.
+    WriteOnce,
+}
+
+/// A range of memory addresses with a [`Protection`].
+///
+/// Violations stop the emulator with a
+/// [`FaultKind::ProtectionViolation`](crate::emulator::FaultKind::ProtectionViolation) regardless
+/// of the fault policy, since the reference VM has no notion of them.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::emulator::{Protection, Region};
+///
+/// let region: Region = "mem[0x00..0x40]=read-only".parse().unwrap();
+/// assert_eq!(region.range, 0x00..=0x3f);
+/// assert_eq!(region.protection, Protection::ReadOnly);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    /// The protected addresses.
+    pub range: RangeInclusive<u8>,
+    /// What the protection forbids.
+    pub protection: Protection,
+}
+
+/// The protected regions of an emulator's memory and the writes made to them.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Protections {
+    /// The protected regions, in the order they were added.
+    regions: Vec<Region>,
+    /// The step at which each write-once address was first written, or `None` if it was written
+    /// before the state was restored from a snapshot, so that the write can't be undone.
+    written: HashMap<u8, Option<u64>>,
+}
+
+impl Protections {
+    /// Adds a protected region.
+    pub(super) fn add(&mut self, region: Region) {
+        self.regions.push(region);
+    }
+
+    /// Constructs protections for `regions` in which the write-once addresses in `written` have
+    /// already been written.
+    pub(super) fn restore(regions: Vec<Region>, written: &[u8]) -> Self {
+        Self {
+            regions,
+            written: written.iter().map(|&address| (address, None)).collect(),
+        }
+    }
+
+    /// Returns the write-once addresses that have been written, in ascending order.
+    pub(super) fn written(&self) -> Vec<u8> {
+        let mut written: Vec<u8> = self.written.keys().copied().collect();
+        written.sort_unstable();
+
+        written
+    }
+
+    /// Returns the protected regions.
+    pub(super) fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Returns the protection that a read, or a write if `write`, of `address` violates, if any.
+    pub(super) fn violated_by(&self, address: u8, write: bool) -> Option<Protection> {
+        self.regions
+            .iter()
+            .filter(|region| region.range.contains(&address))
+            .map(|region| region.protection)
+            .find(|protection| match protection {
+                Protection::ReadOnly => write,
+                Protection::NoAccess => true,
+                Protection::WriteOnce => write && self.written.contains_key(&address),
+            })
+    }
+
+    /// Records a write to `address` made by step number `step`.
+    pub(super) fn record_write(&mut self, address: u8, step: u64) {
+        let write_once = self.regions.iter().any(|region| {
+            region.protection == Protection::WriteOnce && region.range.contains(&address)
+        });
+
+        if write_once {
+            self.written.entry(address).or_insert(Some(step));
+        }
+    }
+
+    /// Forgets the first writes made by step number `step`, which was undone.
+    pub(super) fn undo(&mut self, step: u64) {
+        self.written
+            .retain(|_, &mut written_at| written_at != Some(step));
+    }
+}
+
+impl FromStr for Protection {
+    type Err = anyhow::Error;
+
+    fn from_str(protection: &str) -> Result<Self> {
+        match protection {
+            "read-only" => Ok(Protection::ReadOnly),
+            "no-access" => Ok(Protection::NoAccess),
+            "write-once" => Ok(Protection::WriteOnce),
+            _ => Err(anyhow!(
+                "Invalid protection: {protection} (expected read-only, no-access, or write-once)"
+            )),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parses a memory range and a protection like `mem[0x00..0x40]=read-only`.
+    fn from_str(region: &str) -> Result<Self> {
+        let (target, protection) = region
+            .rsplit_once('=')
+            .with_context(|| format!("Expected RANGE=PROTECTION: {region}"))?;
+
+        let WatchTarget::Memory(range) = target.parse()? else {
+            bail!("Only memory can be protected: {target}");
+        };
+
+        Ok(Region {
+            range,
+            protection: protection.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protection::ReadOnly => write!(f, "read-only"),
+            Protection::NoAccess => write!(f, "no-access"),
+            Protection::WriteOnce => write!(f, "write-once"),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let target = WatchTarget::Memory(self.range.clone());
+        write!(f, "{target}={}", self.protection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::{testing, Emulator, EmulatorBuilder, Fault, FaultKind},
+        yan85::{constants::Constants, register::Register},
+    };
+
+    /// Returns an emulator running the program in `source` with `regions` protected.
+    fn emulator(source: &str, regions: &[&str]) -> Emulator {
+        let mut builder = testing::load(EmulatorBuilder::new(Constants::default()), source, b"xyz")
+            .history_size(16);
+        for region in regions {
+            builder = builder.protect(region.parse().unwrap());
+        }
+
+        builder.build()
+    }
+
+    /// Runs `emulator` until it exits or faults, returning the fault.
+    fn run(emulator: &mut Emulator) -> Option<Fault> {
+        let error = testing::run(emulator, |_, _| {}).err()?;
+        error.downcast_ref::<Fault>().cloned()
+    }
+
+    #[test]
+    fn test_parse() {
+        let region: Region = "mem[0x40..=0x4f]=write-once".parse().unwrap();
+        assert_eq!(region.range, 0x40..=0x4f);
+        assert_eq!(region.protection, Protection::WriteOnce);
+        assert_eq!(region.to_string(), "mem[0x40..=0x4f]=write-once");
+
+        assert!("mem[0x40]".parse::<Region>().is_err());
+        assert!("stack[0]=read-only".parse::<Region>().is_err());
+        assert!("mem[0x40]=executable".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn test_read_only() {
+        let source = "IMM a = 0x40
+LDM b = *a
+STM *a = b
+";
+        let mut emulator = emulator(source, &["mem[0x40..0x50]=read-only"]);
+        let fault = run(&mut emulator).unwrap();
+
+        assert_eq!(fault.index, 2);
+        assert_eq!(
+            fault.kind,
+            FaultKind::ProtectionViolation {
+                address: 0x40,
+                write: true,
+                protection: Protection::ReadOnly
+            }
+        );
+        assert_eq!(fault.kind.to_string(), "write to read-only memory at 0x40");
+    }
+
+    #[test]
+    fn test_no_access() {
+        let source = "IMM a = 1
+IMM b = 0x3e
+IMM c = 4
+SYS 0x8 NONE
+";
+        let mut emulator = emulator(source, &["mem[0x40]=no-access"]);
+        let fault = run(&mut emulator).unwrap();
+
+        assert_eq!(fault.index, 3);
+        assert_eq!(fault.kind.to_string(), "read of no-access memory at 0x40");
+    }
+
+    #[test]
+    fn test_write_once() {
+        let source = "IMM a = 0
+IMM b = 0x40
+IMM c = 2
+SYS 0x4 NONE
+IMM c = 0x41
+STM *c = a
+";
+        let mut emulator = emulator(source, &["mem[0x41]=write-once"]);
+        let fault = run(&mut emulator).unwrap();
+
+        assert_eq!(fault.index, 5);
+        assert_eq!(
+            fault.kind.to_string(),
+            "second write to write-once memory at 0x41"
+        );
+
+        // Undoing the first write allows another one.
+        for _ in 0..3 {
+            emulator.step_back().unwrap();
+        }
+        emulator.memory_mut()[0x41] = 0;
+        emulator.registers_mut()[Register::C] = 0x41;
+        emulator.registers_mut()[Register::I] = 5;
+        assert!(emulator.step().is_ok());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let source = "IMM a = 0x40
+IMM b = 1
+STM *a = b
+STM *a = b
+";
+        let mut emulator = emulator(source, &["mem[0x40..0x44]=write-once"]);
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+        let snapshot = emulator.snapshot().unwrap();
+
+        let mut restored = Emulator::new(Constants::default(), Vec::new(), Default::default());
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.protected_regions(), emulator.protected_regions());
+
+        let fault = run(&mut restored).unwrap();
+        assert_eq!(fault.index, 3);
+        assert_eq!(
+            fault.kind.to_string(),
+            "second write to write-once memory at 0x40"
+        );
+    }
+}
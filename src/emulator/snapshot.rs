@@ -0,0 +1,62 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emulator::{files::FileState, FaultPolicy, Region},
+    yan85::{constants::Constants, memory::Memory, registers::Registers, stack::Stack},
+};
+
+/// The full state of an [`Emulator`](super::Emulator), as taken by
+/// [`Emulator::snapshot`](super::Emulator::snapshot).
+///
+/// Snapshots are saved as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The registers.
+    pub registers: Registers,
+    /// The memory.
+    pub memory: Memory,
+    /// The stack.
+    pub stack: Stack,
+    /// The program's machine code.
+    pub code: Vec<u8>,
+    /// The constants the code was assembled with.
+    pub constants: Constants,
+    /// The files opened by the guest.
+    pub files: Vec<FileState>,
+    /// The index of the instruction emulated by the most recent step.
+    pub index: u8,
+    /// Whether execution fell through from index 0xff back to 0 in the most recent step.
+    #[serde(default)]
+    pub fell_through: bool,
+    /// How guest behaviors with no sensible meaning are handled.
+    pub fault_policy: FaultPolicy,
+    /// The number of steps taken.
+    pub steps: u64,
+    /// The program's exit code, if it has exited.
+    pub exit_code: Option<u8>,
+    /// The protected regions of memory.
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    /// The addresses in write-once regions that have already been written.
+    #[serde(default)]
+    pub written: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Saves the snapshot to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+
+    /// Loads a snapshot from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).context("Unable to parse snapshot")
+    }
+}
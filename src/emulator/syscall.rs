@@ -0,0 +1,63 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::yan85::{memory::Memory, registers::Registers};
+
+/// A syscall made by the guest, as reported by
+/// [`Emulator::last_syscall`](super::Emulator::last_syscall).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Syscall {
+    /// The syscall number.
+    pub number: u8,
+    /// The values of registers a, b, and c when the syscall was made.
+    pub args: [u8; 3],
+    /// The value the syscall returned.
+    pub result: u8,
+}
+
+/// A user-supplied implementation of a Yan85 syscall.
+///
+/// Handlers registered with [`Emulator::register_syscall`](super::Emulator::register_syscall)
+/// take precedence over the built-in syscalls, so they can both override existing syscalls and add
+/// new ones. Closures with a matching signature implement this trait.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::{
+///     emulator::Emulator,
+///     yan85::{
+///         constants::Constants, instruction::Instruction, memory::Memory, register::Register,
+///         registers::Registers,
+///     },
+/// };
+///
+/// let mut emulator = Emulator::new(
+///     Constants::default(),
+///     vec![Instruction::SYS(0x40, Register::D)],
+///     Memory::default(),
+/// );
+///
+/// // a syscall that doubles register a
+/// emulator.register_syscall(
+///     0x40,
+///     Box::new(|registers: &mut Registers, _: &mut Memory| {
+///         Ok(registers[Register::A].wrapping_mul(2))
+///     }),
+/// );
+///
+/// emulator.step().unwrap();
+/// ```
+pub trait SyscallHandler {
+    /// Performs the syscall, returning the value to place in the syscall's return register.
+    fn call(&mut self, registers: &mut Registers, memory: &mut Memory) -> Result<u8>;
+}
+
+impl<F> SyscallHandler for F
+where
+    F: FnMut(&mut Registers, &mut Memory) -> Result<u8>,
+{
+    fn call(&mut self, registers: &mut Registers, memory: &mut Memory) -> Result<u8> {
+        self(registers, memory)
+    }
+}
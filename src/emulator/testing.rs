@@ -0,0 +1,26 @@
+use anyhow::Result;
+
+use super::{BufferIo, Emulator, EmulatorBuilder};
+use crate::{
+    asm::parse_asm_program,
+    emu::{emulate_until, Limits},
+    yan85::instruction::Instruction,
+};
+
+/// Loads the program in `source` into `builder`, with `stdin` as the guest's standard input.
+pub(crate) fn load(builder: EmulatorBuilder, source: &str, stdin: &[u8]) -> EmulatorBuilder {
+    let program = parse_asm_program(source.to_string()).unwrap();
+    builder
+        .code(program.instructions)
+        .io(Box::new(BufferIo::new(stdin.to_vec())))
+}
+
+/// Steps `emulator` until the program exits, calling `on_step` with each instruction. Returns the
+/// error of the step that failed, if any.
+pub(crate) fn run(
+    emulator: &mut Emulator,
+    on_step: impl FnMut(&Emulator, Instruction),
+) -> Result<()> {
+    emulate_until(emulator, &Limits::default(), |_| false, on_step)?;
+    Ok(())
+}
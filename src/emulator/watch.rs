@@ -0,0 +1,267 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::{all_consuming, map, opt, verify},
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+
+use crate::{
+    asm::{parse_int_literal, parse_register},
+    emulator::{Access, Location},
+    yan85::register::Register,
+};
+
+/// Guest state observed by a [`Watchpoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchTarget {
+    /// A register.
+    Register(Register),
+    /// A range of memory addresses.
+    Memory(RangeInclusive<u8>),
+    /// A range of stack slots.
+    Stack(RangeInclusive<u8>),
+}
+
+/// The accesses that trigger a [`Watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Any read.
+    Read,
+    /// Any write, even one that doesn't change the value.
+    Write,
+    /// A write that changes the value.
+    Change,
+}
+
+/// A watchpoint that triggers on accesses to guest state.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::emulator::{WatchKind, Watchpoint};
+///
+/// let watchpoint = Watchpoint {
+///     target: "mem[0x40..0x50]".parse().unwrap(),
+///     kind: WatchKind::Write,
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// The state being watched.
+    pub target: WatchTarget,
+    /// The accesses being watched for.
+    pub kind: WatchKind,
+}
+
+impl WatchTarget {
+    /// Whether `location` is part of the target.
+    pub fn contains(&self, location: Location) -> bool {
+        match (self, location) {
+            (WatchTarget::Register(watched), Location::Register(register)) => *watched == register,
+            (WatchTarget::Memory(range), Location::Memory(address)) => range.contains(&address),
+            (WatchTarget::Stack(range), Location::Stack(slot)) => range.contains(&slot),
+            _ => false,
+        }
+    }
+}
+
+impl Watchpoint {
+    /// Whether `access` triggers the watchpoint.
+    pub fn matches(&self, access: &Access) -> bool {
+        if !self.target.contains(access.location()) {
+            return false;
+        }
+
+        match (self.kind, access) {
+            (WatchKind::Read, Access::Read { .. }) => true,
+            (WatchKind::Write, Access::Write { .. }) => true,
+            (WatchKind::Change, Access::Write { old, new, .. }) => old != new,
+            _ => false,
+        }
+    }
+
+    /// Returns the first of `accesses` that triggers the watchpoint.
+    pub fn triggered_by<'a>(&self, accesses: &'a [Access]) -> Option<&'a Access> {
+        accesses.iter().find(|access| self.matches(access))
+    }
+}
+
+impl FromStr for WatchTarget {
+    type Err = anyhow::Error;
+
+    /// Parses a register name like `c`, or a memory or stack address or range like `mem[0x40]`,
+    /// `mem[0x40..0x50]`, or `stack[0..=3]`.
+    fn from_str(target: &str) -> Result<Self> {
+        let (_, target) = all_consuming(parse_watch_target)(target.trim())
+            .map_err(|_| anyhow!("Invalid watch target: {target}"))?;
+
+        target
+    }
+}
+
+impl FromStr for WatchKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "read" => Ok(WatchKind::Read),
+            "write" => Ok(WatchKind::Write),
+            "change" => Ok(WatchKind::Change),
+            _ => Err(anyhow!(
+                "Invalid watch kind: {kind} (expected read, write, or change)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, range) = match self {
+            WatchTarget::Register(register) => return write!(f, "{}", register.name()),
+            WatchTarget::Memory(range) => ("mem", range),
+            WatchTarget::Stack(range) => ("stack", range),
+        };
+
+        if range.start() == range.end() {
+            write!(f, "{name}[{:#04x}]", range.start())
+        } else {
+            write!(f, "{name}[{:#04x}..={:#04x}]", range.start(), range.end())
+        }
+    }
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchKind::Read => write!(f, "read"),
+            WatchKind::Write => write!(f, "write"),
+            WatchKind::Change => write!(f, "change"),
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.target, self.kind)
+    }
+}
+
+/// Parses a watch target. Fails if a range is empty.
+fn parse_watch_target(input: &str) -> IResult<&str, Result<WatchTarget>> {
+    // registers must come last; `s` would consume the "s" in "stack"
+    alt((
+        map(preceded(tag("mem"), parse_range), |range| {
+            range.map(WatchTarget::Memory)
+        }),
+        map(preceded(tag("stack"), parse_range), |range| {
+            range.map(WatchTarget::Stack)
+        }),
+        map(
+            verify(parse_register, |register| *register != Register::None),
+            |register| Ok(WatchTarget::Register(register)),
+        ),
+    ))(input)
+}
+
+/// Parses a bracketed address or range, e.g. `[0x40]`, `[0x40..0x50]`, or `[0x40..=0x4f]`.
+fn parse_range(input: &str) -> IResult<&str, Result<RangeInclusive<u8>>> {
+    let (remaining, (start, end)) = delimited(
+        tag("["),
+        pair(
+            parse_int_literal,
+            opt(alt((
+                map(preceded(tag("..="), parse_int_literal), Some),
+                map(preceded(tag(".."), parse_int_literal), |end| {
+                    end.checked_sub(1)
+                }),
+            ))),
+        ),
+        tag("]"),
+    )(input)?;
+
+    let range = match end {
+        None => Ok(start..=start),
+        Some(Some(end)) if end >= start => Ok(start..=end),
+        Some(_) => Err(anyhow!("Empty range")),
+    };
+
+    Ok((remaining, range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(
+            "c".parse::<WatchTarget>().unwrap(),
+            WatchTarget::Register(Register::C)
+        );
+        assert_eq!(
+            "mem[0x40]".parse::<WatchTarget>().unwrap(),
+            WatchTarget::Memory(0x40..=0x40)
+        );
+        assert_eq!(
+            "mem[0x40..0x50]".parse::<WatchTarget>().unwrap(),
+            WatchTarget::Memory(0x40..=0x4f)
+        );
+        assert_eq!(
+            "stack[0..=3]".parse::<WatchTarget>().unwrap(),
+            WatchTarget::Stack(0..=3)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_targets() {
+        assert!("NONE".parse::<WatchTarget>().is_err());
+        assert!("mem[0x50..0x40]".parse::<WatchTarget>().is_err());
+        assert!("mem[0x40..0x40]".parse::<WatchTarget>().is_err());
+        assert!("memory[0x40]".parse::<WatchTarget>().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let write = Access::Write {
+            location: Location::Memory(0x44),
+            old: 1,
+            new: 1,
+        };
+
+        let watch = |kind| Watchpoint {
+            target: WatchTarget::Memory(0x40..=0x4f),
+            kind,
+        };
+
+        assert!(watch(WatchKind::Write).matches(&write));
+        assert!(!watch(WatchKind::Change).matches(&write));
+        assert!(!watch(WatchKind::Read).matches(&write));
+    }
+
+    #[test]
+    fn test_register_change() {
+        let watchpoint = Watchpoint {
+            target: WatchTarget::Register(Register::C),
+            kind: WatchKind::Change,
+        };
+
+        let accesses = [
+            Access::Write {
+                location: Location::Register(Register::B),
+                old: 0,
+                new: 1,
+            },
+            Access::Write {
+                location: Location::Register(Register::C),
+                old: 0,
+                new: 1,
+            },
+        ];
+
+        assert_eq!(watchpoint.triggered_by(&accesses), Some(&accesses[1]));
+    }
+}
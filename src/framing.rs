@@ -0,0 +1,66 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Reads the next message from `input`, or returns `None` when the client disconnects.
+///
+/// Messages are JSON preceded by a `Content-Length` header and a blank line.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = length.context("Message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Writes `message` to `output` with a `Content-Length` header.
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let message = json!({"seq": 1, "type": "request", "command": "threads"});
+
+        let mut output = Vec::new();
+        write_message(&mut output, &message).unwrap();
+        assert!(output.starts_with(b"Content-Length: 46\r\n\r\n{"));
+
+        let mut input = Cursor::new(output);
+        assert_eq!(read_message(&mut input).unwrap(), Some(message));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+}
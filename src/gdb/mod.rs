@@ -0,0 +1,438 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Read},
+    net::TcpStream,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    emulator::Emulator,
+    hex::{decode_hex, encode_hex},
+    yan85::{register::Register, registers::Registers},
+};
+
+/// Framing and encoding of remote serial protocol packets.
+mod packet;
+
+use packet::{read_packet, write_packet, Incoming, INTERRUPT};
+
+/// The number of steps between checks for an interrupt from the client while continuing.
+const INTERRUPT_INTERVAL: u64 = 4096;
+
+/// The description of the Yan85 registers sent to clients, in the order used by `g` packets.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gyan85.yan85">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="b" bitsize="8" type="uint8"/>
+    <reg name="c" bitsize="8" type="uint8"/>
+    <reg name="d" bitsize="8" type="uint8"/>
+    <reg name="s" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="8" type="code_ptr"/>
+    <reg name="f" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Serves an emulated program to debugger frontends like GDB over the remote serial protocol.
+///
+/// Registers are numbered in the order a, b, c, d, s, i, f. Addresses refer to Yan85 memory, and
+/// breakpoint addresses are instruction indices.
+pub struct GdbServer {
+    /// The emulator being debugged.
+    emulator: Emulator,
+    /// The instruction indices of software breakpoints.
+    breakpoints: BTreeSet<u8>,
+}
+
+impl GdbServer {
+    /// Constructs a server for the program loaded in `emulator`.
+    pub fn new(emulator: Emulator) -> Self {
+        Self {
+            emulator,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the emulator being debugged.
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    /// Serves a connected client until it detaches, kills the program, or disconnects.
+    pub fn serve(&mut self, stream: TcpStream) -> Result<()> {
+        let mut input = stream.try_clone()?;
+        let mut output = stream;
+
+        while let Some(incoming) = read_packet(&mut input, &mut output)? {
+            let Incoming::Packet(packet) = incoming else {
+                // the program isn't running, so it's already stopped
+                write_packet(&mut output, "S02")?;
+                continue;
+            };
+
+            match packet.as_str() {
+                "k" => break,
+                "D" => {
+                    write_packet(&mut output, "OK")?;
+                    break;
+                }
+                _ => {
+                    let reply = self.handle(&packet, || interrupted(&input))?;
+                    write_packet(&mut output, &reply)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a packet other than `k` and `D`, returning the reply. `interrupted` is polled while
+    /// continuing, and stops the program when it returns `true`.
+    ///
+    /// Malformed packets are answered with an error reply, and unsupported ones with an empty
+    /// reply, as the protocol requires.
+    pub fn handle(
+        &mut self,
+        packet: &str,
+        mut interrupted: impl FnMut() -> io::Result<bool>,
+    ) -> Result<String> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => Ok("S05".to_string()),
+            "g" => Ok(self.read_registers()),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.set_breakpoint(args, true),
+            "z" => self.set_breakpoint(args, false),
+            "s" => Ok(self.step()),
+            "c" => self.resume(&mut interrupted),
+            "H" | "T" => Ok("OK".to_string()),
+            "q" => Ok(self.query(args)),
+            _ => Ok(String::new()),
+        };
+
+        Ok(reply.unwrap_or_else(|_| "E01".to_string()))
+    }
+
+    /// Answers a `q` query.
+    fn query(&self, query: &str) -> String {
+        if let Some(annex) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            return xfer(TARGET_XML, annex).unwrap_or_else(|_| "E01".to_string());
+        }
+
+        match query.split(':').next().unwrap_or_default() {
+            "Supported" => "PacketSize=1000;qXfer:features:read+".to_string(),
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Encodes every register.
+    fn read_registers(&self) -> String {
+        let values = Registers::ALL.map(|register| self.emulator.registers()[register]);
+        encode_hex(&values)
+    }
+
+    /// Sets every register from `hex`.
+    fn write_registers(&mut self, hex: &str) -> Result<String> {
+        let values = decode_hex(hex)?;
+        for (register, value) in Registers::ALL.into_iter().zip(values) {
+            self.emulator.registers_mut()[register] = value;
+        }
+
+        Ok("OK".to_string())
+    }
+
+    /// Encodes the register numbered `number`.
+    fn read_register(&self, number: &str) -> Result<String> {
+        let register = register(number)?;
+        Ok(encode_hex(&[self.emulator.registers()[register]]))
+    }
+
+    /// Sets a register from an argument of the form `number=value`.
+    fn write_register(&mut self, args: &str) -> Result<String> {
+        let (number, hex) = args.split_once('=').context("Expected number=value")?;
+        let value = *decode_hex(hex)?.first().context("Expected a value")?;
+        self.emulator.registers_mut()[register(number)?] = value;
+
+        Ok("OK".to_string())
+    }
+
+    /// Encodes memory, given arguments of the form `address,length`. Reads are cut short at the end
+    /// of memory.
+    fn read_memory(&self, args: &str) -> Result<String> {
+        let (address, length) = address_and_length(args)?;
+        let end = address.saturating_add(length).min(256);
+        if address >= end {
+            return Err(anyhow!("Address out of bounds: {address:#x}"));
+        }
+
+        let bytes: Vec<u8> = (address..end)
+            .map(|address| self.emulator.memory()[address as u8])
+            .collect();
+
+        Ok(encode_hex(&bytes))
+    }
+
+    /// Writes memory, given arguments of the form `address,length:bytes`.
+    fn write_memory(&mut self, args: &str) -> Result<String> {
+        let (range, hex) = args
+            .split_once(':')
+            .context("Expected address,length:bytes")?;
+        let (address, length) = address_and_length(range)?;
+        let bytes = decode_hex(hex)?;
+
+        if bytes.len() != length || address.saturating_add(length) > 256 {
+            return Err(anyhow!("Invalid write of {length} bytes to {address:#x}"));
+        }
+
+        for (offset, byte) in bytes.into_iter().enumerate() {
+            self.emulator.memory_mut()[(address + offset) as u8] = byte;
+        }
+
+        Ok("OK".to_string())
+    }
+
+    /// Inserts or removes a breakpoint, given arguments of the form `type,index,kind`. Software and
+    /// hardware breakpoints are treated alike, and watchpoints are unsupported.
+    fn set_breakpoint(&mut self, args: &str, insert: bool) -> Result<String> {
+        let mut fields = args.split(',');
+        let kind = fields.next().unwrap_or_default();
+        if kind != "0" && kind != "1" {
+            return Ok(String::new());
+        }
+
+        let index = fields.next().context("Expected an index")?;
+        let index = u8::from_str_radix(index, 16)?;
+
+        if insert {
+            self.breakpoints.insert(index);
+        } else {
+            self.breakpoints.remove(&index);
+        }
+
+        Ok("OK".to_string())
+    }
+
+    /// Steps through one instruction, returning the stop reply.
+    fn step(&mut self) -> String {
+        if let Some(reply) = self.exit_reply() {
+            return reply;
+        }
+
+        match self.emulator.step() {
+            Ok(_) => self.exit_reply().unwrap_or_else(|| "S05".to_string()),
+            Err(_) => "S0b".to_string(),
+        }
+    }
+
+    /// Runs until a breakpoint, an exit, a fault, or an interrupt, returning the stop reply.
+    fn resume(&mut self, interrupted: &mut impl FnMut() -> io::Result<bool>) -> Result<String> {
+        let mut steps: u64 = 0;
+
+        loop {
+            let reply = self.step();
+            if reply != "S05" {
+                return Ok(reply);
+            }
+
+            if self
+                .breakpoints
+                .contains(&self.emulator.registers()[Register::I])
+            {
+                return Ok(reply);
+            }
+
+            steps += 1;
+            if steps.is_multiple_of(INTERRUPT_INTERVAL) && interrupted()? {
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    /// Returns the stop reply for an exited program, if the program has exited.
+    fn exit_reply(&self) -> Option<String> {
+        self.emulator
+            .exit_code()
+            .map(|exit_code| format!("W{exit_code:02x}"))
+    }
+}
+
+/// Looks up a register by its number in the target description.
+fn register(number: &str) -> Result<Register> {
+    let number = usize::from_str_radix(number, 16)?;
+    Registers::ALL
+        .get(number)
+        .copied()
+        .ok_or_else(|| anyhow!("Unknown register number: {number}"))
+}
+
+/// Parses hex arguments of the form `address,length`.
+fn address_and_length(args: &str) -> Result<(usize, usize)> {
+    let (address, length) = args.split_once(',').context("Expected address,length")?;
+    Ok((
+        usize::from_str_radix(address, 16)?,
+        usize::from_str_radix(length, 16)?,
+    ))
+}
+
+/// Answers a `qXfer` read of `document` given the annex `offset,length`.
+fn xfer(document: &str, annex: &str) -> Result<String> {
+    let (offset, length) = address_and_length(annex)?;
+    let chunk = document
+        .get(offset.min(document.len())..)
+        .unwrap_or_default();
+
+    if chunk.len() > length {
+        Ok(format!("m{}", &chunk[..length]))
+    } else {
+        Ok(format!("l{chunk}"))
+    }
+}
+
+/// Checks whether the client has sent an interrupt, without waiting.
+fn interrupted(stream: &TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0];
+    let result = (&*stream).read(&mut byte);
+    stream.set_nonblocking(false)?;
+
+    match result {
+        Ok(1) => Ok(byte[0] == INTERRUPT),
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpListener, thread};
+
+    use super::*;
+    use crate::{
+        asm::parse_asm_file,
+        emulator::BufferIo,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    /// Stores 0x41 at 0x10, then exits with the value at 0x20.
+    const STORE: &str = "
+        IMM a = 0x10
+        IMM b = 0x41
+        STM *a = b
+        IMM a = 0x20
+        LDM a = *a
+        SYS 0x20 NONE
+    ";
+
+    /// Constructs a server for `STORE`.
+    fn server() -> GdbServer {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file(STORE.to_string()).unwrap(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(b"")));
+
+        GdbServer::new(emulator)
+    }
+
+    /// Handles `packet` without interrupts.
+    fn handle(server: &mut GdbServer, packet: &str) -> String {
+        server.handle(packet, || Ok(false)).unwrap()
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut server = server();
+
+        assert_eq!(handle(&mut server, "s"), "S05");
+        assert_eq!(handle(&mut server, "g"), "10000000000100");
+        assert_eq!(handle(&mut server, "p5"), "01");
+
+        assert_eq!(handle(&mut server, "P1=7f"), "OK");
+        assert_eq!(server.emulator().registers()[Register::B], 0x7f);
+
+        assert_eq!(handle(&mut server, "G01020304050607"), "OK");
+        assert_eq!(server.emulator().registers()[Register::F], 7);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut server = server();
+
+        assert_eq!(handle(&mut server, "M20,2:0506"), "OK");
+        assert_eq!(handle(&mut server, "m1f,3"), "000506");
+        assert_eq!(handle(&mut server, "mff,4"), "00");
+        assert_eq!(handle(&mut server, "m100,1"), "E01");
+        assert_eq!(handle(&mut server, "Mff,2:0000"), "E01");
+        assert_eq!(handle(&mut server, "Mffffffffffffffff,1:00"), "E01");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut server = server();
+
+        assert_eq!(handle(&mut server, "Z0,3,1"), "OK");
+        assert_eq!(handle(&mut server, "c"), "S05");
+        assert_eq!(server.emulator().registers()[Register::I], 3);
+        assert_eq!(server.emulator().memory()[0x10], 0x41);
+
+        assert_eq!(handle(&mut server, "M20,1:2a"), "OK");
+        assert_eq!(handle(&mut server, "z0,3,1"), "OK");
+        assert_eq!(handle(&mut server, "c"), "W2a");
+        assert_eq!(handle(&mut server, "s"), "W2a");
+    }
+
+    #[test]
+    fn test_target_description() {
+        let mut server = server();
+
+        let first = handle(&mut server, "qXfer:features:read:target.xml:0,10");
+        assert_eq!(first, format!("m{}", &TARGET_XML[..0x10]));
+
+        let rest = handle(&mut server, "qXfer:features:read:target.xml:10,1000");
+        assert_eq!(rest, format!("l{}", &TARGET_XML[0x10..]));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut server = server();
+
+        assert_eq!(handle(&mut server, "vMustReplyEmpty"), "");
+        assert_eq!(handle(&mut server, "Z2,10,1"), "");
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"$p5#a5$c#63$k#6b").unwrap();
+
+            let mut replies = String::new();
+            stream.read_to_string(&mut replies).unwrap();
+            replies
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = server();
+        server.serve(stream).unwrap();
+        drop(server);
+
+        assert_eq!(client.join().unwrap(), "+$00#60+$W00#b7+");
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{self, Read, Write};
+
+use anyhow::Result;
+
+/// The byte a client sends to interrupt the running program.
+pub const INTERRUPT: u8 = 0x03;
+
+/// Something received from a GDB client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// A packet, with its framing and checksum removed.
+    Packet(String),
+    /// A request to interrupt the running program.
+    Interrupt,
+}
+
+/// Computes the checksum of a packet's data: the sum of its bytes modulo 256.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Reads the next packet or interrupt from `input`, acknowledging packets with a `+` on `output`.
+/// Returns `None` when the client disconnects.
+///
+/// Acknowledgements from the client are skipped, and packets with bad checksums are answered with a
+/// `-` so that the client retransmits them.
+pub fn read_packet(input: &mut impl Read, output: &mut impl Write) -> Result<Option<Incoming>> {
+    loop {
+        let Some(byte) = read_byte(input)? else {
+            return Ok(None);
+        };
+
+        match byte {
+            INTERRUPT => return Ok(Some(Incoming::Interrupt)),
+            b'$' => {}
+            _ => continue,
+        }
+
+        let mut data = Vec::new();
+        loop {
+            match read_byte(input)? {
+                Some(b'#') => break,
+                Some(byte) => data.push(byte),
+                None => return Ok(None),
+            }
+        }
+
+        let mut digits = [0; 2];
+        input.read_exact(&mut digits)?;
+        let expected = u8::from_str_radix(std::str::from_utf8(&digits)?, 16)?;
+
+        if checksum(&data) != expected {
+            output.write_all(b"-")?;
+            output.flush()?;
+            continue;
+        }
+
+        output.write_all(b"+")?;
+        output.flush()?;
+
+        return Ok(Some(Incoming::Packet(String::from_utf8(data)?)));
+    }
+}
+
+/// Writes `data` as a packet to `output`. Special characters are escaped.
+pub fn write_packet(output: &mut impl Write, data: &str) -> io::Result<()> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data.as_bytes() {
+        match byte {
+            b'$' | b'#' | b'}' | b'*' => escaped.extend([b'}', byte ^ 0x20]),
+            _ => escaped.push(byte),
+        }
+    }
+
+    output.write_all(b"$")?;
+    output.write_all(&escaped)?;
+    write!(output, "#{:02x}", checksum(&escaped))?;
+    output.flush()
+}
+
+/// Reads one byte, or returns `None` at the end of the input.
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_read_packet() {
+        let mut output = Vec::new();
+        let packet = read_packet(&mut Cursor::new("+$g#67"), &mut output).unwrap();
+
+        assert_eq!(packet, Some(Incoming::Packet("g".to_string())));
+        assert_eq!(output, b"+");
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let mut output = Vec::new();
+        let packet = read_packet(&mut Cursor::new("$g#00$g#67"), &mut output).unwrap();
+
+        assert_eq!(packet, Some(Incoming::Packet("g".to_string())));
+        assert_eq!(output, b"-+");
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let packet = read_packet(&mut Cursor::new([INTERRUPT]), &mut Vec::new()).unwrap();
+        assert_eq!(packet, Some(Incoming::Interrupt));
+    }
+
+    #[test]
+    fn test_write_packet() {
+        let mut output = Vec::new();
+        write_packet(&mut output, "OK").unwrap();
+        assert_eq!(output, b"$OK#9a");
+
+        output.clear();
+        write_packet(&mut output, "a#").unwrap();
+        assert_eq!(output, b"$a}\x03#e1");
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::{bail, Context, Result};
+
+/// Encodes `bytes` as pairs of lowercase hex digits.
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a string of hex digit pairs, ignoring whitespace.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        bail!("Odd number of hex digits");
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).with_context(|| format!("Invalid hex byte: {pair}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(encode_hex(&[0x01, 0xab]), "01ab");
+        assert_eq!(decode_hex("01ab").unwrap(), [0x01, 0xab]);
+        assert_eq!(decode_hex("01 ab\n").unwrap(), [0x01, 0xab]);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("0g").is_err());
+    }
+}
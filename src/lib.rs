@@ -4,11 +4,41 @@
 
 /// Yan85 assembler. Converts Yan85 assembly to machine code.
 pub mod asm;
+/// Instruction coverage of Yan85 programs.
+pub mod coverage;
+/// Side-channel recovery of the input a Yan85 program checks.
+pub mod crack;
+/// Debug Adapter Protocol server for debugging Yan85 assembly in editors.
+pub mod dap;
+/// Interactive Yan85 debugger built on the emulator.
+pub mod debugger;
 /// Yan85 disassembler. Converts Yan85 machine code to assembly.
 pub mod disasm;
-/// Yan85 emulator wrapper that steps through instructions indefinitely.
+/// Yan85 emulator wrapper that runs programs until they exit or hit a limit.
 pub mod emu;
 /// Yan85 emulator.
 pub mod emulator;
+/// `Content-Length` framing of the JSON messages exchanged by the DAP and LSP servers.
+mod framing;
+/// GDB remote serial protocol server for debugging emulated programs.
+pub mod gdb;
+/// Hex encoding and decoding of bytes.
+pub mod hex;
+/// Language Server Protocol server for editing Yan85 assembly.
+pub mod lsp;
+/// Hot-spot profiling of Yan85 programs.
+pub mod profile;
+/// Recording of recent steps for crash reports.
+pub mod recorder;
+/// Detection of reads of uninitialized state in Yan85 programs.
+pub mod sanitize;
+/// `strace`-style descriptions of syscalls.
+pub mod strace;
+/// Symbolic execution of Yan85 programs over standard input.
+pub mod symex;
+/// Taint tracking from standard input to comparisons.
+pub mod taint;
+/// Structured execution traces of Yan85 programs.
+pub mod trace;
 /// Yan85 architecture representation structures.
 pub mod yan85;
@@ -0,0 +1,263 @@
+use anyhow::Result;
+
+use crate::{
+    asm::{assemble, parse_asm_program, AsmError, LabelSpan, Program},
+    yan85::{constants::Constants, registers::Registers},
+};
+
+/// The instruction mnemonics offered as completions.
+const MNEMONICS: [&str; 8] = ["IMM", "ADD", "STK", "STM", "LDM", "CMP", "JMP", "SYS"];
+
+/// A range of characters on one line, with zero-based positions as in the Language Server
+/// Protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The line.
+    pub line: usize,
+    /// The first character.
+    pub start: usize,
+    /// The character after the last.
+    pub end: usize,
+}
+
+/// What a completion refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// An instruction mnemonic.
+    Mnemonic,
+    /// A register name.
+    Register,
+    /// A label.
+    Label,
+}
+
+/// An open assembly file.
+#[derive(Debug, Clone)]
+pub struct Document {
+    /// The file's contents.
+    text: String,
+    /// The assembled program, or the error that prevented assembly.
+    program: Result<Program, AsmError>,
+}
+
+impl Document {
+    /// Parses `text`.
+    pub fn new(text: String) -> Self {
+        let program = parse_asm_program(text.clone()).map_err(|e| match e.downcast::<AsmError>() {
+            Ok(error) => error,
+            Err(e) => AsmError {
+                line: 1,
+                message: e.to_string(),
+            },
+        });
+
+        Self { text, program }
+    }
+
+    /// Returns the error that prevented the file from being assembled, if any.
+    pub fn error(&self) -> Option<&AsmError> {
+        self.program.as_ref().err()
+    }
+
+    /// Describes the instruction or label at `line` and `character`: the instruction's index and
+    /// encoding under `constants`, or the index a label refers to.
+    pub fn hover(&self, constants: Constants, line: usize, character: usize) -> Option<String> {
+        let program = self.program.as_ref().ok()?;
+
+        if let Some((span, word)) = self.word_at(line, character) {
+            let is_label = self.is_definition(span) || self.references(word).contains(&span);
+            if let Some(&index) = program.labels.get(word).filter(|_| is_label) {
+                return Some(format!("label `{word}`: instruction `{index:#04x}`"));
+            }
+        }
+
+        let index = program.lines.iter().position(|&l| l == line + 1)?;
+        let bytes = assemble(constants, &program.instructions[index..=index]);
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+
+        Some(format!(
+            "instruction `{index:#04x}`: bytes `{}`",
+            hex.join(" ")
+        ))
+    }
+
+    /// Finds the definition of the label named `name`, if the file assembled.
+    pub fn definition(&self, name: &str) -> Option<Span> {
+        let program = self.program.as_ref().ok()?;
+        program
+            .definitions
+            .iter()
+            .find(|label| label.name == name)
+            .map(Span::from)
+    }
+
+    /// Finds every reference to the label named `name`, not including its definition, if the file
+    /// assembled.
+    pub fn references(&self, name: &str) -> Vec<Span> {
+        let Ok(program) = &self.program else {
+            return Vec::new();
+        };
+
+        program
+            .references
+            .iter()
+            .filter(|label| label.name == name)
+            .map(Span::from)
+            .collect()
+    }
+
+    /// Returns the word at `line` and `character`, along with its span.
+    pub fn word_at(&self, line: usize, character: usize) -> Option<(Span, &str)> {
+        let text = self.text.lines().nth(line)?;
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+        let start = text
+            .get(..character.min(text.len()))?
+            .char_indices()
+            .rfind(|&(_, c)| !is_word(c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let end = text[start..]
+            .find(|c| !is_word(c))
+            .map_or(text.len(), |i| start + i);
+
+        (start < end).then(|| (Span { line, start, end }, &text[start..end]))
+    }
+
+    /// Suggests completions at `line` and `character`: mnemonics at the start of a line, and
+    /// registers and labels after that. Labels are only suggested if the file assembled.
+    pub fn completions(&self, line: usize, character: usize) -> Vec<(String, CompletionKind)> {
+        let text = self.text.lines().nth(line).unwrap_or_default();
+        let before = text.get(..character.min(text.len())).unwrap_or(text);
+
+        if !before.trim_start().contains(char::is_whitespace) {
+            return MNEMONICS
+                .iter()
+                .map(|mnemonic| (mnemonic.to_string(), CompletionKind::Mnemonic))
+                .collect();
+        }
+
+        let registers = Registers::ALL
+            .iter()
+            .map(|register| register.name())
+            .chain(["NONE"])
+            .map(|name| (name.to_string(), CompletionKind::Register));
+        let labels = self
+            .program
+            .iter()
+            .flat_map(|program| program.labels.keys())
+            .map(|label| (label.clone(), CompletionKind::Label));
+
+        registers.chain(labels).collect()
+    }
+
+    /// Returns whether `span` is the name in a label definition.
+    fn is_definition(&self, span: Span) -> bool {
+        self.program.iter().any(|program| {
+            program
+                .definitions
+                .iter()
+                .any(|label| Span::from(label) == span)
+        })
+    }
+}
+
+impl From<&LabelSpan> for Span {
+    fn from(label: &LabelSpan) -> Self {
+        Span {
+            line: label.line - 1,
+            start: label.start,
+            end: label.end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A program with a loop.
+    const LOOP: &str = "IMM d = loop
+loop:
+IMM a = 1
+JMP 0x1 d
+IMM c = loop
+";
+
+    #[test]
+    fn test_error() {
+        let document = Document::new("IMM a = 1\nIMM b\n".to_string());
+        let error = document.error().unwrap();
+
+        assert_eq!(error.line, 2);
+        assert!(Document::new(LOOP.to_string()).error().is_none());
+    }
+
+    #[test]
+    fn test_hover() {
+        let document = Document::new(LOOP.to_string());
+
+        assert_eq!(
+            document.hover(Constants::default(), 2, 0).unwrap(),
+            "instruction `0x01`: bytes `01 01 01`"
+        );
+        assert_eq!(
+            document.hover(Constants::default(), 0, 10).unwrap(),
+            "label `loop`: instruction `0x01`"
+        );
+        assert!(document.hover(Constants::default(), 1, 5).is_none());
+    }
+
+    #[test]
+    fn test_definition_and_references() {
+        let document = Document::new(LOOP.to_string());
+
+        assert_eq!(
+            document.definition("loop"),
+            Some(Span {
+                line: 1,
+                start: 0,
+                end: 4
+            })
+        );
+        assert_eq!(
+            document.references("loop"),
+            [
+                Span {
+                    line: 0,
+                    start: 8,
+                    end: 12
+                },
+                Span {
+                    line: 4,
+                    start: 8,
+                    end: 12
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_word_at() {
+        let document = Document::new(LOOP.to_string());
+
+        let (span, word) = document.word_at(4, 9).unwrap();
+        assert_eq!(word, "loop");
+        assert_eq!(span.start, 8);
+        assert!(document.word_at(4, 7).is_none());
+    }
+
+    #[test]
+    fn test_completions() {
+        let document = Document::new(LOOP.to_string());
+
+        let mnemonics = document.completions(2, 1);
+        assert_eq!(mnemonics.len(), 8);
+        assert!(mnemonics
+            .iter()
+            .all(|(_, kind)| *kind == CompletionKind::Mnemonic));
+
+        let operands = document.completions(2, 4);
+        assert!(operands.contains(&("a".to_string(), CompletionKind::Register)));
+        assert!(operands.contains(&("loop".to_string(), CompletionKind::Label)));
+    }
+}
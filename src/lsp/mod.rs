@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    framing::{read_message, write_message},
+    yan85::constants::Constants,
+};
+
+/// Analysis of open assembly files.
+mod document;
+
+pub use document::{CompletionKind, Document, Span};
+
+/// The JSON-RPC error code for requests the server doesn't support.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code for requests that failed.
+const REQUEST_FAILED: i64 = -32803;
+
+/// Serves the Language Server Protocol for Yan85 assembly files.
+///
+/// Open files are assembled as they change, and errors are published as diagnostics. Hovering
+/// over an instruction shows its encoding under the server's constants, labels support
+/// go-to-definition and find-references, and mnemonics, registers, and labels are completed.
+pub struct LspServer<R, W> {
+    /// The source of client messages.
+    input: R,
+    /// The destination of server messages.
+    output: W,
+    /// The encoding constants used to describe instructions.
+    constants: Constants,
+    /// The open files, by URI.
+    documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> LspServer<R, W> {
+    /// Constructs a server that reads messages from `input`, writes to `output`, and encodes
+    /// instructions with `constants`.
+    pub fn new(input: R, output: W, constants: Constants) -> Self {
+        Self {
+            input,
+            output,
+            constants,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Serves requests until the client sends `exit` or disconnects.
+    pub fn run(&mut self) -> Result<()> {
+        while let Some(message) = read_message(&mut self.input)? {
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+            let Some(id) = message.get("id") else {
+                if method == "exit" {
+                    return Ok(());
+                }
+
+                self.notify(&method, &params)?;
+                continue;
+            };
+
+            let response = match self.handle(&method, &params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(e) => {
+                    let code = if e.downcast_ref::<UnsupportedMethod>().is_some() {
+                        METHOD_NOT_FOUND
+                    } else {
+                        REQUEST_FAILED
+                    };
+
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": e.to_string() },
+                    })
+                }
+            };
+            write_message(&mut self.output, &response)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles a request, returning its result.
+    fn handle(&mut self, method: &str, params: &Value) -> Result<Value> {
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "gyan85" },
+            }),
+            "shutdown" => Value::Null,
+            "textDocument/hover" => {
+                let (document, line, character) = self.position(params)?;
+
+                match document.hover(self.constants, line, character) {
+                    Some(value) => json!({ "contents": { "kind": "markdown", "value": value } }),
+                    None => Value::Null,
+                }
+            }
+            "textDocument/definition" => {
+                let uri = uri(params)?;
+                let (document, line, character) = self.position(params)?;
+
+                document
+                    .word_at(line, character)
+                    .and_then(|(_, word)| document.definition(word))
+                    .map_or(Value::Null, |span| location(uri, span))
+            }
+            "textDocument/references" => {
+                let uri = uri(params)?;
+                let (document, line, character) = self.position(params)?;
+                let Some((_, word)) = document.word_at(line, character) else {
+                    return Ok(json!([]));
+                };
+
+                let declaration = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(false)
+                    .then(|| document.definition(word))
+                    .flatten();
+                let references: Vec<Value> = declaration
+                    .into_iter()
+                    .chain(document.references(word))
+                    .map(|span| location(uri, span))
+                    .collect();
+
+                json!(references)
+            }
+            "textDocument/completion" => {
+                let (document, line, character) = self.position(params)?;
+                let items: Vec<Value> = document
+                    .completions(line, character)
+                    .into_iter()
+                    .map(|(label, kind)| {
+                        let kind = match kind {
+                            CompletionKind::Mnemonic => 14,
+                            CompletionKind::Register => 6,
+                            CompletionKind::Label => 18,
+                        };
+
+                        json!({ "label": label, "kind": kind })
+                    })
+                    .collect();
+
+                json!(items)
+            }
+            _ => return Err(UnsupportedMethod(method.to_string()).into()),
+        };
+
+        Ok(result)
+    }
+
+    /// Handles a notification.
+    fn notify(&mut self, method: &str, params: &Value) -> Result<()> {
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.update(uri(params)?, text)
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"]
+                    .as_array()
+                    .context("Missing contentChanges")?;
+                let Some(text) = changes.last().and_then(|change| change["text"].as_str()) else {
+                    return Ok(());
+                };
+
+                self.update(uri(params)?, text)
+            }
+            "textDocument/didClose" => {
+                let uri = uri(params)?;
+                self.documents.remove(uri);
+                self.publish(uri, Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the contents of the file at `uri` and publishes its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Result<()> {
+        let document = Document::new(text.to_string());
+
+        let diagnostics = document
+            .error()
+            .map(|error| {
+                let line = error.line.saturating_sub(1);
+                let length = text.lines().nth(line).map_or(0, str::len);
+
+                json!({
+                    "range": range(Span { line, start: 0, end: length }),
+                    "severity": 1,
+                    "source": "gyan85",
+                    "message": error.message,
+                })
+            })
+            .into_iter()
+            .collect();
+
+        self.documents.insert(uri.to_string(), document);
+        self.publish(uri, diagnostics)
+    }
+
+    /// Sends the client the diagnostics for the file at `uri`.
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Value>) -> Result<()> {
+        write_message(
+            &mut self.output,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics },
+            }),
+        )
+    }
+
+    /// Returns the file and zero-based position that a request refers to.
+    fn position(&self, params: &Value) -> Result<(&Document, usize, usize)> {
+        let uri = uri(params)?;
+        let document = self
+            .documents
+            .get(uri)
+            .with_context(|| format!("Unknown document: {uri}"))?;
+
+        let position = &params["position"];
+        let line = position["line"].as_u64().context("Missing line")?;
+        let character = position["character"]
+            .as_u64()
+            .context("Missing character")?;
+
+        Ok((document, line as usize, character as usize))
+    }
+}
+
+/// A request with a method the server doesn't support.
+#[derive(Debug)]
+struct UnsupportedMethod(String);
+
+impl std::fmt::Display for UnsupportedMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unsupported method: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedMethod {}
+
+/// Returns the URI of the file that a message refers to.
+fn uri(params: &Value) -> Result<&str> {
+    match params["textDocument"]["uri"].as_str() {
+        Some(uri) => Ok(uri),
+        None => bail!("Missing textDocument.uri"),
+    }
+}
+
+/// Converts `span` to a protocol range.
+fn range(span: Span) -> Value {
+    json!({
+        "start": { "line": span.line, "character": span.start },
+        "end": { "line": span.line, "character": span.end },
+    })
+}
+
+/// Converts `span` in the file at `uri` to a protocol location.
+fn location(uri: &str, span: Span) -> Value {
+    json!({ "uri": uri, "range": range(span) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// The URI of the file opened in tests.
+    const URI: &str = "file:///loop.asm";
+
+    /// A program with a loop.
+    const LOOP: &str = "IMM d = loop
+loop:
+IMM a = 1
+JMP 0x1 d
+";
+
+    /// Runs a session with `messages`, returning the messages sent by the server.
+    fn session(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            let mut message = message.clone();
+            message["jsonrpc"] = json!("2.0");
+            write_message(&mut input, &message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        LspServer::new(Cursor::new(input), &mut output, Constants::default())
+            .run()
+            .unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+
+        messages
+    }
+
+    /// Returns the `didOpen` notification for `text`.
+    fn open(text: &str) -> Value {
+        json!({
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "yan85", "version": 1, "text": text } },
+        })
+    }
+
+    /// Returns a request for `method` at `line` and `character`.
+    fn at(id: u64, method: &str, line: u64, character: u64) -> Value {
+        json!({
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            },
+        })
+    }
+
+    /// Returns the result of the response to the request with `id`.
+    fn result(messages: &[Value], id: u64) -> &Value {
+        &messages.iter().find(|m| m["id"] == id).unwrap()["result"]
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let messages = session(&[
+            json!({ "id": 1, "method": "initialize", "params": {} }),
+            open(LOOP),
+            json!({
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": URI, "version": 2 },
+                    "contentChanges": [{ "text": "IMM d = done\nIMM a = 1\n" }],
+                },
+            }),
+            json!({ "id": 2, "method": "shutdown" }),
+            json!({ "method": "exit" }),
+        ]);
+
+        assert_eq!(
+            result(&messages, 1)["capabilities"]["hoverProvider"],
+            json!(true)
+        );
+
+        let diagnostics: Vec<&Value> = messages
+            .iter()
+            .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+            .map(|m| &m["params"]["diagnostics"])
+            .collect();
+        assert_eq!(diagnostics[0], &json!([]));
+        assert_eq!(
+            diagnostics[1][0]["range"],
+            json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 12 } })
+        );
+        assert_eq!(diagnostics[1][0]["message"], "Undefined label: done");
+    }
+
+    #[test]
+    fn test_navigation() {
+        let messages = session(&[
+            open(LOOP),
+            at(1, "textDocument/hover", 2, 0),
+            at(2, "textDocument/definition", 0, 9),
+            at(3, "textDocument/references", 1, 2),
+            at(4, "textDocument/completion", 3, 0),
+            json!({ "id": 5, "method": "textDocument/formatting", "params": {} }),
+        ]);
+
+        assert_eq!(
+            result(&messages, 1)["contents"]["value"],
+            "instruction `0x01`: bytes `01 01 01`"
+        );
+        assert_eq!(
+            result(&messages, 2)["range"]["start"],
+            json!({ "line": 1, "character": 0 })
+        );
+
+        let references = result(&messages, 3).as_array().unwrap();
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[1]["range"]["start"]["character"], 8);
+
+        let completions = result(&messages, 4).as_array().unwrap();
+        assert!(completions.contains(&json!({ "label": "JMP", "kind": 14 })));
+
+        let error = &messages.iter().find(|m| m["id"] == 5).unwrap()["error"];
+        assert_eq!(error["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -1,16 +1,43 @@
 //! Command-line interface to the assembler, disassembler, and emulator.
 
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::exit,
+    time::Duration,
+};
 
-use anyhow::{anyhow, Context, Result};
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args as ClapArgs, Parser, Subcommand};
 
 use gyan85::{
-    asm::{assemble, parse_asm_file},
+    asm::{assemble, parse_asm_file, parse_asm_program, parse_byte},
+    coverage::Coverage,
+    crack::{crack, CrackOptions, Metric},
+    dap::DapServer,
+    debugger::Debugger,
     disasm::disassemble,
-    emu::emulate,
-    emulator::Emulator,
-    yan85::memory::Memory,
+    emu::{emulate_until, Limits, StopReason},
+    emulator::{
+        BufferIo, Emulator, EmulatorBuilder, FaultPolicy, GuestIo, HostIo, Location, Region,
+        Snapshot, StreamIo, WatchTarget,
+    },
+    gdb::GdbServer,
+    hex::decode_hex,
+    lsp::LspServer,
+    profile::Profile,
+    recorder::FlightRecorder,
+    sanitize::Sanitizer,
+    symex::{explore, model_input, parse_model, SymexOptions},
+    taint::Taint,
+    trace::{diff_traces, read_trace, record, trace, TraceFilter},
+    yan85::{
+        constants::Constants, instruction::Instruction, memory::Memory, register::Register,
+        registers::Registers, stack::Stack,
+    },
 };
 
 /// Supported arguments.
@@ -48,16 +75,430 @@ enum Command {
     #[clap(alias = "emu", alias = "run")]
     Emulate {
         /// Path of the machine code file to emulate.
-        path: PathBuf,
+        #[clap(required_unless_present = "from")]
+        path: Option<PathBuf>,
 
         /// Whether to output the disassembly of each instruction emulated.
         #[clap(short = 'd', long)]
         show_disassembly: bool,
 
+        /// Initial machine state.
+        #[clap(flatten)]
+        state: StateArgs,
+
+        /// Save a snapshot of the machine to PATH and stop when the instruction at INDEX is about
+        /// to be emulated.
+        #[clap(long, num_args = 2, value_names = ["INDEX", "PATH"])]
+        save_at: Vec<String>,
+
+        /// Resume from a saved snapshot instead of starting a program.
+        #[clap(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["path", "memory_image_path", "stack_image_path", "registers"]
+        )]
+        from: Option<PathBuf>,
+
+        /// Limits on how long to run.
+        #[clap(flatten)]
+        limits: LimitArgs,
+
+        /// Record how often each instruction runs and which way each `JMP` goes, and save it to
+        /// PATH.
+        #[clap(long, value_name = "PATH")]
+        coverage: Option<PathBuf>,
+
+        /// Print a ranking of the instructions, blocks, opcodes, and syscalls that run most often.
+        #[clap(long)]
+        profile: bool,
+
+        /// Save the profile to PATH in the folded stack format read by flame graph tools.
+        #[clap(long, value_name = "PATH")]
+        profile_folded: Option<PathBuf>,
+
+        /// Track which bytes of standard input each value depends on, and report every `CMP` of a
+        /// value that depends on input.
+        #[clap(long)]
+        taint: bool,
+
+        /// Warn about reads of registers, memory, and stack slots that were never written, and
+        /// pops of values that were never pushed.
+        #[clap(long)]
+        sanitize: bool,
+
+        /// Print each syscall with its arguments decoded, e.g. `open("/flag", O_RDONLY) = 3`.
+        #[clap(long)]
+        strace: bool,
+
+        /// Write the output of `--strace` to PATH instead of standard error.
+        #[clap(long, value_name = "PATH", requires = "strace")]
+        strace_output: Option<PathBuf>,
+
+        /// Keep the last STEPS steps, and if the program faults, print them with the registers,
+        /// stack, and the memory they accessed.
+        #[clap(long, value_name = "STEPS", default_value_t = 16)]
+        flight_recorder: usize,
+
+        /// Wait for a GDB client on ADDRESS, e.g. `127.0.0.1:1234`, and let it control the program
+        /// over the remote serial protocol.
+        #[clap(long, value_name = "ADDRESS")]
+        gdb: Option<String>,
+
+        /// How to handle guest behaviors like stack overflows and out-of-bounds accesses. Defaults to
+        /// stop, or to the snapshot's policy when resuming with --from.
+        #[clap(long, value_enum)]
+        on_fault: Option<FaultPolicy>,
+
+        /// Guest standard stream redirection.
+        #[clap(flatten)]
+        io: IoArgs,
+    },
+
+    /// Emulate the supplied Yan85 machine code, writing a JSON Lines trace of each step.
+    #[clap(alias = "tr")]
+    Trace {
+        /// Path of the machine code file to trace.
+        path: PathBuf,
+
+        /// Initial machine state.
+        #[clap(flatten)]
+        state: StateArgs,
+
+        /// Path of a file to write the trace to, instead of standard output.
+        #[clap(short = 'o', long = "output")]
+        output_path: Option<PathBuf>,
+
+        /// Only record syscalls.
+        #[clap(long)]
+        syscalls_only: bool,
+
+        /// Limits on how long to run.
+        #[clap(flatten)]
+        limits: LimitArgs,
+
+        /// Only record steps that access a register or a memory or stack range, e.g. `c` or
+        /// `mem[0x40..0x50]`. May be repeated.
+        #[clap(long = "touching")]
+        targets: Vec<WatchTarget>,
+
+        /// How to handle guest behaviors like stack overflows and out-of-bounds accesses.
+        #[clap(long, value_enum, default_value_t = FaultPolicy::Stop)]
+        on_fault: FaultPolicy,
+
+        /// Guest standard stream redirection.
+        #[clap(flatten)]
+        io: IoArgs,
+    },
+
+    /// Find where two runs of a Yan85 program diverge, e.g. with different input.
+    #[clap(alias = "tdiff")]
+    TraceDiff {
+        /// Path of the machine code file to run twice.
+        #[clap(required_unless_present = "traces")]
+        path: Option<PathBuf>,
+
+        /// Compare two saved traces instead of running a program.
+        #[clap(long, num_args = 2, value_names = ["A", "B"], conflicts_with = "path")]
+        traces: Vec<PathBuf>,
+
+        /// Path of a file to use as standard input for the first run.
+        #[clap(long, conflicts_with = "stdin_hex_a")]
+        stdin_file_a: Option<PathBuf>,
+
+        /// Hex-encoded standard input for the first run.
+        #[clap(long)]
+        stdin_hex_a: Option<String>,
+
+        /// Path of a file to use as standard input for the second run.
+        #[clap(long, conflicts_with = "stdin_hex_b")]
+        stdin_file_b: Option<PathBuf>,
+
+        /// Hex-encoded standard input for the second run.
+        #[clap(long)]
+        stdin_hex_b: Option<String>,
+
+        /// Path to an initial memory image for the first run.
+        #[clap(long)]
+        memory_image_a: Option<PathBuf>,
+
+        /// Path to an initial memory image for the second run.
+        #[clap(long)]
+        memory_image_b: Option<PathBuf>,
+
+        /// Limits on how long to run each program.
+        #[clap(flatten)]
+        limits: LimitArgs,
+
+        /// How to handle guest behaviors like stack overflows and out-of-bounds accesses.
+        #[clap(long, value_enum, default_value_t = FaultPolicy::Stop)]
+        on_fault: FaultPolicy,
+    },
+
+    /// Recover the input a Yan85 program checks, using instruction counts as a side channel.
+    Crack {
+        /// Path of the machine code file to crack.
+        path: PathBuf,
+
         /// Path to an initial Yan85 memory image.
         #[clap(short = 'm', long = "memory-image")]
         memory_image_path: Option<PathBuf>,
+
+        /// The length of the input the program reads.
+        #[clap(short = 'n', long)]
+        length: usize,
+
+        /// The characters to try at each position of the input. Defaults to printable ASCII.
+        #[clap(long)]
+        alphabet: Option<String>,
+
+        /// The character used for positions that haven't been recovered yet.
+        #[clap(long, default_value_t = 'A')]
+        padding: char,
+
+        /// Stop once the instruction at this index is reached, e.g. the `OPEN` of `/flag`.
+        #[clap(long, value_parser = parse_byte)]
+        target: Option<u8>,
+
+        /// How candidate inputs are ranked.
+        #[clap(long, value_enum, default_value_t = Metric::Steps)]
+        metric: Metric,
+
+        /// Limits on how long to run each candidate.
+        #[clap(flatten)]
+        limits: LimitArgs,
+
+        /// How to handle guest behaviors like stack overflows and out-of-bounds accesses.
+        #[clap(long, value_enum, default_value_t = FaultPolicy::Stop)]
+        on_fault: FaultPolicy,
     },
+
+    /// Find standard input that reaches an instruction by executing a Yan85 program symbolically.
+    Symex {
+        /// Path of the machine code file to explore.
+        #[clap(required_unless_present = "model")]
+        path: Option<PathBuf>,
+
+        /// Path to an initial Yan85 memory image.
+        #[clap(short = 'm', long = "memory-image")]
+        memory_image_path: Option<PathBuf>,
+
+        /// The index of the instruction to reach, e.g. the `OPEN` of `/flag`.
+        #[clap(long, value_parser = parse_byte, required_unless_present = "model")]
+        target: Option<u8>,
+
+        /// Write the constraints of the path to the target as SMT-LIB2 to this file. If the
+        /// built-in solver gives up, the constraints of the path it gave up on are written instead.
+        #[clap(long, value_name = "PATH")]
+        emit_smt: Option<PathBuf>,
+
+        /// Convert the output of an SMT solver run on a script from `--emit-smt` to input instead
+        /// of exploring a program.
+        #[clap(long, value_name = "PATH", conflicts_with_all = ["path", "emit_smt"])]
+        model: Option<PathBuf>,
+
+        /// The maximum number of steps to run each path for.
+        #[clap(long, default_value_t = 100_000)]
+        max_steps: u64,
+
+        /// The maximum number of paths to explore.
+        #[clap(long, default_value_t = 10_000)]
+        max_paths: usize,
+
+        /// The character used for input that doesn't affect whether the target is reached.
+        #[clap(long, default_value_t = 'A')]
+        padding: char,
+
+        /// Path of a file to save the input to.
+        #[clap(short = 'o', long = "output")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Show the disassembly of Yan85 machine code annotated with coverage from `emulate --coverage`.
+    #[clap(alias = "cov")]
+    Coverage {
+        /// Path of the machine code file the coverage was recorded for.
+        path: PathBuf,
+
+        /// Paths of coverage files to merge.
+        #[clap(required = true)]
+        coverage_paths: Vec<PathBuf>,
+
+        /// Path of a file to save the merged coverage to.
+        #[clap(short = 'o', long = "output")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Serve the Debug Adapter Protocol over standard input and output, for debugging Yan85
+    /// assembly in editors like VS Code.
+    Dap,
+
+    /// Serve the Language Server Protocol over standard input and output, for editing Yan85
+    /// assembly.
+    Lsp,
+
+    /// Debug the supplied Yan85 machine code interactively.
+    #[clap(alias = "dbg")]
+    Debug {
+        /// Path of the machine code file to debug.
+        path: PathBuf,
+
+        /// Initial machine state.
+        #[clap(flatten)]
+        state: StateArgs,
+
+        /// Path of the assembly the machine code was assembled from, for resolving labels.
+        #[clap(short = 's', long = "source")]
+        source_path: Option<PathBuf>,
+
+        /// The number of recent steps recorded for reverse execution.
+        #[clap(long, default_value_t = 100_000)]
+        history: usize,
+
+        /// How to handle guest behaviors like stack overflows and out-of-bounds accesses.
+        #[clap(long, value_enum, default_value_t = FaultPolicy::Stop)]
+        on_fault: FaultPolicy,
+
+        /// Guest standard stream redirection.
+        #[clap(flatten)]
+        io: IoArgs,
+    },
+}
+
+/// Options for setting the machine's initial state.
+#[derive(ClapArgs, Debug, Clone)]
+struct StateArgs {
+    /// Path to an initial Yan85 memory image.
+    #[clap(short = 'm', long = "memory-image")]
+    memory_image_path: Option<PathBuf>,
+
+    /// Path to an initial Yan85 stack image.
+    #[clap(long = "stack-image")]
+    stack_image_path: Option<PathBuf>,
+
+    /// An initial register value, e.g. `a=0x10`. May be repeated.
+    #[clap(long = "reg", value_name = "REGISTER=VALUE", value_parser = parse_register_value)]
+    registers: Vec<(Register, u8)>,
+
+    /// Protect a memory range from the guest, e.g. `mem[0x00..0x40]=read-only`. The protection is
+    /// one of `read-only`, `no-access`, or `write-once`. May be repeated.
+    #[clap(long = "protect", value_name = "RANGE=PROTECTION")]
+    regions: Vec<Region>,
+}
+
+impl StateArgs {
+    /// Returns the locations given initial values.
+    fn initialized(&self) -> Vec<Location> {
+        let mut locations = Vec::new();
+        if self.memory_image_path.is_some() {
+            locations.extend((0..=u8::MAX).map(Location::Memory));
+        }
+        if self.stack_image_path.is_some() {
+            locations.extend((0..=u8::MAX).map(Location::Stack));
+        }
+        locations.extend(self.registers.iter().map(|&(r, _)| Location::Register(r)));
+
+        locations
+    }
+
+    /// Starts building an emulator for `instructions` with the requested initial state.
+    fn into_builder(
+        self,
+        consts: Constants,
+        instructions: Vec<Instruction>,
+    ) -> Result<EmulatorBuilder> {
+        let mut builder = EmulatorBuilder::new(consts)
+            .code(instructions)
+            .memory(read_memory_image(self.memory_image_path)?);
+
+        if let Some(path) = self.stack_image_path {
+            builder = builder.stack(Stack::from(read_image(path, "Stack")?));
+        }
+
+        for (register, value) in self.registers {
+            builder = builder.register(register, value);
+        }
+
+        for region in self.regions {
+            builder = builder.protect(region);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Options for stopping programs that run too long.
+#[derive(ClapArgs, Debug, Clone)]
+struct LimitArgs {
+    /// Stop after emulating this many steps.
+    #[clap(long)]
+    max_steps: Option<u64>,
+
+    /// Stop after running for this many seconds.
+    #[clap(long, value_name = "SECONDS")]
+    timeout: Option<f64>,
+
+    /// Stop when the program returns to an earlier state without making a syscall in between,
+    /// i.e. when it's stuck in an infinite loop.
+    #[clap(long)]
+    detect_loops: bool,
+}
+
+impl LimitArgs {
+    /// Converts the options to [`Limits`].
+    fn limits(&self) -> Result<Limits> {
+        Ok(Limits {
+            max_steps: self.max_steps,
+            timeout: self.timeout.map(Duration::try_from_secs_f64).transpose()?,
+            detect_loops: self.detect_loops,
+        })
+    }
+}
+
+/// Options for redirecting the guest's standard streams.
+#[derive(ClapArgs, Debug, Clone)]
+struct IoArgs {
+    /// Path of a file to use as the guest's standard input.
+    #[clap(long, conflicts_with = "stdin_hex")]
+    stdin_file: Option<PathBuf>,
+
+    /// Hex-encoded bytes to use as the guest's standard input, e.g. `41424344`.
+    #[clap(long)]
+    stdin_hex: Option<String>,
+
+    /// Path of a file to write the guest's standard output to.
+    #[clap(long)]
+    stdout_file: Option<PathBuf>,
+}
+
+impl IoArgs {
+    /// Constructs the guest's standard streams as requested.
+    fn into_io(self) -> Result<Box<dyn GuestIo>> {
+        if self.stdin_file.is_none() && self.stdin_hex.is_none() && self.stdout_file.is_none() {
+            return Ok(Box::new(HostIo));
+        }
+
+        let stdin: Box<dyn Read> = match (self.stdin_file, self.stdin_hex) {
+            (Some(path), _) => Box::new(File::open(path)?),
+            (None, Some(hex)) => Box::new(io::Cursor::new(decode_hex(&hex)?)),
+            (None, None) => Box::new(io::stdin()),
+        };
+
+        let stdout: Box<dyn Write> = match self.stdout_file {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+
+        Ok(Box::new(StreamIo::new(stdin, stdout)))
+    }
+}
+
+/// Parses an initial register value of the form `REGISTER=VALUE`, e.g. `a=0x10`.
+fn parse_register_value(value: &str) -> Result<(Register, u8)> {
+    let (name, byte) = value
+        .split_once('=')
+        .with_context(|| format!("Expected REGISTER=VALUE: {value}"))?;
+
+    Ok((Registers::by_name(name)?, parse_byte(byte)?))
 }
 
 fn main() -> Result<()> {
@@ -93,26 +534,428 @@ fn main() -> Result<()> {
         Command::Emulate {
             path,
             show_disassembly,
-            memory_image_path,
+            state,
+            save_at,
+            from,
+            limits,
+            coverage: coverage_path,
+            profile: show_profile,
+            profile_folded,
+            taint: track_taint,
+            sanitize,
+            strace,
+            strace_output,
+            flight_recorder,
+            gdb,
+            on_fault,
+            io,
+        } => {
+            let initialized = match from {
+                Some(_) => Registers::ALL
+                    .into_iter()
+                    .map(Location::Register)
+                    .chain((0..=u8::MAX).map(Location::Memory))
+                    .chain((0..=u8::MAX).map(Location::Stack))
+                    .collect(),
+                None => state.initialized(),
+            };
+            let mut emulator = match (path, from) {
+                (_, Some(from)) => {
+                    let mut emulator = Emulator::new(consts, Vec::new(), Memory::default());
+                    emulator.restore(&Snapshot::load(from)?)?;
+                    for region in state.regions {
+                        emulator.protect(region);
+                    }
+
+                    emulator
+                }
+                (Some(path), None) => {
+                    let bytes = fs::read(path)?;
+                    let disassembly = disassemble(consts, bytes)?;
+
+                    state.into_builder(consts, disassembly)?.build()
+                }
+                (None, None) => bail!("Expected a program or a snapshot"),
+            };
+            emulator.set_io(io.into_io()?);
+            if let Some(policy) = on_fault {
+                emulator.set_fault_policy(policy);
+            }
+
+            if let Some(address) = gdb {
+                let listener = TcpListener::bind(&address)?;
+                eprintln!("waiting for GDB on {}", listener.local_addr()?);
+                let (stream, client) = listener.accept()?;
+                eprintln!("GDB connected from {client}");
+
+                let mut server = GdbServer::new(emulator);
+                server.serve(stream)?;
+
+                match server.emulator().exit_code() {
+                    Some(exit_code) => exit(exit_code.into()),
+                    None => return Ok(()),
+                }
+            }
+
+            let save_at = match save_at.as_slice() {
+                [index, path] => Some((parse_byte(index)?, PathBuf::from(path))),
+                _ => None,
+            };
+            let stop = |emulator: &Emulator| {
+                save_at
+                    .as_ref()
+                    .is_some_and(|&(index, _)| emulator.registers()[Register::I] == index)
+            };
+
+            let limits = limits.limits()?;
+
+            let profiling = show_profile || profile_folded.is_some();
+            let mut coverage = Coverage::default();
+            let mut profile = Profile::default();
+            let mut taint = Taint::default();
+            let mut strace_log: Box<dyn Write> = match strace_output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stderr()),
+            };
+            let mut strace_error = None;
+            let mut recorder = FlightRecorder::new(flight_recorder);
+            let mut sanitizer = Sanitizer::new(&emulator);
+            for location in initialized {
+                sanitizer.initialize(location);
+            }
+            let on_step = |emulator: &Emulator, instruction| {
+                if show_disassembly {
+                    println!("{instruction}");
+                }
+                recorder.record(emulator, instruction);
+                if let Some(syscall) = emulator.last_syscall().filter(|_| strace) {
+                    let line = syscall.strace(consts, emulator.memory());
+                    if let Err(error) = writeln!(strace_log, "{line}") {
+                        strace_error.get_or_insert(error);
+                    }
+                }
+                if coverage_path.is_some() {
+                    coverage.record(emulator, instruction);
+                }
+                if profiling {
+                    profile.record(emulator, instruction);
+                }
+                if track_taint {
+                    if let Some(comparison) = taint.record(emulator, instruction) {
+                        eprintln!("{comparison}");
+                    }
+                }
+                if sanitize {
+                    for warning in sanitizer.record(emulator, instruction) {
+                        eprintln!("warning: {warning}");
+                    }
+                }
+            };
+
+            let result = emulate_until(&mut emulator, &limits, stop, on_step);
+            if result.is_err() && flight_recorder > 0 {
+                eprint!("{}", recorder.report(&emulator));
+            }
+            let strace_result = match strace_error {
+                Some(error) => Err(error),
+                None => strace_log.flush(),
+            };
+            if let Some(path) = coverage_path {
+                coverage.save(path)?;
+            }
+            if show_profile {
+                eprint!("{}", profile.report(consts, emulator.instructions()));
+            }
+            if let Some(path) = profile_folded {
+                profile.write_folded(BufWriter::new(File::create(path)?))?;
+            }
+            strace_result?;
+
+            match result? {
+                StopReason::Exited(exit_code) => exit(exit_code.into()),
+                StopReason::Stopped => {
+                    let (_, path) = save_at.expect("emulation only stops at --save-at");
+                    emulator.snapshot()?.save(&path)?;
+                    eprintln!("saved snapshot after {} steps", emulator.steps());
+
+                    Ok(())
+                }
+                reason => bail!("The program {reason} after {} steps", emulator.steps()),
+            }
+        }
+        Command::Trace {
+            path,
+            state,
+            output_path,
+            syscalls_only,
+            limits,
+            targets,
+            on_fault,
+            io,
         } => {
             let bytes = fs::read(path)?;
             let disassembly = disassemble(consts, bytes)?;
 
-            let memory = match memory_image_path {
-                Some(path) => {
-                    let image: [u8; 256] = fs::read(path)?
-                        .try_into()
-                        .map_err(|_| anyhow!("Memory image of wrong size"))?;
+            let mut emulator = state
+                .into_builder(consts, disassembly)?
+                .io(io.into_io()?)
+                .fault_policy(on_fault)
+                .build();
+
+            let filter = TraceFilter {
+                syscalls_only,
+                targets,
+            };
+            let output: Box<dyn Write> = match output_path {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout().lock()),
+            };
+            match trace(&mut emulator, &limits.limits()?, &filter, output)? {
+                StopReason::Exited(exit_code) => exit(exit_code.into()),
+                reason => bail!("The program {reason} after {} steps", emulator.steps()),
+            }
+        }
+        Command::TraceDiff {
+            path,
+            traces,
+            stdin_file_a,
+            stdin_hex_a,
+            stdin_file_b,
+            stdin_hex_b,
+            memory_image_a,
+            memory_image_b,
+            limits,
+            on_fault,
+        } => {
+            let (a, b) = match (path, traces.as_slice()) {
+                (Some(path), _) => {
+                    let bytes = fs::read(path)?;
+                    let instructions = disassemble(consts, bytes)?;
+                    let limits = limits.limits()?;
+
+                    let run = |name, stdin_file, stdin_hex, memory_image| -> Result<_> {
+                        let mut emulator = Emulator::new(
+                            consts,
+                            instructions.clone(),
+                            read_memory_image(memory_image)?,
+                        );
+                        emulator
+                            .set_io(Box::new(BufferIo::new(read_stdin(stdin_file, stdin_hex)?)));
+                        emulator.set_fault_policy(on_fault);
+
+                        let (events, reason) = record(&mut emulator, &limits);
+                        match reason {
+                            Ok(reason) => {
+                                println!("run {name} {reason} after {} steps", emulator.steps())
+                            }
+                            Err(e) => {
+                                println!(
+                                    "run {name} failed after {} steps: {e:#}",
+                                    emulator.steps()
+                                )
+                            }
+                        }
+
+                        Ok(events)
+                    };
+
+                    let a = run("a", stdin_file_a, stdin_hex_a, memory_image_a)?;
+                    let b = run("b", stdin_file_b, stdin_hex_b, memory_image_b)?;
+
+                    (a, b)
+                }
+                (None, [a, b]) => (
+                    read_trace(BufReader::new(File::open(a)?)).collect::<Result<_>>()?,
+                    read_trace(BufReader::new(File::open(b)?)).collect::<Result<_>>()?,
+                ),
+                _ => bail!("Expected a program or two traces"),
+            };
 
-                    Memory::from(image)
+            print!("{}", diff_traces(a, b));
+            Ok(())
+        }
+        Command::Crack {
+            path,
+            memory_image_path,
+            length,
+            alphabet,
+            padding,
+            target,
+            metric,
+            limits,
+            on_fault,
+        } => {
+            let bytes = fs::read(path)?;
+            let instructions = disassemble(consts, bytes)?;
+            let memory = read_memory_image(memory_image_path)?;
+
+            let defaults = CrackOptions::default();
+            let limits = Limits {
+                max_steps: limits.max_steps.or(defaults.limits.max_steps),
+                ..limits.limits()?
+            };
+            let options = CrackOptions {
+                alphabet: alphabet.map_or(defaults.alphabet, String::into_bytes),
+                length,
+                padding: u8::try_from(padding).context("The padding must be a single byte")?,
+                target,
+                metric,
+                limits,
+                fault_policy: on_fault,
+            };
+
+            let crack = crack(consts, &instructions, &memory, &options, |prefix| {
+                eprintln!("{}", prefix.escape_ascii());
+            });
+
+            println!("{}", crack.input.escape_ascii());
+            if target.is_some() && !crack.reached_target {
+                bail!("The recovered input doesn't reach the target instruction");
+            }
+
+            Ok(())
+        }
+        Command::Symex {
+            path,
+            memory_image_path,
+            target,
+            emit_smt,
+            model,
+            max_steps,
+            max_paths,
+            padding,
+            output_path,
+        } => {
+            let padding = u8::try_from(padding).context("The padding must be a single byte")?;
+
+            let input = match (path, target, model) {
+                (_, _, Some(model)) => {
+                    let model = parse_model(&fs::read_to_string(model)?)?;
+                    let length = model.keys().last().map_or(0, |&offset| offset + 1);
+                    model_input(&model, length, padding)
                 }
-                None => Memory::default(),
+                (Some(path), Some(target), None) => {
+                    let bytes = fs::read(path)?;
+                    let instructions = disassemble(consts, bytes)?;
+                    let memory = read_memory_image(memory_image_path)?;
+
+                    let options = SymexOptions {
+                        target,
+                        max_steps,
+                        max_paths,
+                        padding,
+                        ..Default::default()
+                    };
+                    let exploration = explore(consts, &instructions, &memory, &options);
+                    eprintln!(
+                        "Explored {} paths ({} abandoned)",
+                        exploration.paths, exploration.abandoned
+                    );
+
+                    let path = match (&exploration.reached, &exploration.unsolved) {
+                        (Some(reached), _) => Some(&reached.path),
+                        (None, unsolved) => unsolved.as_ref(),
+                    };
+                    if let (Some(smt_path), Some(path)) = (emit_smt, path) {
+                        fs::write(&smt_path, path.smtlib(consts.flag).to_string())?;
+                        eprintln!(
+                            "Saved the constraints of a path with {} steps to {}",
+                            path.steps,
+                            smt_path.display()
+                        );
+                    }
+
+                    match exploration.reached {
+                        Some(reached) => reached.input,
+                        None if exploration.unsolved.is_some() => bail!(
+                            "The solver gave up on a path to instruction {target:#04x}; use \
+                             --emit-smt to solve it with an SMT solver"
+                        ),
+                        None => bail!("No input found that reaches instruction {target:#04x}"),
+                    }
+                }
+                _ => bail!("Expected a program and target, or a model"),
             };
 
-            let mut emulator = Emulator::new(consts, disassembly, memory);
-            emulate(&mut emulator, show_disassembly)?;
+            println!("{}", input.escape_ascii());
+            if let Some(output_path) = output_path {
+                fs::write(output_path, &input)?;
+            }
 
             Ok(())
         }
+        Command::Coverage {
+            path,
+            coverage_paths,
+            output_path,
+        } => {
+            let bytes = fs::read(path)?;
+            let instructions = disassemble(consts, bytes)?;
+
+            let mut coverage = Coverage::default();
+            for path in coverage_paths {
+                coverage.merge(&Coverage::load(path)?);
+            }
+
+            if let Some(path) = output_path {
+                coverage.save(path)?;
+            }
+
+            print!("{}", coverage.annotate(&instructions));
+            Ok(())
+        }
+        Command::Dap => DapServer::new(BufReader::new(io::stdin()), io::stdout(), consts).run(),
+        Command::Lsp => LspServer::new(io::stdin().lock(), io::stdout(), consts).run(),
+        Command::Debug {
+            path,
+            state,
+            source_path,
+            history,
+            on_fault,
+            io,
+        } => {
+            let bytes = fs::read(path)?;
+            let disassembly = disassemble(consts, bytes)?;
+
+            let labels = match source_path {
+                Some(path) => parse_asm_program(fs::read_to_string(path)?)?.labels,
+                None => BTreeMap::new(),
+            };
+
+            let emulator = state
+                .into_builder(consts, disassembly)?
+                .io(io.into_io()?)
+                .fault_policy(on_fault)
+                .history_size(history)
+                .build();
+
+            let mut debugger = Debugger::new(emulator, labels);
+            debugger.run(io::stdin().lock(), io::stdout())
+        }
+    }
+}
+
+/// Reads guest standard input from the file at `path` or decodes it from `hex`, or returns no input
+/// if there is neither.
+fn read_stdin(path: Option<PathBuf>, hex: Option<String>) -> Result<Vec<u8>> {
+    match (path, hex) {
+        (Some(path), _) => Ok(fs::read(path)?),
+        (None, Some(hex)) => decode_hex(&hex),
+        (None, None) => Ok(Vec::new()),
     }
 }
+
+/// Reads the memory image at `path`, or returns zeroed memory if there is none.
+fn read_memory_image(path: Option<PathBuf>) -> Result<Memory> {
+    match path {
+        Some(path) => Ok(Memory::from(read_image(path, "Memory")?)),
+        None => Ok(Memory::default()),
+    }
+}
+
+/// Reads the 256-byte `kind` image at `path`.
+fn read_image(path: PathBuf, kind: &str) -> Result<[u8; 256]> {
+    fs::read(path)?
+        .try_into()
+        .map_err(|_| anyhow!("{kind} image of wrong size"))
+}
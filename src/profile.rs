@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    emulator::Emulator,
+    yan85::{constants::Constants, instruction::Instruction},
+};
+
+/// The number of entries shown in each ranking of a [`Report`].
+const TOP: usize = 20;
+
+/// Execution statistics for a basic block, i.e. a run of instructions that's entered at the start
+/// and only left at the end.
+///
+/// Blocks start at every instruction that was jumped to or that followed a jump or syscall, and end
+/// before the next such instruction, so no two blocks overlap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockStats {
+    /// The number of times the block was entered.
+    pub entries: u64,
+    /// The index of the last instruction in the block that was executed.
+    pub end: u8,
+    /// The number of instructions executed in the block.
+    pub steps: u64,
+}
+
+/// Counts how often each part of a program runs, for finding hot spots.
+///
+/// Feed it each step with [`Profile::record`], e.g. from the `on_step` callback of
+/// [`emulate_until`](crate::emu::emulate_until).
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// The number of steps recorded.
+    pub steps: u64,
+    /// The number of times each instruction was executed, keyed by index.
+    pub instructions: BTreeMap<u8, u64>,
+    /// The number of times each opcode was executed, keyed by mnemonic.
+    pub opcodes: BTreeMap<&'static str, u64>,
+    /// The number of times each syscall was made, keyed by syscall number.
+    pub syscalls: BTreeMap<u8, u64>,
+    /// The wall-clock time syscalls spent in host I/O, as measured by
+    /// [`Emulator::last_io_time`].
+    pub io_time: Duration,
+    /// The indices of the instructions that start basic blocks.
+    leaders: BTreeSet<u8>,
+    /// The index of the previous instruction, unless it ended a block.
+    previous: Option<u8>,
+}
+
+impl Profile {
+    /// Records the step that `emulator` just took, which emulated `instruction`.
+    pub fn record(&mut self, emulator: &Emulator, instruction: Instruction) {
+        let index = emulator.last_index();
+
+        if self
+            .previous
+            .is_none_or(|previous| previous.wrapping_add(1) != index)
+        {
+            self.leaders.insert(index);
+        }
+
+        self.steps += 1;
+        *self.instructions.entry(index).or_default() += 1;
+        *self.opcodes.entry(instruction.mnemonic()).or_default() += 1;
+
+        if let Some(syscall) = emulator.last_syscall() {
+            *self.syscalls.entry(syscall.number).or_default() += 1;
+            self.io_time += emulator.last_io_time();
+        }
+
+        // jumps and syscalls end blocks, even when they fall through
+        self.previous = match instruction {
+            Instruction::JMP(..) | Instruction::SYS(..) => None,
+            _ => Some(index),
+        };
+    }
+
+    /// Returns the basic blocks, keyed by the index of their first instruction.
+    pub fn blocks(&self) -> BTreeMap<u8, BlockStats> {
+        let mut blocks: BTreeMap<u8, BlockStats> = BTreeMap::new();
+        for (&index, &count) in &self.instructions {
+            let Some(block) = self.block_of(index) else {
+                continue;
+            };
+
+            let stats = blocks.entry(block).or_default();
+            if index == block {
+                stats.entries = count;
+            }
+            stats.end = stats.end.max(index);
+            stats.steps += count;
+        }
+
+        blocks
+    }
+
+    /// Returns the first instruction of the basic block that the instruction at `index` is in.
+    ///
+    /// Execution only reaches an instruction by falling through from its block's start, so the
+    /// block is the one with the closest start at or before it.
+    fn block_of(&self, index: u8) -> Option<u8> {
+        self.leaders.range(..=index).next_back().copied()
+    }
+
+    /// Returns a ranked report of the profile, for display.
+    pub fn report<'a>(
+        &'a self,
+        constants: Constants,
+        instructions: &'a [Instruction],
+    ) -> Report<'a> {
+        Report {
+            profile: self,
+            constants,
+            instructions,
+        }
+    }
+
+    /// Writes the profile in the folded stack format read by flame graph tools like `inferno` and
+    /// `flamegraph.pl`. Each line has a block and an instruction in it, weighted by the number of
+    /// times the instruction was executed.
+    pub fn write_folded(&self, mut output: impl Write) -> io::Result<()> {
+        for (&index, count) in &self.instructions {
+            if let Some(block) = self.block_of(index) {
+                writeln!(output, "block_{block:#04x};{index:#04x} {count}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`Profile`] ranked by execution count.
+pub struct Report<'a> {
+    /// The profile.
+    profile: &'a Profile,
+    /// The constants, for naming syscalls.
+    constants: Constants,
+    /// The program, for disassembling hot instructions.
+    instructions: &'a [Instruction],
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let profile = self.profile;
+        let steps = profile.steps.max(1) as f64;
+        let percent = |count: u64| 100.0 * count as f64 / steps;
+
+        writeln!(
+            f,
+            "{} steps, {:.3?} in host I/O",
+            profile.steps, profile.io_time
+        )?;
+
+        writeln!(f, "\nhot instructions:")?;
+        for (index, count) in ranked(&profile.instructions) {
+            write!(f, "{count:>10} {:>5.1}%  {index:#04x}", percent(count))?;
+            match self.instructions.get(usize::from(index)) {
+                Some(instruction) => writeln!(f, ": {instruction}")?,
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(f, "\nhot blocks:")?;
+        let blocks = profile.blocks();
+        let counts: BTreeMap<u8, u64> = blocks
+            .iter()
+            .map(|(&start, block)| (start, block.steps))
+            .collect();
+        for (start, count) in ranked(&counts) {
+            let block = blocks[&start];
+            writeln!(
+                f,
+                "{count:>10} {:>5.1}%  {start:#04x}..={:#04x} (entered {} times)",
+                percent(count),
+                block.end,
+                block.entries
+            )?;
+        }
+
+        writeln!(f, "\nopcodes:")?;
+        for (mnemonic, count) in ranked(&profile.opcodes) {
+            writeln!(f, "{count:>10} {:>5.1}%  {mnemonic}", percent(count))?;
+        }
+
+        if !profile.syscalls.is_empty() {
+            writeln!(f, "\nsyscalls:")?;
+            for (number, count) in ranked(&profile.syscalls) {
+                match self.constants.syscall.name(number) {
+                    Some(name) => writeln!(f, "{count:>10}  {name}")?,
+                    None => writeln!(f, "{count:>10}  {number:#04x}")?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the most common entries of `counts`, most common first.
+fn ranked<K: Copy + Ord>(counts: &BTreeMap<K, u64>) -> Vec<(K, u64)> {
+    let mut ranked: Vec<(K, u64)> = counts.iter().map(|(&key, &count)| (key, count)).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked.truncate(TOP);
+
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::parse_asm_file,
+        emu::{emulate_until, Limits},
+        emulator::BufferIo,
+        yan85::memory::Memory,
+    };
+
+    /// Writes "hi" three times in a loop, then exits.
+    const LOOP: &str = "
+        IMM a = 0x40
+        IMM b = 0x68
+        STM *a = b
+        IMM a = 0x41
+        IMM b = 0x69
+        STM *a = b
+        IMM d = 3
+        IMM c = 0
+        loop:
+        IMM a = 1
+        IMM b = 0x40
+        IMM c = 2
+        SYS 0x8 NONE
+        IMM b = 0xff
+        ADD d b
+        IMM b = 0
+        IMM c = loop
+        CMP d b
+        JMP 0x8 c
+        SYS 0x20 NONE
+    ";
+
+    /// Profiles `LOOP`.
+    fn profile() -> (Profile, Vec<Instruction>) {
+        let instructions = parse_asm_file(LOOP.to_string()).unwrap();
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            instructions.clone(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(b"")));
+
+        let mut profile = Profile::default();
+        emulate_until(
+            &mut emulator,
+            &Limits::default(),
+            |_| false,
+            |emulator, instruction| profile.record(emulator, instruction),
+        )
+        .unwrap();
+
+        (profile, instructions)
+    }
+
+    #[test]
+    fn test_counts() {
+        let (profile, _) = profile();
+
+        assert_eq!(profile.steps, 8 + 3 * 10 + 1);
+        assert_eq!(profile.instructions[&8], 3);
+        assert_eq!(profile.opcodes["SYS"], 4);
+        assert_eq!(profile.syscalls[&0x8], 3);
+        assert_eq!(profile.syscalls[&0x20], 1);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let (profile, _) = profile();
+        let blocks = profile.blocks();
+
+        // the loop starts a block where the setup code falls into it, and is split by the WRITE
+        // syscall
+        assert_eq!(
+            blocks[&0],
+            BlockStats {
+                entries: 1,
+                end: 7,
+                steps: 8
+            }
+        );
+        assert_eq!(
+            blocks[&8],
+            BlockStats {
+                entries: 3,
+                end: 11,
+                steps: 12
+            }
+        );
+        assert_eq!(
+            blocks[&12],
+            BlockStats {
+                entries: 3,
+                end: 17,
+                steps: 18
+            }
+        );
+        assert_eq!(blocks[&18].entries, 1);
+        assert_eq!(
+            blocks.values().map(|block| block.steps).sum::<u64>(),
+            profile.steps
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        colored::control::set_override(false);
+        let (profile, instructions) = profile();
+        let report = profile
+            .report(Constants::default(), &instructions)
+            .to_string();
+
+        assert!(report.starts_with("39 steps"));
+        assert!(report.contains("         3   7.7%  0x08: IMM a = 0x1\n"));
+        assert!(report.contains("         3  WRITE\n"));
+    }
+
+    #[test]
+    fn test_io_time() {
+        let instructions = parse_asm_file("IMM a = 0\nSYS 0x20 NONE\n".to_string()).unwrap();
+        let mut emulator = Emulator::new(Constants::default(), instructions, Memory::default());
+
+        // only the time the emulator measured in host I/O is counted
+        let mut profile = Profile::default();
+        let mut io_time = Duration::ZERO;
+        emulate_until(
+            &mut emulator,
+            &Limits::default(),
+            |_| false,
+            |emulator, instruction| {
+                io_time += emulator.last_io_time();
+                profile.record(emulator, instruction);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(profile.syscalls[&0x20], 1);
+        assert_eq!(profile.io_time, io_time);
+    }
+
+    #[test]
+    fn test_folded() {
+        let (profile, _) = profile();
+
+        let mut folded = Vec::new();
+        profile.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+
+        assert!(folded.starts_with("block_0x00;0x00 1\n"));
+        assert!(folded.contains("block_0x08;0x08 3\n"));
+        assert!(folded.contains("block_0x0c;0x11 3\n"));
+    }
+}
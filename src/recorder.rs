@@ -0,0 +1,223 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+};
+
+use crate::{
+    emulator::{Access, Emulator, Location},
+    yan85::{instruction::Instruction, register::Register},
+};
+
+/// A step kept by a [`FlightRecorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedStep {
+    /// The index of the instruction emulated.
+    pub index: u8,
+    /// The instruction emulated.
+    pub instruction: Instruction,
+    /// The guest state the step accessed, in order.
+    pub accesses: Vec<Access>,
+}
+
+/// Keeps the last few steps of a run, so that a crash can be explained after the fact.
+#[derive(Debug, Clone)]
+pub struct FlightRecorder {
+    /// The most recent steps, oldest first.
+    steps: VecDeque<RecordedStep>,
+    /// The number of steps kept.
+    capacity: usize,
+}
+
+/// The state of a crashed emulator and the steps that led up to the crash, written by
+/// [`FlightRecorder::report`].
+pub struct CrashReport<'a> {
+    /// The recorder.
+    recorder: &'a FlightRecorder,
+    /// The crashed emulator.
+    emulator: &'a Emulator,
+}
+
+impl FlightRecorder {
+    /// Constructs a recorder that keeps the last `capacity` steps.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            steps: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records the step that `emulator` just took, which emulated `instruction`, forgetting the
+    /// oldest step if the recorder is full.
+    pub fn record(&mut self, emulator: &Emulator, instruction: Instruction) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.steps.len() == self.capacity {
+            self.steps.pop_front();
+        }
+
+        self.steps.push_back(RecordedStep {
+            index: emulator.last_index(),
+            instruction,
+            accesses: emulator.last_accesses().to_vec(),
+        });
+    }
+
+    /// Returns the recorded steps, oldest first.
+    pub fn steps(&self) -> impl Iterator<Item = &RecordedStep> {
+        self.steps.iter()
+    }
+
+    /// Returns a report of the recorded steps and the state of `emulator`, whose last step failed.
+    ///
+    /// The report lists the recorded steps and the failed one with the state they changed, the
+    /// registers with the flags in `f` named, the values on the stack, and a hexdump of the memory
+    /// the steps accessed.
+    pub fn report<'a>(&'a self, emulator: &'a Emulator) -> CrashReport<'a> {
+        CrashReport {
+            recorder: self,
+            emulator,
+        }
+    }
+}
+
+impl fmt::Display for CrashReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let emulator = self.emulator;
+        let index = emulator.last_index();
+
+        writeln!(
+            f,
+            "last {} of {} steps:",
+            self.recorder.steps.len() + 1,
+            emulator.steps()
+        )?;
+        for step in self.recorder.steps() {
+            write!(f, "   {:#04x}: {}", step.index, step.instruction)?;
+            write_changes(f, &step.accesses)?;
+        }
+        match emulator.instructions().get(index as usize) {
+            Some(instruction) => write!(f, "=> {index:#04x}: {instruction}")?,
+            None => write!(f, "=> {index:#04x}: (no instruction)")?,
+        }
+        write_changes(f, emulator.last_accesses())?;
+
+        let registers = emulator.registers();
+        let flags = emulator.constants().flag.names(registers[Register::F]);
+        writeln!(f, "registers: {registers} ({flags})")?;
+
+        let sp = registers[Register::S];
+        writeln!(f, "stack: {sp} values")?;
+        for slot in 0..sp {
+            writeln!(f, "  {slot:#04x}: {:#04x}", emulator.stack()[slot])?;
+        }
+
+        let rows: BTreeSet<u8> = self
+            .recorder
+            .steps()
+            .flat_map(|step| &step.accesses)
+            .chain(emulator.last_accesses())
+            .filter_map(|access| match access.location() {
+                Location::Memory(address) => Some(address & 0xf0),
+                _ => None,
+            })
+            .collect();
+        if !rows.is_empty() {
+            writeln!(f, "memory accessed:")?;
+        }
+        for row in rows {
+            write!(f, "{}", emulator.memory().hexdump(row, 16))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the state changed by `accesses`, e.g. `  ; a: 0x00 -> 0x10`, ending the line.
+fn write_changes(f: &mut fmt::Formatter, accesses: &[Access]) -> fmt::Result {
+    let changes: Vec<String> = accesses
+        .iter()
+        .filter_map(|access| match *access {
+            Access::Write {
+                location: Location::Register(Register::I),
+                ..
+            } => None,
+            Access::Write { location, old, new } => {
+                Some(format!("{location}: {old:#04x} -> {new:#04x}"))
+            }
+            Access::Read { .. } => None,
+        })
+        .collect();
+
+    match changes.is_empty() {
+        true => writeln!(f),
+        false => writeln!(f, "  ; {}", changes.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::{testing, EmulatorBuilder},
+        yan85::constants::Constants,
+    };
+
+    /// Runs the program in `source` until it fails, returning the report.
+    fn crash(source: &str, capacity: usize) -> String {
+        colored::control::set_override(false);
+
+        let mut emulator =
+            testing::load(EmulatorBuilder::new(Constants::default()), source, b"hi").build();
+
+        let mut recorder = FlightRecorder::new(capacity);
+        let result = testing::run(&mut emulator, |emulator, instruction| {
+            recorder.record(emulator, instruction);
+        });
+        assert!(result.is_err());
+
+        recorder.report(&emulator).to_string()
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let source = "IMM a = 1
+IMM b = 2
+IMM c = 3
+STK NONE a
+STK b NONE
+STK b NONE
+";
+        let report = crash(source, 2);
+
+        assert!(report.starts_with("last 3 of 6 steps:\n"));
+        assert!(!report.contains("IMM c"));
+        assert!(
+            report.contains("   0x03: STK NONE a  ; stack[0x00]: 0x00 -> 0x01, s: 0x00 -> 0x01\n")
+        );
+        assert!(report.contains("=> 0x05: STK b NONE\n"));
+        assert!(
+            report.contains("registers: a=0x01 b=0x01 c=0x03 d=0x00 s=0x00 i=0x06 f=0x00 (none)\n")
+        );
+        assert!(report.contains("stack: 0 values\n"));
+    }
+
+    #[test]
+    fn test_memory() {
+        let source = "IMM a = 0
+IMM b = 0x42
+IMM c = 2
+SYS 0x4 NONE
+IMM a = 0x42
+IMM b = 0x68
+CMP a b
+SYS 0x10 NONE
+";
+        let report = crash(source, 16);
+
+        assert!(report.contains("=> 0x07: SYS 0x10 NONE\n"));
+        assert!(report.contains("f=0x09 (L N)\n"));
+        assert!(report.contains("memory accessed:\n0x40: 00 00 68 69 "));
+        assert!(report.ends_with("|..hi............|\n"));
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+
+use crate::{
+    emulator::{Access, Emulator, Location},
+    yan85::{instruction::Instruction, register::Register, registers::RegisterMap},
+};
+
+/// A suspicious access found by a [`Sanitizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// A value was read before anything was written to its location.
+    UninitializedRead {
+        /// The index of the instruction that read it.
+        index: u8,
+        /// The location read.
+        location: Location,
+    },
+    /// A `STK` popped more values than had been pushed.
+    PopBelowDepth {
+        /// The index of the `STK`.
+        index: u8,
+        /// The stack slot popped.
+        slot: u8,
+    },
+}
+
+/// Finds reads of registers, memory, and stack slots that were never written, which only work
+/// because the machine starts out zeroed, and pops of values that were never pushed.
+///
+/// Locations start out uninitialized, except for registers `s` and `i`, whose initial values are
+/// part of the machine's definition. Use [`Sanitizer::initialize`] for state loaded from images or
+/// set on the command line. Each location is warned about once.
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    /// Whether each register has been written.
+    registers: RegisterMap<bool>,
+    /// Whether each memory byte has been written.
+    memory: [bool; 256],
+    /// Whether each stack slot has been written.
+    stack: [bool; 256],
+    /// The number of values pushed and not yet popped.
+    depth: u8,
+}
+
+impl Sanitizer {
+    /// Constructs a sanitizer for a program about to run on `emulator`, treating the stack
+    /// pointer's current value as the number of values pushed.
+    pub fn new(emulator: &Emulator) -> Self {
+        let mut sanitizer = Self {
+            registers: RegisterMap::default(),
+            memory: [false; 256],
+            stack: [false; 256],
+            depth: emulator.registers()[Register::S],
+        };
+        sanitizer.initialize(Location::Register(Register::S));
+        sanitizer.initialize(Location::Register(Register::I));
+
+        sanitizer
+    }
+
+    /// Marks `location` as holding a meaningful value, e.g. one loaded from a memory image.
+    pub fn initialize(&mut self, location: Location) {
+        if let Some(initialized) = self.slot(location) {
+            *initialized = true;
+        }
+    }
+
+    /// Checks the step that `emulator` just took, which emulated `instruction`, returning any
+    /// warnings.
+    pub fn record(&mut self, emulator: &Emulator, instruction: Instruction) -> Vec<Warning> {
+        let index = emulator.last_index();
+        let mut warnings = Vec::new();
+
+        if let Instruction::STK(pop, push) = instruction {
+            if push != Register::None {
+                self.depth = self.depth.saturating_add(1);
+            }
+            if pop != Register::None {
+                match self.depth.checked_sub(1) {
+                    Some(depth) => self.depth = depth,
+                    None => {
+                        let popped =
+                            emulator
+                                .last_accesses()
+                                .iter()
+                                .find_map(|access| match *access {
+                                    Access::Read {
+                                        location: Location::Stack(slot),
+                                        ..
+                                    } => Some(slot),
+                                    _ => None,
+                                });
+                        if let Some(slot) = popped {
+                            warnings.push(Warning::PopBelowDepth { index, slot });
+                        }
+                    }
+                }
+            }
+        }
+
+        for access in emulator.last_accesses() {
+            match *access {
+                Access::Read { location, .. } => {
+                    if let Some(initialized) = self.slot(location) {
+                        if !*initialized {
+                            *initialized = true;
+                            warnings.push(Warning::UninitializedRead { index, location });
+                        }
+                    }
+                }
+                Access::Write { location, new, .. } => {
+                    self.initialize(location);
+
+                    let sets_sp = location == Location::Register(Register::S);
+                    if sets_sp && !matches!(instruction, Instruction::STK(..)) {
+                        self.depth = new;
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns whether `location` has been written, or `None` for [`Register::None`].
+    fn slot(&mut self, location: Location) -> Option<&mut bool> {
+        match location {
+            Location::Register(register) => self.registers.get_mut(register),
+            Location::Memory(address) => Some(&mut self.memory[address as usize]),
+            Location::Stack(slot) => Some(&mut self.stack[slot as usize]),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UninitializedRead { index, location } => {
+                write!(f, "{index:#04x}: read of uninitialized {location}")
+            }
+            Warning::PopBelowDepth { index, slot } => {
+                write!(
+                    f,
+                    "{index:#04x}: pop of stack[{slot:#04x}], below the pushed depth"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::{testing, EmulatorBuilder, FaultPolicy},
+        yan85::constants::Constants,
+    };
+
+    /// Runs the program in `source` from `builder`'s state, returning the warnings.
+    fn run(source: &str, builder: EmulatorBuilder, initialized: &[Location]) -> Vec<Warning> {
+        let mut emulator = testing::load(builder, source, b"hi")
+            .fault_policy(FaultPolicy::Wrap)
+            .build();
+
+        let mut sanitizer = Sanitizer::new(&emulator);
+        for &location in initialized {
+            sanitizer.initialize(location);
+        }
+
+        let mut warnings = Vec::new();
+        testing::run(&mut emulator, |emulator, instruction| {
+            warnings.extend(sanitizer.record(emulator, instruction));
+        })
+        .unwrap();
+
+        warnings
+    }
+
+    #[test]
+    fn test_uninitialized_reads() {
+        let source = "IMM b = 0x40
+IMM c = 2
+SYS 0x4 NONE
+LDM d = *b
+ADD d a
+ADD d a
+IMM b = 0x80
+LDM c = *b
+IMM a = 0
+SYS 0x20 NONE
+";
+        let warnings = run(source, EmulatorBuilder::new(Constants::default()), &[]);
+
+        assert_eq!(
+            warnings,
+            [
+                Warning::UninitializedRead {
+                    index: 2,
+                    location: Location::Register(Register::A)
+                },
+                Warning::UninitializedRead {
+                    index: 7,
+                    location: Location::Memory(0x80)
+                },
+            ]
+        );
+        assert_eq!(
+            warnings[1].to_string(),
+            "0x07: read of uninitialized mem[0x80]"
+        );
+
+        let initialized = [Location::Register(Register::A), Location::Memory(0x80)];
+        let builder = EmulatorBuilder::new(Constants::default());
+        assert!(run(source, builder, &initialized).is_empty());
+    }
+
+    #[test]
+    fn test_syscall_args() {
+        let source = "IMM a = 0
+SYS 0x20 NONE
+";
+        let warnings = run(source, EmulatorBuilder::new(Constants::default()), &[]);
+
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_pop_below_depth() {
+        let source = "IMM a = 1
+STK NONE a
+STK b NONE
+STK c NONE
+IMM s = 2
+STK d NONE
+IMM a = 0
+SYS 0x20 NONE
+";
+        let warnings = run(source, EmulatorBuilder::new(Constants::default()), &[]);
+
+        assert_eq!(
+            warnings,
+            [
+                Warning::PopBelowDepth {
+                    index: 3,
+                    slot: 0xff
+                },
+                Warning::UninitializedRead {
+                    index: 3,
+                    location: Location::Stack(0xff)
+                },
+                Warning::UninitializedRead {
+                    index: 5,
+                    location: Location::Stack(1)
+                },
+            ]
+        );
+
+        let builder = EmulatorBuilder::new(Constants::default()).register(Register::S, 1);
+        assert!(run(source, builder, &[Location::Stack(0)]).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::{
+    emulator::{OpenFlags, Syscall, SYSCALL_FAILURE},
+    yan85::{constants::Constants, memory::Memory},
+};
+
+/// A syscall written like `strace` writes it, by [`Syscall::strace`].
+pub struct Strace<'a> {
+    /// The syscall.
+    syscall: Syscall,
+    /// The encoding constants, for the syscall numbers.
+    constants: Constants,
+    /// The memory after the syscall returned.
+    memory: &'a Memory,
+}
+
+impl Syscall {
+    /// Returns the syscall as `strace` would write it, e.g. `open("/flag", O_RDONLY) = 3`, with
+    /// strings read from `memory`, which should be the memory just after the syscall returned.
+    ///
+    /// Syscalls that aren't built in are written with their number and all three arguments, e.g.
+    /// `syscall_0x40(0x01, 0x02, 0x03) = 4`.
+    pub fn strace(self, constants: Constants, memory: &Memory) -> Strace<'_> {
+        Strace {
+            syscall: self,
+            constants,
+            memory,
+        }
+    }
+}
+
+impl fmt::Display for Strace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Syscall {
+            number,
+            args: [a, b, c],
+            result,
+        } = self.syscall;
+
+        let s = self.constants.syscall;
+        match number {
+            _ if number == s.OPEN => {
+                let path: Vec<u8> = (a..=u8::MAX)
+                    .map(|address| self.memory[address])
+                    .take_while(|&byte| byte != 0)
+                    .collect();
+                let flags = OpenFlags(b.into());
+
+                write!(f, "open(\"{}\", {flags}", path.escape_ascii())?;
+                if flags.contains(OpenFlags::CREAT) {
+                    write!(f, ", 0{c:o}")?;
+                }
+                // file descriptors never reach SYSCALL_FAILURE
+                write!(f, ") = {}", Return(result, SYSCALL_FAILURE - 1))
+            }
+            _ if number == s.READ_CODE => {
+                write!(f, "read_code({a}, {b:#04x}, {c:#04x}) = {result}")
+            }
+            _ if number == s.READ_MEMORY => {
+                write!(
+                    f,
+                    "read_memory({a}, {b:#04x}, {c:#04x}) = {}",
+                    Return(result, c)
+                )?;
+                if result != SYSCALL_FAILURE || c == u8::MAX {
+                    let read: Vec<u8> = (0..result)
+                        .map(|i| self.memory[b.wrapping_add(i)])
+                        .collect();
+                    write!(f, " \"{}\"", read.escape_ascii())?;
+                }
+
+                Ok(())
+            }
+            _ if number == s.WRITE => {
+                write!(f, "write({a}, {b:#04x}, {c}) = {}", Return(result, c))
+            }
+            _ if number == s.SLEEP => write!(f, "sleep({a}) = {result}"),
+            _ if number == s.EXIT => write!(f, "exit({a}) = ?"),
+            _ => write!(
+                f,
+                "syscall_{number:#04x}({a:#04x}, {b:#04x}, {c:#04x}) = {result}"
+            ),
+        }
+    }
+}
+
+/// The return value of a syscall that succeeds with at most the given value, written as `-1` if it
+/// can only mean [`SYSCALL_FAILURE`].
+struct Return(u8, u8);
+
+impl fmt::Display for Return {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Return(SYSCALL_FAILURE, max) if max != SYSCALL_FAILURE => write!(f, "-1"),
+            Return(result, _) => write!(f, "{result}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `syscall` as strace writes it, with `bytes` in memory at 0x40.
+    fn strace(syscall: &str, args: [u8; 3], result: u8, bytes: &[u8]) -> String {
+        let constants = Constants::default();
+        let mut memory = Memory::default();
+        memory[0x40..0x40 + bytes.len() as u8].copy_from_slice(bytes);
+
+        let number = match syscall {
+            "OPEN" => constants.syscall.OPEN,
+            "READ_MEMORY" => constants.syscall.READ_MEMORY,
+            "WRITE" => constants.syscall.WRITE,
+            "EXIT" => constants.syscall.EXIT,
+            _ => 0x40,
+        };
+        let syscall = Syscall {
+            number,
+            args,
+            result,
+        };
+
+        syscall.strace(constants, &memory).to_string()
+    }
+
+    #[test]
+    fn test_open() {
+        assert_eq!(
+            strace("OPEN", [0x40, 0, 0], 3, b"/flag\0"),
+            "open(\"/flag\", O_RDONLY) = 3"
+        );
+        assert_eq!(
+            strace("OPEN", [0x40, 0o101, 0o44], 0xff, b"out\0"),
+            "open(\"out\", O_WRONLY|O_CREAT, 044) = -1"
+        );
+    }
+
+    #[test]
+    fn test_read_and_write() {
+        assert_eq!(
+            strace("READ_MEMORY", [3, 0x40, 0x20], 5, b"hi\n\"x"),
+            "read_memory(3, 0x40, 0x20) = 5 \"hi\\n\\\"x\""
+        );
+        assert_eq!(
+            strace("READ_MEMORY", [0, 0x40, 0x20], 0xff, b""),
+            "read_memory(0, 0x40, 0x20) = -1"
+        );
+        assert_eq!(
+            strace("WRITE", [1, 0x40, 18], 18, b""),
+            "write(1, 0x40, 18) = 18"
+        );
+    }
+
+    #[test]
+    fn test_other() {
+        assert_eq!(strace("EXIT", [0, 0, 0], 0, b""), "exit(0) = ?");
+        assert_eq!(
+            strace("custom", [1, 2, 3], 4, b""),
+            "syscall_0x40(0x01, 0x02, 0x03) = 4"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::BTreeSet, fmt, rc::Rc};
+
+use crate::yan85::constants::Flag;
+
+/// A byte whose value may depend on standard input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// A known value.
+    Const(u8),
+    /// The byte of standard input at an offset.
+    Input(usize),
+    /// The wrapping sum of two values, as computed by `ADD`.
+    Add(Rc<Expr>, Rc<Expr>),
+    /// The flags that `CMP` assigns when comparing two values.
+    Flags(Rc<Expr>, Rc<Expr>),
+}
+
+impl Expr {
+    /// Returns the sum of `a` and `b`, folding constants into a single constant on the right.
+    pub fn sum(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_add(b)),
+            (Expr::Const(0), expr) | (expr, Expr::Const(0)) => expr,
+            (Expr::Const(c), expr) | (expr, Expr::Const(c)) => match expr {
+                Expr::Add(a, b) => match *b {
+                    Expr::Const(b) => Expr::sum((*a).clone(), Expr::Const(b.wrapping_add(c))),
+                    _ => Expr::Add(Rc::new(Expr::Add(a, b)), Rc::new(Expr::Const(c))),
+                },
+                expr => Expr::Add(Rc::new(expr), Rc::new(Expr::Const(c))),
+            },
+            (a, b) => Expr::Add(Rc::new(a), Rc::new(b)),
+        }
+    }
+
+    /// Returns the flags from comparing `a` to `b`, folding constants.
+    pub fn compare(a: Expr, b: Expr, flag: Flag) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(flag.compare(a, b)),
+            (a, b) => Expr::Flags(Rc::new(a), Rc::new(b)),
+        }
+    }
+
+    /// Returns the value if it's known.
+    pub fn as_const(&self) -> Option<u8> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value when standard input byte `n` is `input(n)`.
+    pub fn eval(&self, flag: Flag, input: &impl Fn(usize) -> u8) -> u8 {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Input(offset) => input(*offset),
+            Expr::Add(a, b) => a.eval(flag, input).wrapping_add(b.eval(flag, input)),
+            Expr::Flags(a, b) => flag.compare(a.eval(flag, input), b.eval(flag, input)),
+        }
+    }
+
+    /// Adds the offsets of the input bytes the value depends on to `inputs`.
+    pub fn inputs(&self, inputs: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Input(offset) => {
+                inputs.insert(*offset);
+            }
+            Expr::Add(a, b) | Expr::Flags(a, b) => {
+                a.inputs(inputs);
+                b.inputs(inputs);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{value:#04x}"),
+            Expr::Input(offset) => write!(f, "in[{offset}]"),
+            Expr::Add(a, b) => write!(f, "({a} + {b})"),
+            Expr::Flags(a, b) => write!(f, "cmp({a}, {b})"),
+        }
+    }
+}
+
+/// A condition that a path places on standard input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// The value has at least one of the bits in the mask set, as when a `JMP` is taken.
+    AnyBits(Expr, u8),
+    /// The value has none of the bits in the mask set, as when a `JMP` isn't taken.
+    NoBits(Expr, u8),
+    /// The value is equal to a constant, as when a pointer is given a concrete address.
+    Equals(Expr, u8),
+}
+
+impl Constraint {
+    /// Returns the constrained value.
+    pub fn expr(&self) -> &Expr {
+        match self {
+            Constraint::AnyBits(expr, _)
+            | Constraint::NoBits(expr, _)
+            | Constraint::Equals(expr, _) => expr,
+        }
+    }
+
+    /// Returns whether the constraint holds when standard input byte `n` is `input(n)`.
+    pub fn holds(&self, flag: Flag, input: &impl Fn(usize) -> u8) -> bool {
+        match self {
+            Constraint::AnyBits(expr, mask) => expr.eval(flag, input) & mask != 0,
+            Constraint::NoBits(expr, mask) => expr.eval(flag, input) & mask == 0,
+            Constraint::Equals(expr, value) => expr.eval(flag, input) == *value,
+        }
+    }
+
+    /// Returns the offsets of the input bytes the constraint depends on.
+    pub fn inputs(&self) -> BTreeSet<usize> {
+        let mut inputs = BTreeSet::new();
+        self.expr().inputs(&mut inputs);
+        inputs
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::AnyBits(expr, mask) => write!(f, "{expr} & {mask:#04x} != 0"),
+            Constraint::NoBits(expr, mask) => write!(f, "{expr} & {mask:#04x} == 0"),
+            Constraint::Equals(expr, value) => write!(f, "{expr} == {value:#04x}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_folds_constants() {
+        let sum = Expr::sum(Expr::sum(Expr::Input(0), Expr::Const(0xff)), Expr::Const(2));
+
+        assert_eq!(sum.to_string(), "(in[0] + 0x01)");
+        assert_eq!(Expr::sum(Expr::Const(1), Expr::Const(2)), Expr::Const(3));
+        assert_eq!(Expr::sum(Expr::Input(1), Expr::Const(0)), Expr::Input(1));
+    }
+
+    #[test]
+    fn test_eval() {
+        let flag = Flag::default();
+        let expr = Expr::compare(
+            Expr::sum(Expr::Input(0), Expr::Input(1)),
+            Expr::Const(0x10),
+            flag,
+        );
+
+        assert_eq!(expr.eval(flag, &|n| [0x08, 0x08][n]), flag.E);
+        assert_eq!(expr.eval(flag, &|n| [0x08, 0x09][n]), flag.G | flag.N);
+    }
+
+    #[test]
+    fn test_constraint() {
+        let flag = Flag::default();
+        let constraint = Constraint::AnyBits(
+            Expr::compare(Expr::Input(2), Expr::Const(b'A'), flag),
+            flag.E,
+        );
+
+        assert!(constraint.holds(flag, &|_| b'A'));
+        assert!(!constraint.holds(flag, &|_| b'B'));
+        assert_eq!(constraint.inputs(), BTreeSet::from([2]));
+        assert_eq!(constraint.to_string(), "cmp(in[2], 0x41) & 0x04 != 0");
+    }
+}
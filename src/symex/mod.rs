@@ -0,0 +1,679 @@
+use crate::yan85::{
+    constants::Constants, instruction::Instruction, memory::Memory, register::Register,
+    registers::RegisterMap,
+};
+
+/// Values and path constraints that depend on standard input.
+mod expr;
+/// Export of path constraints to SMT-LIB2 and import of solver models.
+mod smt;
+/// A constraint solver for bytes.
+mod solver;
+
+pub use expr::{Constraint, Expr};
+pub use smt::{parse_model, SmtLib};
+pub use solver::{solve, Model, Solution};
+
+/// The file descriptor of the first file a program opens.
+const FIRST_FD: u8 = 3;
+
+/// Options for [`explore`].
+#[derive(Debug, Clone)]
+pub struct SymexOptions {
+    /// The index of the instruction to find input for, such as the `OPEN` of `/flag`.
+    pub target: u8,
+    /// The maximum number of instructions to execute on each path.
+    pub max_steps: u64,
+    /// The maximum number of paths to explore, counting each side of a branch as a new path.
+    pub max_paths: usize,
+    /// The maximum number of assignments the solver tries for each query.
+    pub solver_budget: u64,
+    /// The byte used for input that the path to the target doesn't constrain.
+    pub padding: u8,
+}
+
+impl Default for SymexOptions {
+    fn default() -> Self {
+        Self {
+            target: 0,
+            max_steps: 100_000,
+            max_paths: 10_000,
+            solver_budget: 1_000_000,
+            padding: b'A',
+        }
+    }
+}
+
+/// A path through a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path {
+    /// The constraints the path places on standard input.
+    pub constraints: Vec<Constraint>,
+    /// The number of bytes of standard input read along the path.
+    pub inputs: usize,
+    /// The number of instructions executed on the path.
+    pub steps: u64,
+}
+
+/// A path that reaches the target instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reached {
+    /// Standard input that leads down the path.
+    pub input: Vec<u8>,
+    /// The path.
+    pub path: Path,
+}
+
+/// The result of [`explore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exploration {
+    /// The first path found to the target instruction, if any.
+    pub reached: Option<Reached>,
+    /// The first path whose constraints the solver gave up on, if any. Its constraints can be
+    /// exported with [`Path::smtlib`] and solved elsewhere.
+    pub unsolved: Option<Path>,
+    /// The number of paths explored.
+    pub paths: usize,
+    /// The number of paths given up on because they ran too long or the solver gave up.
+    pub abandoned: usize,
+}
+
+/// The machine state along one path.
+#[derive(Debug, Clone)]
+struct State {
+    /// The registers.
+    registers: RegisterMap<Expr>,
+    /// The memory.
+    memory: Vec<Expr>,
+    /// The stack.
+    stack: Vec<Expr>,
+    /// The path so far.
+    path: Path,
+    /// The file descriptor the next `OPEN` returns.
+    next_fd: u8,
+}
+
+/// Why a path couldn't continue.
+enum End {
+    /// The program exited or faulted.
+    Finished,
+    /// The solver gave up on the path's constraints.
+    Unsolved(Path),
+}
+
+/// Explores the paths through a program that reads symbolic standard input, looking for input
+/// that reaches `options.target`.
+///
+/// Bytes read from standard input by `READ_MEMORY` become symbols, and execution forks at each
+/// `JMP` whose flags depend on them, keeping the sides that the solver finds feasible. Pointers,
+/// stack pointers, jump targets, and syscall arguments that depend on input are given one concrete
+/// value consistent with the path. Other file descriptors read nothing, and syscalls other than
+/// `OPEN`, `READ_MEMORY`, `WRITE`, `SLEEP`, and `EXIT` end the path.
+pub fn explore(
+    constants: Constants,
+    instructions: &[Instruction],
+    memory: &Memory,
+    options: &SymexOptions,
+) -> Exploration {
+    let explorer = Explorer {
+        constants,
+        instructions,
+        options,
+    };
+
+    let state = State {
+        registers: RegisterMap::from_fn(|_| Expr::Const(0)),
+        memory: (0..=u8::MAX)
+            .map(|address| Expr::Const(memory[address]))
+            .collect(),
+        stack: vec![Expr::Const(0); 256],
+        path: Path::default(),
+        next_fd: FIRST_FD,
+    };
+
+    let mut exploration = Exploration {
+        reached: None,
+        unsolved: None,
+        paths: 1,
+        abandoned: 0,
+    };
+    let mut pending = vec![state];
+
+    while let Some(mut state) = pending.pop() {
+        if state.registers[Register::I] == Expr::Const(options.target) {
+            match explorer.solve(&state) {
+                Ok(Some(model)) => {
+                    exploration.reached = Some(Reached {
+                        input: model_input(&model, state.path.inputs, options.padding),
+                        path: state.path,
+                    });
+                    return exploration;
+                }
+                Ok(None) => continue,
+                Err(end) => {
+                    exploration.end(end);
+                    continue;
+                }
+            }
+        }
+
+        if state.path.steps >= options.max_steps {
+            exploration.abandoned += 1;
+            continue;
+        }
+
+        match explorer.step(&mut state, &mut exploration) {
+            Ok(fork) => {
+                if let Some(fork) = fork {
+                    if exploration.paths >= options.max_paths {
+                        exploration.abandoned += 1;
+                    } else {
+                        exploration.paths += 1;
+                        pending.push(fork);
+                    }
+                }
+                pending.push(state);
+            }
+            Err(end) => exploration.end(end),
+        }
+    }
+
+    exploration
+}
+
+impl Exploration {
+    /// Records why a path ended.
+    fn end(&mut self, end: End) {
+        match end {
+            End::Finished => {}
+            End::Unsolved(path) => {
+                self.abandoned += 1;
+                self.unsolved.get_or_insert(path);
+            }
+        }
+    }
+}
+
+/// Returns standard input of `length` bytes with the values in `model`, using `padding` for bytes
+/// the model doesn't include.
+pub fn model_input(model: &Model, length: usize, padding: u8) -> Vec<u8> {
+    (0..length)
+        .map(|offset| model.get(&offset).copied().unwrap_or(padding))
+        .collect()
+}
+
+/// Executes instructions symbolically.
+struct Explorer<'a> {
+    /// The encoding constants.
+    constants: Constants,
+    /// The program.
+    instructions: &'a [Instruction],
+    /// The exploration options.
+    options: &'a SymexOptions,
+}
+
+impl Explorer<'_> {
+    /// Executes the next instruction on `state`'s path. Returns the state of the other side of a
+    /// branch if both are feasible.
+    fn step(&self, state: &mut State, exploration: &mut Exploration) -> Result<Option<State>, End> {
+        let index = self.concretize(state, Register::I)?;
+        let instruction = *self.instructions.get(index as usize).ok_or(End::Finished)?;
+        if index == u8::MAX {
+            return Err(End::Finished);
+        }
+
+        state.path.steps += 1;
+        self.write(state, Register::I, Expr::Const(index + 1))?;
+
+        match instruction {
+            Instruction::IMM(register, value) => self.write(state, register, Expr::Const(value))?,
+            Instruction::ADD(a, b) => {
+                let sum = Expr::sum(self.read(state, a)?, self.read(state, b)?);
+                self.write(state, a, sum)?;
+            }
+            Instruction::STK(pop, push) => {
+                if push != Register::None {
+                    let sp = self.concretize(state, Register::S)?;
+                    if sp == u8::MAX {
+                        return Err(End::Finished);
+                    }
+
+                    state.stack[sp as usize] = self.read(state, push)?;
+                    self.write(state, Register::S, Expr::Const(sp + 1))?;
+                }
+
+                if pop != Register::None {
+                    let sp = self.concretize(state, Register::S)?;
+                    if sp == 0 {
+                        return Err(End::Finished);
+                    }
+
+                    self.write(state, Register::S, Expr::Const(sp - 1))?;
+                    let value = state.stack[sp as usize - 1].clone();
+                    self.write(state, pop, value)?;
+                }
+            }
+            Instruction::STM(a, b) => {
+                let address = self.concretize(state, a)?;
+                state.memory[address as usize] = self.read(state, b)?;
+            }
+            Instruction::LDM(a, b) => {
+                let address = self.concretize(state, b)?;
+                let value = state.memory[address as usize].clone();
+                self.write(state, a, value)?;
+            }
+            Instruction::CMP(a, b) => {
+                let flags = Expr::compare(
+                    self.read(state, a)?,
+                    self.read(state, b)?,
+                    self.constants.flag,
+                );
+                self.write(state, Register::F, flags)?;
+            }
+            Instruction::JMP(condition, register) => {
+                return self.jump(state, exploration, condition, register)
+            }
+            Instruction::SYS(syscall, register) => self.syscall(state, syscall, register)?,
+        }
+
+        Ok(None)
+    }
+
+    /// Executes a `JMP`, forking if whether it's taken depends on input. Sides that end before
+    /// they fork are recorded in `exploration`.
+    fn jump(
+        &self,
+        state: &mut State,
+        exploration: &mut Exploration,
+        condition: u8,
+        register: Register,
+    ) -> Result<Option<State>, End> {
+        let flags = self.read(state, Register::F)?;
+
+        if let Some(flags) = flags.as_const() {
+            if flags & condition != 0 {
+                let target = self.concretize(state, register)?;
+                self.write(state, Register::I, Expr::Const(target))?;
+            }
+
+            return Ok(None);
+        }
+
+        let taken = self.take_jump(state.clone(), flags.clone(), condition, register);
+
+        state
+            .path
+            .constraints
+            .push(Constraint::NoBits(flags, condition));
+        let not_taken = self
+            .solve(state)
+            .and_then(|model| model.map(|_| ()).ok_or(End::Finished));
+
+        // a side the solver gives up on ends on its own, without taking the other side with it
+        match (not_taken, taken) {
+            (Ok(()), Ok(taken)) => Ok(Some(taken)),
+            (Ok(()), Err(end)) => {
+                exploration.end(end);
+                Ok(None)
+            }
+            (Err(end), Ok(taken)) => {
+                exploration.end(end);
+                *state = taken;
+                Ok(None)
+            }
+            (Err(end), Err(taken_end)) => {
+                exploration.end(taken_end);
+                Err(end)
+            }
+        }
+    }
+
+    /// Returns the state on the side of a `JMP` where it's taken, if that side is feasible.
+    fn take_jump(
+        &self,
+        mut state: State,
+        flags: Expr,
+        condition: u8,
+        register: Register,
+    ) -> Result<State, End> {
+        state
+            .path
+            .constraints
+            .push(Constraint::AnyBits(flags, condition));
+        self.solve(&state)?.ok_or(End::Finished)?;
+
+        let target = self.concretize(&mut state, register)?;
+        self.write(&mut state, Register::I, Expr::Const(target))?;
+
+        Ok(state)
+    }
+
+    /// Executes a `SYS`, placing its return value in `register` unless it's [`Register::None`].
+    fn syscall(&self, state: &mut State, syscall: u8, register: Register) -> Result<(), End> {
+        let s = self.constants.syscall;
+
+        let result = match syscall {
+            _ if syscall == s.OPEN => {
+                let fd = state.next_fd;
+                state.next_fd = fd.wrapping_add(1);
+                Expr::Const(fd)
+            }
+            _ if syscall == s.READ_MEMORY => {
+                let fd = self.concretize(state, Register::A)?;
+                let start = self.concretize(state, Register::B)?;
+                let size = self.concretize(state, Register::C)?;
+                if start as usize + size as usize > 256 {
+                    return Err(End::Finished);
+                }
+
+                if fd == 0 {
+                    for address in start..start + size {
+                        state.memory[address as usize] = Expr::Input(state.path.inputs);
+                        state.path.inputs += 1;
+                    }
+                    Expr::Const(size)
+                } else {
+                    Expr::Const(0)
+                }
+            }
+            _ if syscall == s.WRITE => self.read(state, Register::C)?,
+            _ if syscall == s.SLEEP => Expr::Const(0),
+            _ => return Err(End::Finished),
+        };
+
+        if register != Register::None {
+            self.write(state, register, result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of `register`. Reading [`Register::None`] faults, ending the path.
+    fn read(&self, state: &State, register: Register) -> Result<Expr, End> {
+        state.registers.get(register).cloned().ok_or(End::Finished)
+    }
+
+    /// Assigns `value` to `register`. Writing [`Register::None`] faults, ending the path.
+    fn write(&self, state: &mut State, register: Register, value: Expr) -> Result<(), End> {
+        let slot = state.registers.get_mut(register).ok_or(End::Finished)?;
+        *slot = value;
+
+        Ok(())
+    }
+
+    /// Returns a value of `register` consistent with the path, constraining the path to it.
+    fn concretize(&self, state: &mut State, register: Register) -> Result<u8, End> {
+        let value = self.read(state, register)?;
+        if let Some(value) = value.as_const() {
+            return Ok(value);
+        }
+
+        let model = self.solve(state)?.ok_or(End::Finished)?;
+        let concrete = value.eval(self.constants.flag, &|offset| {
+            model.get(&offset).copied().unwrap_or(self.options.padding)
+        });
+
+        state
+            .path
+            .constraints
+            .push(Constraint::Equals(value, concrete));
+        self.write(state, register, Expr::Const(concrete))?;
+
+        Ok(concrete)
+    }
+
+    /// Solves the path's constraints, returning `None` if they can't hold. Abandons the path if
+    /// the solver gives up.
+    fn solve(&self, state: &State) -> Result<Option<Model>, End> {
+        match solve(
+            &state.path.constraints,
+            self.constants.flag,
+            self.options.solver_budget,
+        ) {
+            Solution::Sat(model) => Ok(Some(model)),
+            Solution::Unsat => Ok(None),
+            Solution::Unknown => Err(End::Unsolved(state.path.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::parse_asm_program;
+
+    /// Explores the program in `source` for input that reaches the instruction at `label`.
+    fn explore_asm(source: &str, label: &str) -> Exploration {
+        explore_asm_with(source, label, 1_000_000)
+    }
+
+    /// Explores like [`explore_asm`] with the given solver budget.
+    fn explore_asm_with(source: &str, label: &str, solver_budget: u64) -> Exploration {
+        let program = parse_asm_program(source.to_string()).unwrap();
+        let options = SymexOptions {
+            target: program.labels[label],
+            solver_budget,
+            ..Default::default()
+        };
+
+        explore(
+            Constants::default(),
+            &program.instructions,
+            &Memory::default(),
+            &options,
+        )
+    }
+
+    #[test]
+    fn test_reach_exit() {
+        let exploration = explore_asm(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 2
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 0x6b
+CMP a c
+IMM d = fail
+JMP 0x8 d
+IMM c = 1
+ADD b c
+LDM a = *b
+IMM c = 0x79
+CMP a c
+JMP 0x8 d
+win:
+IMM a = 0
+SYS 0x20 NONE
+fail:
+IMM a = 1
+SYS 0x20 NONE
+",
+            "win",
+        );
+
+        let reached = exploration.reached.unwrap();
+        assert_eq!(reached.input, b"ky");
+        assert_eq!(reached.path.constraints.len(), 2);
+        assert_eq!(exploration.paths, 3);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let exploration = explore_asm(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 1
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 0
+IMM d = never
+CMP a c
+JMP 0x1 d
+IMM a = 0
+SYS 0x20 NONE
+never:
+SYS 0x20 NONE
+",
+            "never",
+        );
+
+        assert_eq!(exploration.reached, None);
+        assert_eq!(exploration.paths, 1);
+        assert_eq!(exploration.abandoned, 0);
+    }
+
+    #[test]
+    fn test_loop() {
+        let exploration = explore_asm(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 4
+SYS 0x4 NONE
+IMM d = 0
+IMM c = 0
+loop:
+LDM a = *b
+ADD d a
+IMM a = 1
+ADD b a
+ADD c a
+IMM a = 4
+CMP c a
+IMM a = loop
+JMP 0x1 a
+IMM a = 0x10
+CMP d a
+IMM a = win
+JMP 0x4 a
+SYS 0x20 NONE
+win:
+SYS 0x20 NONE
+",
+            "win",
+        );
+
+        let input = exploration.reached.unwrap().input;
+        let sum = input.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        assert_eq!(input.len(), 4);
+        assert_eq!(sum, 0x10);
+    }
+
+    #[test]
+    fn test_symbolic_pointer() {
+        let exploration = explore_asm(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 1
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 0x99
+STM *a = c
+IMM b = 0x41
+LDM a = *b
+CMP a c
+IMM d = win
+JMP 0x4 d
+SYS 0x20 NONE
+win:
+SYS 0x20 NONE
+",
+            "win",
+        );
+
+        let reached = exploration.reached.unwrap();
+        assert_eq!(reached.input, b"A");
+        assert_eq!(
+            reached.path.constraints,
+            [Constraint::Equals(Expr::Input(0), b'A')]
+        );
+    }
+
+    #[test]
+    fn test_unsolved() {
+        let exploration = explore_asm_with(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 3
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 1
+ADD b c
+LDM d = *b
+ADD a d
+ADD b c
+LDM d = *b
+ADD a d
+IMM c = 0xff
+CMP a c
+IMM d = check
+JMP 0x4 d
+SYS 0x20 NONE
+check:
+IMM d = win
+JMP 0x8 d
+SYS 0x20 NONE
+win:
+SYS 0x20 NONE
+",
+            "win",
+            10,
+        );
+
+        assert_eq!(exploration.reached, None);
+        assert_eq!(exploration.abandoned, 1);
+
+        let unsolved = exploration.unsolved.unwrap();
+        assert_eq!(unsolved.inputs, 3);
+        assert_eq!(
+            unsolved.constraints[0].to_string(),
+            "cmp(((in[0] + in[1]) + in[2]), 0xff) & 0x04 != 0"
+        );
+        assert_eq!(unsolved.constraints.len(), 2);
+    }
+
+    #[test]
+    fn test_unsolved_side() {
+        // the solver gives up on the side where the jump to `lose` is taken, but still finds
+        // input for the side where it isn't
+        let exploration = explore_asm_with(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 3
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 1
+ADD b c
+LDM d = *b
+ADD a d
+ADD b c
+LDM d = *b
+ADD a d
+IMM c = 0xff
+CMP a c
+IMM d = check
+JMP 0x4 d
+SYS 0x20 NONE
+check:
+IMM d = lose
+JMP 0x8 d
+win:
+SYS 0x20 NONE
+lose:
+SYS 0x20 NONE
+",
+            "win",
+            10,
+        );
+
+        assert_eq!(exploration.abandoned, 1);
+        assert_eq!(exploration.unsolved.unwrap().constraints.len(), 2);
+
+        let reached = exploration.reached.unwrap();
+        let sum = reached
+            .input
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        assert_eq!(sum, 0xff);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    symex::{Constraint, Expr, Model, Path},
+    yan85::constants::Flag,
+};
+
+/// The SMT-LIB2 constraints of a path, written by [`Path::smtlib`].
+pub struct SmtLib<'a> {
+    /// The path.
+    path: &'a Path,
+    /// The constants `CMP` uses.
+    flag: Flag,
+}
+
+impl Path {
+    /// Returns the path's constraints as an SMT-LIB2 script over bit vectors, with the variable
+    /// `in_N` standing for byte `N` of standard input.
+    ///
+    /// The script asks for the value of every input byte, so the output of a solver run on it can
+    /// be read back with [`parse_model`].
+    pub fn smtlib(&self, flag: Flag) -> SmtLib<'_> {
+        SmtLib { path: self, flag }
+    }
+}
+
+impl fmt::Display for SmtLib<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Flag { L, G, E, N, Z } = self.flag;
+
+        writeln!(f, "; in_N is byte N of standard input")?;
+        writeln!(f, "(set-logic QF_BV)")?;
+        for offset in 0..self.path.inputs {
+            writeln!(f, "(declare-const in_{offset} (_ BitVec 8))")?;
+        }
+
+        writeln!(f, "; the flags CMP assigns when comparing a to b")?;
+        writeln!(
+            f,
+            "(define-fun cmp ((a (_ BitVec 8)) (b (_ BitVec 8))) (_ BitVec 8)"
+        )?;
+        writeln!(
+            f,
+            "  (bvor (ite (bvult a b) {} (ite (bvugt a b) {} {}))",
+            bv(L | N),
+            bv(G | N),
+            bv(E)
+        )?;
+        writeln!(
+            f,
+            "        (ite (and (= a #x00) (= b #x00)) {} #x00)))",
+            bv(Z)
+        )?;
+
+        for constraint in &self.path.constraints {
+            let assertion = match constraint {
+                Constraint::AnyBits(expr, mask) => {
+                    format!("(not (= (bvand {} {}) #x00))", Term(expr), bv(*mask))
+                }
+                Constraint::NoBits(expr, mask) => {
+                    format!("(= (bvand {} {}) #x00)", Term(expr), bv(*mask))
+                }
+                Constraint::Equals(expr, value) => format!("(= {} {})", Term(expr), bv(*value)),
+            };
+            writeln!(f, "(assert {assertion})")?;
+        }
+
+        writeln!(f, "(check-sat)")?;
+        if self.path.inputs > 0 {
+            let inputs: Vec<String> = (0..self.path.inputs)
+                .map(|offset| format!("in_{offset}"))
+                .collect();
+            writeln!(f, "(get-value ({}))", inputs.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An expression written as an SMT-LIB2 term.
+struct Term<'a>(&'a Expr);
+
+impl fmt::Display for Term<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Expr::Const(value) => write!(f, "{}", bv(*value)),
+            Expr::Input(offset) => write!(f, "in_{offset}"),
+            Expr::Add(a, b) => write!(f, "(bvadd {} {})", Term(a), Term(b)),
+            Expr::Flags(a, b) => write!(f, "(cmp {} {})", Term(a), Term(b)),
+        }
+    }
+}
+
+/// Returns `value` as an SMT-LIB2 bit vector literal.
+fn bv(value: u8) -> String {
+    format!("#x{value:02x}")
+}
+
+/// Reads the values of input bytes from a solver's output for a script written by
+/// [`Path::smtlib`].
+///
+/// Both `(get-value ...)` responses, e.g. `((in_0 #x41) (in_1 #x42))`, and models from
+/// `(get-model)`, e.g. `(define-fun in_0 () (_ BitVec 8) #x41)`, are understood. Values may be
+/// hexadecimal, binary, or `(_ bvN 8)` literals.
+pub fn parse_model(text: &str) -> Result<Model> {
+    let mut tokens = tokenize(text);
+    let mut model = Model::new();
+
+    while let Some(token) = tokens.next() {
+        let name = match token {
+            "define-fun" => tokens.next().context("Expected a name after define-fun")?,
+            token if token.starts_with("in_") => token,
+            _ => continue,
+        };
+        let Some(offset) = name.strip_prefix("in_").and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+
+        // Skip to the value, past the argument list and sort of a definition.
+        let mut value = tokens.next().context("Expected a value")?;
+        if value == "(" && token == "define-fun" {
+            for token in tokens.by_ref() {
+                if token == "BitVec" {
+                    break;
+                }
+            }
+            tokens.next();
+            tokens.next();
+            value = tokens.next().context("Expected a value")?;
+        }
+
+        let value = match value {
+            "(" => {
+                let literal: Vec<&str> = tokens.by_ref().take_while(|&t| t != ")").collect();
+                match literal[..] {
+                    ["_", number, "8"] => number
+                        .strip_prefix("bv")
+                        .and_then(|n| n.parse().ok())
+                        .with_context(|| format!("Invalid value of {name}: {number}"))?,
+                    _ => bail!("Invalid value of {name}: ({})", literal.join(" ")),
+                }
+            }
+            value => parse_literal(value).with_context(|| format!("Invalid value of {name}"))?,
+        };
+
+        model.insert(offset, value);
+    }
+
+    Ok(model)
+}
+
+/// Parses a `#x` or `#b` bit vector literal.
+fn parse_literal(literal: &str) -> Result<u8> {
+    if let Some(hex) = literal.strip_prefix("#x") {
+        Ok(u8::from_str_radix(hex, 16)?)
+    } else if let Some(binary) = literal.strip_prefix("#b") {
+        Ok(u8::from_str_radix(binary, 2)?)
+    } else {
+        bail!("Unsupported literal: {literal}")
+    }
+}
+
+/// Splits SMT-LIB2 text into parentheses and atoms, skipping comments.
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.split(';').next().unwrap_or_default())
+        .flat_map(|line| {
+            line.split_inclusive(['(', ')', ' ', '\t'])
+                .flat_map(|piece| match piece.strip_suffix(['(', ')']) {
+                    Some(atom) => [atom, &piece[atom.len()..]],
+                    None => [piece, ""],
+                })
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a path that requires `in_0 + 1 == in_1` and `in_1 == 'b'`.
+    fn path() -> Path {
+        let flag = Flag::default();
+        let sum = Expr::sum(Expr::Input(0), Expr::Const(1));
+
+        Path {
+            constraints: vec![
+                Constraint::AnyBits(Expr::compare(sum, Expr::Input(1), flag), flag.E),
+                Constraint::Equals(Expr::Input(1), b'b'),
+            ],
+            inputs: 3,
+            steps: 10,
+        }
+    }
+
+    #[test]
+    fn test_smtlib() {
+        let script = path().smtlib(Flag::default()).to_string();
+
+        assert!(script.contains("(declare-const in_2 (_ BitVec 8))"));
+        assert!(
+            script.contains("(assert (not (= (bvand (cmp (bvadd in_0 #x01) in_1) #x04) #x00)))")
+        );
+        assert!(script.contains("(assert (= in_1 #x62))"));
+        assert!(script.contains("(bvor (ite (bvult a b) #x09 (ite (bvugt a b) #x0a #x04))"));
+        assert!(script.ends_with("(check-sat)\n(get-value (in_0 in_1 in_2))\n"));
+    }
+
+    #[test]
+    fn test_parse_get_value() {
+        let model = parse_model("sat\n((in_0 #x61)\n (in_1 #b01100010)\n (in_2 (_ bv65 8)))\n");
+
+        assert_eq!(
+            model.unwrap(),
+            Model::from([(0, b'a'), (1, b'b'), (2, b'A')])
+        );
+    }
+
+    #[test]
+    fn test_parse_get_model() {
+        let model = parse_model(
+            "sat
+(
+  ; a comment
+  (define-fun in_1 () (_ BitVec 8)
+    #x62)
+  (define-fun in_0 () (_ BitVec 8) #x61)
+)
+",
+        );
+
+        assert_eq!(model.unwrap(), Model::from([(0, b'a'), (1, b'b')]));
+        assert!(parse_model("((in_0 #xzz))").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use crate::{symex::Constraint, yan85::constants::Flag};
+
+/// Values of input bytes, by offset.
+pub type Model = BTreeMap<usize, u8>;
+
+/// The outcome of [`solve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution {
+    /// The constraints hold for the input bytes in the model.
+    Sat(Model),
+    /// No input satisfies the constraints.
+    Unsat,
+    /// The search gave up before finding out.
+    Unknown,
+}
+
+/// Finds values of the input bytes that satisfy every constraint, trying at most `budget`
+/// assignments.
+///
+/// Since every variable is a byte, each constraint on a single byte is solved by trying all 256
+/// values. The remaining bytes are assigned by backtracking search, trying the byte with the fewest
+/// possible values first and narrowing the values of bytes that a constraint then depends on
+/// alone.
+pub fn solve(constraints: &[Constraint], flag: Flag, budget: u64) -> Solution {
+    let mut variables: Vec<usize> = Vec::new();
+    let constraints: Vec<(&Constraint, Vec<usize>)> = constraints
+        .iter()
+        .map(|constraint| {
+            let inputs = constraint.inputs();
+            let indices = inputs
+                .into_iter()
+                .map(|offset| match variables.iter().position(|&v| v == offset) {
+                    Some(index) => index,
+                    None => {
+                        variables.push(offset);
+                        variables.len() - 1
+                    }
+                })
+                .collect();
+
+            (constraint, indices)
+        })
+        .collect();
+
+    let mut search = Search {
+        variables: &variables,
+        constraints: &constraints,
+        flag,
+        budget,
+    };
+
+    let mut values = vec![None; variables.len()];
+    let mut domains = vec![(0..=u8::MAX).collect::<Vec<u8>>(); variables.len()];
+    for (constraint, indices) in &constraints {
+        match indices[..] {
+            [] if !constraint.holds(flag, &|_| 0) => return Solution::Unsat,
+            [index] => {
+                domains[index] = search.narrow(constraint, indices, &mut values, index, &domains);
+                if domains[index].is_empty() {
+                    return Solution::Unsat;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    search.assign(&mut values, domains)
+}
+
+/// The state of a backtracking search.
+struct Search<'a> {
+    /// The offset of the input byte each variable stands for.
+    variables: &'a [usize],
+    /// The constraints, with the variables each depends on.
+    constraints: &'a [(&'a Constraint, Vec<usize>)],
+    /// The constants `CMP` uses.
+    flag: Flag,
+    /// The number of assignments left to try.
+    budget: u64,
+}
+
+impl Search<'_> {
+    /// Assigns the unassigned variables from `domains`, their possible values.
+    fn assign(&mut self, values: &mut [Option<u8>], domains: Vec<Vec<u8>>) -> Solution {
+        let next = (0..values.len())
+            .filter(|&index| values[index].is_none())
+            .min_by_key(|&index| domains[index].len());
+        let Some(variable) = next else {
+            let model = self
+                .variables
+                .iter()
+                .zip(values.iter())
+                .map(|(&offset, value)| (offset, value.unwrap_or_default()))
+                .collect();
+            return Solution::Sat(model);
+        };
+
+        for &value in &domains[variable] {
+            if self.budget == 0 {
+                return Solution::Unknown;
+            }
+            self.budget -= 1;
+
+            values[variable] = Some(value);
+            if let Some(domains) = self.propagate(variable, values, &domains) {
+                match self.assign(values, domains) {
+                    Solution::Unsat => {}
+                    solution => return solution,
+                }
+            }
+        }
+
+        values[variable] = None;
+        Solution::Unsat
+    }
+
+    /// Checks the constraints on `variable` after it's assigned, narrowing the possible values of
+    /// any variable left as the only unassigned one in a constraint. Returns `None` if a constraint
+    /// can't hold.
+    fn propagate(
+        &self,
+        variable: usize,
+        values: &mut [Option<u8>],
+        domains: &[Vec<u8>],
+    ) -> Option<Vec<Vec<u8>>> {
+        let mut domains = domains.to_vec();
+
+        for (constraint, indices) in self.constraints {
+            if !indices.contains(&variable) {
+                continue;
+            }
+
+            let mut unassigned = indices.iter().filter(|&&index| values[index].is_none());
+            match (unassigned.next(), unassigned.next()) {
+                (None, _) if !constraint.holds(self.flag, &|offset| self.value(values, offset)) => {
+                    return None;
+                }
+                (Some(&index), None) => {
+                    domains[index] = self.narrow(constraint, indices, values, index, &domains);
+                    if domains[index].is_empty() {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Some(domains)
+    }
+
+    /// Returns the possible values of `variable` for which `constraint` holds, given that every
+    /// other variable it depends on is assigned.
+    fn narrow(
+        &self,
+        constraint: &Constraint,
+        indices: &[usize],
+        values: &mut [Option<u8>],
+        variable: usize,
+        domains: &[Vec<u8>],
+    ) -> Vec<u8> {
+        debug_assert!(indices.contains(&variable));
+
+        let narrowed = domains[variable]
+            .iter()
+            .copied()
+            .filter(|&value| {
+                values[variable] = Some(value);
+                constraint.holds(self.flag, &|offset| self.value(values, offset))
+            })
+            .collect();
+        values[variable] = None;
+
+        narrowed
+    }
+
+    /// Returns the value assigned to the input byte at `offset`.
+    fn value(&self, values: &[Option<u8>], offset: usize) -> u8 {
+        self.variables
+            .iter()
+            .position(|&v| v == offset)
+            .and_then(|index| values[index])
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symex::Expr;
+
+    /// A generous search budget.
+    const BUDGET: u64 = 1_000_000;
+
+    /// Returns the constraint that `expr` is equal to `value` according to `CMP`.
+    fn equal(expr: Expr, value: u8) -> Constraint {
+        let flag = Flag::default();
+        Constraint::AnyBits(Expr::compare(expr, Expr::Const(value), flag), flag.E)
+    }
+
+    #[test]
+    fn test_single_bytes() {
+        let constraints = [
+            equal(Expr::sum(Expr::Input(0), Expr::Const(3)), b'd'),
+            equal(Expr::Input(1), b'!'),
+        ];
+
+        assert_eq!(
+            solve(&constraints, Flag::default(), BUDGET),
+            Solution::Sat(Model::from([(0, b'a'), (1, b'!')]))
+        );
+    }
+
+    #[test]
+    fn test_related_bytes() {
+        let flag = Flag::default();
+        let sum = Expr::sum(Expr::Input(0), Expr::Input(1));
+        let constraints = [
+            equal(sum.clone(), 0x90),
+            Constraint::AnyBits(Expr::compare(Expr::Input(0), Expr::Input(1), flag), flag.G),
+            Constraint::NoBits(
+                Expr::compare(Expr::Input(1), Expr::Const(0x40), flag),
+                flag.L,
+            ),
+        ];
+
+        let Solution::Sat(model) = solve(&constraints, flag, BUDGET) else {
+            panic!("expected a solution");
+        };
+        let input = |offset| model[&offset];
+        assert!(constraints.iter().all(|c| c.holds(flag, &input)));
+    }
+
+    #[test]
+    fn test_unsat() {
+        let constraints = [equal(Expr::Input(0), 1), equal(Expr::Input(0), 2)];
+        assert_eq!(
+            solve(&constraints, Flag::default(), BUDGET),
+            Solution::Unsat
+        );
+
+        let constraints = [Constraint::Equals(Expr::Const(1), 2)];
+        assert_eq!(
+            solve(&constraints, Flag::default(), BUDGET),
+            Solution::Unsat
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let sum = Expr::sum(
+            Expr::sum(Expr::Input(0), Expr::Input(1)),
+            Expr::sum(Expr::Input(2), Expr::Input(3)),
+        );
+        let constraints = [
+            equal(sum.clone(), 0),
+            Constraint::NoBits(Expr::compare(sum, Expr::Const(0), Flag::default()), 0x4),
+        ];
+
+        assert_eq!(
+            solve(&constraints, Flag::default(), 1000),
+            Solution::Unknown
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    emulator::{Access, Emulator, Location},
+    yan85::{instruction::Instruction, register::Register, registers::RegisterMap},
+};
+
+/// The offsets of the standard input bytes that a value depends on.
+pub type Labels = BTreeSet<usize>;
+
+/// One side of a `CMP`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operand {
+    /// The register compared.
+    pub register: Register,
+    /// The register's value.
+    pub value: u8,
+    /// The input bytes the value depends on. Empty if the value is a constant.
+    pub inputs: Labels,
+}
+
+/// A `CMP` of a value that depends on input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The index of the `CMP`.
+    pub index: u8,
+    /// The two values compared.
+    pub operands: [Operand; 2],
+}
+
+/// Tracks which bytes of standard input each register, memory byte, and stack slot depends on.
+///
+/// Bytes that `READ_MEMORY` reads from standard input are labelled with their offsets, and labels
+/// flow through `ADD`, `STK`, `STM`, and `LDM`. Immediates and the results of syscalls clear them.
+/// Addresses don't pass their labels on, so `LDM a = *b` only depends on the byte `b` points to.
+#[derive(Debug, Clone)]
+pub struct Taint {
+    /// The labels of each register.
+    registers: RegisterMap<Labels>,
+    /// The labels of each memory byte.
+    memory: Vec<Labels>,
+    /// The labels of each stack slot.
+    stack: Vec<Labels>,
+    /// The number of bytes read from standard input so far.
+    stdin_offset: usize,
+    /// The comparisons of values that depend on input, in the order they were made.
+    comparisons: Vec<Comparison>,
+}
+
+impl Default for Taint {
+    fn default() -> Self {
+        Self {
+            registers: Default::default(),
+            memory: vec![Labels::new(); 256],
+            stack: vec![Labels::new(); 256],
+            stdin_offset: 0,
+            comparisons: Vec::new(),
+        }
+    }
+}
+
+impl Taint {
+    /// Propagates labels through the step that `emulator` just took, which emulated
+    /// `instruction`. Returns the comparison the step made if it compared a value that depends on
+    /// input.
+    pub fn record(&mut self, emulator: &Emulator, instruction: Instruction) -> Option<&Comparison> {
+        let accesses = emulator.last_accesses();
+
+        match instruction {
+            Instruction::IMM(register, _) => self.set(Location::Register(register), Labels::new()),
+            Instruction::ADD(a, b) => {
+                let mut sum = self.labels(Location::Register(a)).clone();
+                sum.extend(self.labels(Location::Register(b)));
+                self.set(Location::Register(a), sum);
+            }
+            Instruction::STK(pop, push) => {
+                if let Some(slot) = find(accesses, true, |l| matches!(l, Location::Stack(_))) {
+                    let labels = self.labels(Location::Register(push)).clone();
+                    self.set(slot, labels);
+                }
+                if let Some(slot) = find(accesses, false, |l| matches!(l, Location::Stack(_))) {
+                    let labels = self.labels(slot).clone();
+                    self.set(Location::Register(pop), labels);
+                }
+            }
+            Instruction::STM(_, b) => {
+                if let Some(address) = find(accesses, true, |l| matches!(l, Location::Memory(_))) {
+                    let labels = self.labels(Location::Register(b)).clone();
+                    self.set(address, labels);
+                }
+            }
+            Instruction::LDM(a, _) => {
+                if let Some(address) = find(accesses, false, |l| matches!(l, Location::Memory(_))) {
+                    let labels = self.labels(address).clone();
+                    self.set(Location::Register(a), labels);
+                }
+            }
+            Instruction::CMP(a, b) => return self.compare(emulator, a, b),
+            Instruction::JMP(..) => {}
+            Instruction::SYS(..) => self.syscall(emulator),
+        }
+
+        None
+    }
+
+    /// Returns the comparisons of values that depend on input made so far.
+    pub fn comparisons(&self) -> &[Comparison] {
+        &self.comparisons
+    }
+
+    /// Returns the input bytes that the value at `location` depends on.
+    pub fn labels(&self, location: Location) -> &Labels {
+        /// The labels of [`Register::None`], which never holds a value.
+        static NONE: Labels = Labels::new();
+
+        match location {
+            Location::Register(register) => self.registers.get(register).unwrap_or(&NONE),
+            Location::Memory(address) => &self.memory[address as usize],
+            Location::Stack(slot) => &self.stack[slot as usize],
+        }
+    }
+
+    /// Replaces the labels of `location`.
+    fn set(&mut self, location: Location, labels: Labels) {
+        let slot = match location {
+            Location::Register(register) => match self.registers.get_mut(register) {
+                Some(slot) => slot,
+                None => return,
+            },
+            Location::Memory(address) => &mut self.memory[address as usize],
+            Location::Stack(slot) => &mut self.stack[slot as usize],
+        };
+
+        *slot = labels;
+    }
+
+    /// Records a `CMP` of `a` and `b` if either depends on input. The flags then depend on
+    /// whatever the operands do.
+    fn compare(&mut self, emulator: &Emulator, a: Register, b: Register) -> Option<&Comparison> {
+        let mut values = emulator
+            .last_accesses()
+            .iter()
+            .filter_map(|access| match *access {
+                Access::Read { value, .. } => Some(value),
+                Access::Write { .. } => None,
+            });
+        let operands = [a, b].map(|register| Operand {
+            register,
+            value: values.next().unwrap_or_default(),
+            inputs: self.labels(Location::Register(register)).clone(),
+        });
+
+        let flags = operands[0]
+            .inputs
+            .union(&operands[1].inputs)
+            .copied()
+            .collect();
+        self.set(Location::Register(Register::F), flags);
+
+        if operands.iter().all(|operand| operand.inputs.is_empty()) {
+            return None;
+        }
+
+        self.comparisons.push(Comparison {
+            index: emulator.last_index(),
+            operands,
+        });
+        self.comparisons.last()
+    }
+
+    /// Labels the bytes a `READ_MEMORY` of standard input wrote, and clears the labels of anything
+    /// else a syscall wrote.
+    fn syscall(&mut self, emulator: &Emulator) {
+        let constants = emulator.constants();
+        let reads_stdin = emulator.last_syscall().is_some_and(|syscall| {
+            syscall.number == constants.syscall.READ_MEMORY && syscall.args[0] == 0
+        });
+
+        for access in emulator.last_accesses() {
+            let Access::Write { location, .. } = *access else {
+                continue;
+            };
+
+            let labels = match location {
+                Location::Memory(_) if reads_stdin => {
+                    self.stdin_offset += 1;
+                    Labels::from([self.stdin_offset - 1])
+                }
+                _ => Labels::new(),
+            };
+            self.set(location, labels);
+        }
+    }
+}
+
+/// Returns the first location matching `is_kind` that was written, if `write`, or read.
+fn find(accesses: &[Access], write: bool, is_kind: impl Fn(&Location) -> bool) -> Option<Location> {
+    accesses
+        .iter()
+        .filter(|access| matches!(access, Access::Write { .. }) == write)
+        .map(Access::location)
+        .find(is_kind)
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {:#04x} ", self.register.name(), self.value)?;
+
+        if self.inputs.is_empty() {
+            return write!(f, "(constant)");
+        }
+
+        let offsets: Vec<String> = self.inputs.iter().map(usize::to_string).collect();
+        write!(f, "(from input[{}])", offsets.join(", "))
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b] = &self.operands;
+        write!(f, "{:#04x}: CMP {a}, {b}", self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::{testing, EmulatorBuilder},
+        yan85::constants::Constants,
+    };
+
+    /// Runs the program in `source` with `stdin`, returning the taint it ends with.
+    fn run(source: &str, stdin: &[u8]) -> Taint {
+        let mut emulator =
+            testing::load(EmulatorBuilder::new(Constants::default()), source, stdin).build();
+
+        let mut taint = Taint::default();
+        testing::run(&mut emulator, |emulator, instruction| {
+            taint.record(emulator, instruction);
+        })
+        .unwrap();
+
+        taint
+    }
+
+    #[test]
+    fn test_propagation() {
+        let taint = run(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 3
+SYS 0x4 NONE
+LDM a = *b
+IMM c = 2
+ADD b c
+LDM c = *b
+ADD a c
+STK NONE a
+STK d NONE
+STM *b = d
+IMM c = 0x10
+STM *c = c
+IMM a = 0
+SYS 0x20 NONE
+",
+            b"xyz",
+        );
+
+        assert_eq!(
+            taint.labels(Location::Register(Register::D)),
+            &Labels::from([0, 2])
+        );
+        assert_eq!(taint.labels(Location::Stack(0)), &Labels::from([0, 2]));
+        assert_eq!(taint.labels(Location::Memory(0x41)), &Labels::from([1]));
+        assert_eq!(taint.labels(Location::Memory(0x42)), &Labels::from([0, 2]));
+        assert!(taint.labels(Location::Memory(0x10)).is_empty());
+        assert!(taint.labels(Location::Register(Register::A)).is_empty());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let taint = run(
+            "IMM a = 0
+IMM b = 0x40
+IMM c = 2
+SYS 0x4 NONE
+IMM c = 1
+ADD b c
+LDM a = *b
+IMM d = 0x31
+ADD a d
+IMM c = 0x7a
+CMP a c
+CMP c d
+IMM a = 0
+SYS 0x20 NONE
+",
+            b"AB",
+        );
+
+        let [comparison] = taint.comparisons() else {
+            panic!("expected one comparison");
+        };
+        assert_eq!(comparison.operands[0].inputs, Labels::from([1]));
+        assert_eq!(comparison.operands[1].value, 0x7a);
+        assert_eq!(
+            comparison.to_string(),
+            "0x0a: CMP a = 0x73 (from input[1]), c = 0x7a (constant)"
+        );
+    }
+}
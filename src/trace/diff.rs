@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    emulator::Access,
+    trace::TraceEvent,
+    yan85::{instruction::Instruction, register::Register},
+};
+
+/// The first step at which two traces differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The number of steps emulated before the divergence.
+    pub step: u64,
+    /// The first trace's event at that step, or `None` if the trace had already ended.
+    pub a: Option<TraceEvent>,
+    /// The second trace's event at that step, or `None` if the trace had already ended.
+    pub b: Option<TraceEvent>,
+}
+
+/// A `CMP` instruction that set different flags in two traces, or that only ran in one of them.
+///
+/// Executions are paired by occurrence, so the third time the instruction ran in one trace is
+/// compared to the third time it ran in the other, even after control flow diverges. The side of
+/// an execution with no counterpart in the other trace is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CmpDifference {
+    /// The index of the `CMP` instruction.
+    pub index: u8,
+    /// The instruction itself.
+    pub instruction: Instruction,
+    /// How many times the instruction had already run in each trace.
+    pub occurrence: usize,
+    /// The values compared in the first trace.
+    pub operands_a: Option<(u8, u8)>,
+    /// The values compared in the second trace.
+    pub operands_b: Option<(u8, u8)>,
+    /// The flags set in the first trace.
+    pub flags_a: Option<u8>,
+    /// The flags set in the second trace.
+    pub flags_b: Option<u8>,
+}
+
+/// The differences between two traces, as found by [`diff_traces`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceDiff {
+    /// The first step that accessed different values or left different registers.
+    pub state: Option<Divergence>,
+    /// The first step that executed a different instruction, or that only one trace reached.
+    pub control_flow: Option<Divergence>,
+    /// Every `CMP` execution whose flags differed, in the order they ran in the first trace,
+    /// followed by those that only ran in the second.
+    pub cmps: Vec<CmpDifference>,
+}
+
+/// A `CMP` execution: the values compared and the flags set.
+type CmpResult = ((u8, u8), u8);
+
+/// A `CMP` execution in a trace: its index, instruction, occurrence, and result.
+type Cmp = (u8, Instruction, usize, CmpResult);
+
+/// Compares two complete traces of a program, such as runs with different input.
+///
+/// Traces are compared step by step, so they must not be filtered.
+pub fn diff_traces(
+    a: impl IntoIterator<Item = TraceEvent>,
+    b: impl IntoIterator<Item = TraceEvent>,
+) -> TraceDiff {
+    let (mut a, mut b) = (a.into_iter(), b.into_iter());
+    let mut diff = TraceDiff::default();
+
+    let mut cmps_a: Vec<Cmp> = Vec::new();
+    let mut cmps_b: Vec<Cmp> = Vec::new();
+    let mut occurrences_a: HashMap<u8, usize> = HashMap::new();
+    let mut occurrences_b: HashMap<u8, usize> = HashMap::new();
+
+    let mut step = 0;
+
+    loop {
+        let (event_a, event_b) = (a.next(), b.next());
+        if event_a.is_none() && event_b.is_none() {
+            break;
+        }
+
+        if let Some(event) = &event_a {
+            if let Some(result) = cmp_result(event) {
+                let occurrence = next_occurrence(&mut occurrences_a, event.index);
+                cmps_a.push((event.index, event.instruction, occurrence, result));
+            }
+        }
+        if let Some(event) = &event_b {
+            if let Some(result) = cmp_result(event) {
+                let occurrence = next_occurrence(&mut occurrences_b, event.index);
+                cmps_b.push((event.index, event.instruction, occurrence, result));
+            }
+        }
+
+        if diff.control_flow.is_none() {
+            let (same_control_flow, same_state) = match (&event_a, &event_b) {
+                (Some(event_a), Some(event_b)) => (
+                    event_a.index == event_b.index && event_a.instruction == event_b.instruction,
+                    event_a.registers_after == event_b.registers_after
+                        && event_a.accesses == event_b.accesses,
+                ),
+                _ => (false, false),
+            };
+
+            let divergence = || Divergence {
+                step,
+                a: event_a.clone(),
+                b: event_b.clone(),
+            };
+
+            if !same_state && diff.state.is_none() {
+                diff.state = Some(divergence());
+            }
+            if !same_control_flow {
+                diff.control_flow = Some(divergence());
+            }
+        }
+
+        step += 1;
+    }
+
+    diff.cmps = pair_cmps(cmps_a, cmps_b)
+        .into_iter()
+        .filter(|cmp| cmp.flags_a != cmp.flags_b)
+        .collect();
+
+    diff
+}
+
+/// Pairs the `CMP` executions of two traces by index and occurrence, in the order they ran in the
+/// first trace, followed by those that only ran in the second.
+fn pair_cmps(cmps_a: Vec<Cmp>, cmps_b: Vec<Cmp>) -> Vec<CmpDifference> {
+    let mut unpaired_b: HashMap<(u8, usize), CmpResult> = cmps_b
+        .iter()
+        .map(|&(index, _, occurrence, result)| ((index, occurrence), result))
+        .collect();
+
+    let mut cmps: Vec<CmpDifference> = cmps_a
+        .into_iter()
+        .map(|(index, instruction, occurrence, (operands_a, flags_a))| {
+            let b = unpaired_b.remove(&(index, occurrence));
+
+            CmpDifference {
+                index,
+                instruction,
+                occurrence,
+                operands_a: Some(operands_a),
+                operands_b: b.map(|(operands, _)| operands),
+                flags_a: Some(flags_a),
+                flags_b: b.map(|(_, flags)| flags),
+            }
+        })
+        .collect();
+
+    cmps.extend(
+        cmps_b
+            .into_iter()
+            .filter(|(index, _, occurrence, _)| unpaired_b.contains_key(&(*index, *occurrence)))
+            .map(
+                |(index, instruction, occurrence, (operands_b, flags_b))| CmpDifference {
+                    index,
+                    instruction,
+                    occurrence,
+                    operands_a: None,
+                    operands_b: Some(operands_b),
+                    flags_a: None,
+                    flags_b: Some(flags_b),
+                },
+            ),
+    );
+
+    cmps
+}
+
+/// Returns the values compared and the flags set by `event` if it's a `CMP`.
+fn cmp_result(event: &TraceEvent) -> Option<CmpResult> {
+    if !matches!(event.instruction, Instruction::CMP(..)) {
+        return None;
+    }
+
+    let mut reads = event.accesses.iter().filter_map(|access| match access {
+        Access::Read { value, .. } => Some(*value),
+        Access::Write { .. } => None,
+    });
+    let operands = (reads.next()?, reads.next()?);
+
+    Some((operands, event.registers_after[Register::F]))
+}
+
+/// Counts an execution of the instruction at `index`, returning how many times it ran before.
+fn next_occurrence(occurrences: &mut HashMap<u8, usize>, index: u8) -> usize {
+    let count = occurrences.entry(index).or_default();
+    *count += 1;
+
+    *count - 1
+}
+
+/// Describes an event, or the lack of one.
+fn describe(event: &Option<TraceEvent>) -> String {
+    match event {
+        Some(event) => {
+            let writes: Vec<String> = event
+                .accesses
+                .iter()
+                .filter(|access| matches!(access, Access::Write { .. }))
+                .map(ToString::to_string)
+                .collect();
+
+            format!(
+                "{:#04x}: {}\n     {}\n     {}",
+                event.index,
+                event.instruction,
+                event.registers_after,
+                writes.join(", ")
+            )
+        }
+        None => "<trace ended>".to_string(),
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "  a: {}", describe(&self.a))?;
+        write!(f, "  b: {}", describe(&self.b))
+    }
+}
+
+impl fmt::Display for CmpDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#04x}: {} ", self.index, self.instruction)?;
+        write!(f, "(execution {}): ", self.occurrence + 1)?;
+        write!(f, "a: {}, ", describe_cmp(self.operands_a, self.flags_a))?;
+        write!(f, "b: {}", describe_cmp(self.operands_b, self.flags_b))
+    }
+}
+
+/// Describes one side of a [`CmpDifference`], e.g. `0x41 vs 0x4b -> f=0x09`.
+fn describe_cmp(operands: Option<(u8, u8)>, flags: Option<u8>) -> String {
+    match (operands, flags) {
+        (Some((x, y)), Some(flags)) => format!("{x:#04x} vs {y:#04x} -> f={flags:#04x}"),
+        _ => "not run".to_string(),
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.state {
+            Some(divergence) => writeln!(
+                f,
+                "state diverges at step {}:\n{divergence}",
+                divergence.step
+            )?,
+            None => writeln!(f, "the traces are identical")?,
+        }
+
+        if let Some(divergence) = &self.control_flow {
+            writeln!(
+                f,
+                "control flow diverges at step {}:\n{divergence}",
+                divergence.step
+            )?;
+        }
+
+        if !self.cmps.is_empty() {
+            writeln!(f, "CMPs with different flags:")?;
+            for cmp in &self.cmps {
+                writeln!(f, "  {cmp}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::parse_asm_file,
+        emulator::{BufferIo, Emulator},
+        trace::Tracer,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    /// Compares each byte of a two-byte input to "KY", exiting with 1 if both match.
+    const CHECK: &str = "
+        IMM a = 0
+        IMM b = 0x40
+        IMM c = 2
+        SYS 0x4 d
+        IMM a = 0x40
+        LDM b = *a
+        IMM c = 0x4b
+        IMM d = 0x10
+        CMP b c
+        JMP 0x8 d
+        IMM a = 0x41
+        LDM b = *a
+        IMM c = 0x59
+        CMP b c
+        JMP 0x8 d
+        IMM a = 1
+        SYS 0x20 NONE
+    ";
+
+    /// Traces `CHECK` with `stdin`.
+    fn run(stdin: &[u8]) -> Vec<TraceEvent> {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file(CHECK.to_string()).unwrap(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(stdin)));
+
+        Tracer::new(&mut emulator).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_identical() {
+        let diff = diff_traces(run(b"AA"), run(b"AA"));
+        assert_eq!(diff, TraceDiff::default());
+    }
+
+    #[test]
+    fn test_divergence() {
+        let diff = diff_traces(run(b"KA"), run(b"KY"));
+
+        let state = diff.state.unwrap();
+        assert_eq!(state.step, 3);
+
+        let control_flow = diff.control_flow.unwrap();
+        assert_eq!(control_flow.step, 15);
+        assert_eq!(control_flow.a.unwrap().index, 0x10);
+        assert_eq!(control_flow.b.unwrap().index, 0xf);
+
+        assert_eq!(diff.cmps.len(), 1);
+        assert_eq!(diff.cmps[0].index, 0xd);
+        assert_eq!(diff.cmps[0].operands_a, Some((b'A', b'Y')));
+        assert_eq!(diff.cmps[0].operands_b, Some((b'Y', b'Y')));
+    }
+
+    #[test]
+    fn test_trace_ends_early() {
+        let a = run(b"KY");
+        let b = a[..5].to_vec();
+
+        let diff = diff_traces(a, b);
+
+        let control_flow = diff.control_flow.unwrap();
+        assert_eq!(control_flow.step, 5);
+        assert!(control_flow.b.is_none());
+    }
+
+    #[test]
+    fn test_cmps_paired_by_occurrence() {
+        let diff = diff_traces(run(b"AA"), run(b"KA"));
+
+        assert_eq!(diff.cmps.len(), 2);
+        assert_eq!(diff.cmps[0].index, 8);
+        assert_eq!(diff.cmps[0].occurrence, 0);
+
+        // the second CMP only runs in the second trace
+        assert_eq!(diff.cmps[1].index, 0xd);
+        assert_eq!(diff.cmps[1].operands_a, None);
+        assert_eq!(diff.cmps[1].operands_b, Some((b'A', b'Y')));
+        assert!(diff.cmps[1]
+            .to_string()
+            .ends_with("a: not run, b: 0x41 vs 0x59 -> f=0x09"));
+    }
+}
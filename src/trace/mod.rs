@@ -0,0 +1,407 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emu::{emulate_until, Limits, StopReason},
+    emulator::{jump_taken, Access, Emulator, Syscall, WatchTarget},
+    yan85::{instruction::Instruction, registers::Registers},
+};
+
+/// Comparison of two traces.
+mod diff;
+
+pub use diff::{diff_traces, CmpDifference, Divergence, TraceDiff};
+
+/// A record of a single emulated step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    /// The number of steps emulated before this one.
+    pub step: u64,
+    /// The index of the instruction emulated.
+    pub index: u8,
+    /// The instruction emulated.
+    pub instruction: Instruction,
+    /// The registers before the step.
+    pub registers_before: Registers,
+    /// The registers after the step.
+    pub registers_after: Registers,
+    /// The registers, memory, and stack accessed by the step, in order.
+    pub accesses: Vec<Access>,
+    /// For `JMP` instructions, whether the jump was taken.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump_taken: Option<bool>,
+    /// For `SYS` instructions, the syscall's arguments and result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syscall: Option<Syscall>,
+}
+
+impl TraceEvent {
+    /// Records step number `step`, which emulated `instruction` on `emulator` starting from
+    /// `registers_before`.
+    fn new(
+        step: u64,
+        registers_before: Registers,
+        emulator: &Emulator,
+        instruction: Instruction,
+    ) -> Self {
+        let accesses = emulator.last_accesses().to_vec();
+
+        TraceEvent {
+            step,
+            index: emulator.last_index(),
+            instruction,
+            registers_before,
+            registers_after: emulator.registers().clone(),
+            jump_taken: jump_taken(instruction, &accesses),
+            accesses,
+            syscall: emulator.last_syscall(),
+        }
+    }
+}
+
+/// Selects the [`TraceEvent`]s worth recording.
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    /// Whether to only select syscalls.
+    pub syscalls_only: bool,
+    /// If not empty, only select steps that access a register, memory address, or stack slot in one
+    /// of these targets.
+    pub targets: Vec<WatchTarget>,
+}
+
+impl TraceFilter {
+    /// Whether `event` is selected.
+    pub fn matches(&self, event: &TraceEvent) -> bool {
+        if self.syscalls_only && event.syscall.is_none() {
+            return false;
+        }
+
+        self.targets.is_empty()
+            || event.accesses.iter().any(|access| {
+                self.targets
+                    .iter()
+                    .any(|target| target.contains(access.location()))
+            })
+    }
+}
+
+/// Steps through the program loaded in an emulator, recording a [`TraceEvent`] for each step.
+///
+/// As an iterator, it ends after the program exits or faults.
+///
+/// # Examples
+///
+/// ```
+/// use gyan85::{
+///     emulator::Emulator,
+///     trace::Tracer,
+///     yan85::{constants::Constants, instruction::Instruction, memory::Memory, register::Register},
+/// };
+///
+/// let constants = Constants::default();
+/// let mut emulator = Emulator::new(
+///     constants,
+///     vec![
+///         Instruction::IMM(Register::A, 42),
+///         Instruction::SYS(constants.syscall.EXIT, Register::None),
+///     ],
+///     Memory::default(),
+/// );
+///
+/// let events: Vec<_> = Tracer::new(&mut emulator).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(events.len(), 2);
+/// assert_eq!(events[1].syscall.unwrap().args[0], 42);
+/// ```
+pub struct Tracer<'a> {
+    /// The emulator running the program being traced.
+    emulator: &'a mut Emulator,
+    /// The number of steps emulated so far.
+    steps: u64,
+    /// Whether the program has exited or faulted.
+    finished: bool,
+}
+
+impl<'a> Tracer<'a> {
+    /// Constructs a tracer for the program loaded in `emulator`.
+    pub fn new(emulator: &'a mut Emulator) -> Self {
+        Self {
+            emulator,
+            steps: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns the emulator running the program being traced.
+    pub fn emulator(&self) -> &Emulator {
+        self.emulator
+    }
+
+    /// Steps through the next instruction, recording what it did.
+    pub fn step(&mut self) -> Result<TraceEvent> {
+        let registers_before = self.emulator.registers().clone();
+        let instruction = self.emulator.step()?;
+
+        let event = TraceEvent::new(self.steps, registers_before, self.emulator, instruction);
+        self.steps += 1;
+
+        Ok(event)
+    }
+}
+
+impl Iterator for Tracer<'_> {
+    type Item = Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.emulator.exit_code().is_some() {
+            return None;
+        }
+
+        let event = self.step();
+        self.finished = event.is_err();
+
+        Some(event)
+    }
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits or reaches one of `limits`,
+/// writing the events selected by `filter` to `output` as JSON Lines. Returns why emulation
+/// stopped.
+pub fn trace(
+    emulator: &mut Emulator,
+    limits: &Limits,
+    filter: &TraceFilter,
+    mut output: impl Write,
+) -> Result<StopReason> {
+    let reason = emulate_events(emulator, limits, |event| match filter.matches(&event) {
+        true => write_event(&mut output, &event),
+        false => Ok(()),
+    });
+    output.flush()?;
+
+    reason
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits or reaches one of `limits`,
+/// returning every step's event along with why emulation stopped, or the fault that stopped it.
+pub fn record(emulator: &mut Emulator, limits: &Limits) -> (Vec<TraceEvent>, Result<StopReason>) {
+    let mut events = Vec::new();
+    let reason = emulate_events(emulator, limits, |event| {
+        events.push(event);
+        Ok(())
+    });
+
+    (events, reason)
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits, reaches one of `limits`, or
+/// `on_event` fails, calling `on_event` with the event of each step.
+fn emulate_events(
+    emulator: &mut Emulator,
+    limits: &Limits,
+    mut on_event: impl FnMut(TraceEvent) -> Result<()>,
+) -> Result<StopReason> {
+    let mut registers_before = emulator.registers().clone();
+    let mut step = 0;
+    let error = RefCell::new(None);
+
+    let stop = |_: &Emulator| error.borrow().is_some();
+    let on_step = |emulator: &Emulator, instruction| {
+        let event = TraceEvent::new(step, registers_before.clone(), emulator, instruction);
+        registers_before = emulator.registers().clone();
+        step += 1;
+
+        if let Err(e) = on_event(event) {
+            *error.borrow_mut() = Some(e);
+        }
+    };
+
+    let reason = emulate_until(emulator, limits, stop, on_step);
+    match error.into_inner() {
+        Some(e) => Err(e),
+        None => reason,
+    }
+}
+
+/// Writes `event` to `output` as one line of JSON.
+fn write_event(output: &mut impl Write, event: &TraceEvent) -> Result<()> {
+    serde_json::to_writer(&mut *output, event)?;
+    writeln!(output)?;
+
+    Ok(())
+}
+
+/// Reads a trace written by [`trace`].
+pub fn read_trace(input: impl BufRead) -> impl Iterator<Item = Result<TraceEvent>> {
+    input.lines().enumerate().map(|(i, line)| {
+        serde_json::from_str(&line?)
+            .with_context(|| format!("Invalid trace event on line {}", i + 1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::parse_asm_file,
+        emulator::{BufferIo, Fault, Location},
+        yan85::{constants::Constants, memory::Memory, register::Register},
+    };
+
+    /// Reads two bytes of input to 0x40 and compares them, exiting with 0x41 if they're equal and 0
+    /// otherwise.
+    const PROGRAM: &str = "
+        IMM a = 0
+        IMM b = 0x40
+        IMM c = 2
+        SYS 0x4 d
+        IMM a = 0x40
+        LDM b = *a
+        IMM a = 0x41
+        LDM c = *a
+        IMM d = 0xc
+        CMP b c
+        JMP 0x4 d
+        IMM a = 0
+        SYS 0x20 NONE
+    ";
+
+    /// Traces `PROGRAM` with `stdin`, returning the selected events and the exit code.
+    fn run(stdin: &[u8], filter: &TraceFilter) -> (Vec<TraceEvent>, u8) {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file(PROGRAM.to_string()).unwrap(),
+            Memory::default(),
+        );
+        emulator.set_io(Box::new(BufferIo::new(stdin)));
+
+        let mut output = Vec::new();
+        let reason = trace(&mut emulator, &Limits::default(), filter, &mut output).unwrap();
+        let StopReason::Exited(exit_code) = reason else {
+            panic!("the program {reason}");
+        };
+
+        let events = read_trace(output.as_slice())
+            .collect::<Result<_>>()
+            .unwrap();
+
+        (events, exit_code)
+    }
+
+    #[test]
+    fn test_events() {
+        let (events, exit_code) = run(b"AA", &TraceFilter::default());
+
+        assert_eq!(exit_code, 0x41);
+        assert_eq!(events.len(), 12);
+        assert_eq!(events[0].registers_after[Register::A], 0);
+
+        let read = &events[3];
+        assert_eq!(read.index, 3);
+        assert_eq!(read.registers_after[Register::D], 2);
+        assert_eq!(
+            read.syscall,
+            Some(Syscall {
+                number: 0x4,
+                args: [0, 0x40, 2],
+                result: 2,
+            })
+        );
+        assert!(read.accesses.contains(&Access::Write {
+            location: Location::Memory(0x41),
+            old: 0,
+            new: b'A',
+        }));
+
+        assert_eq!(events[10].jump_taken, Some(true));
+        assert_eq!(events[11].step, 11);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file(PROGRAM.to_string()).unwrap(),
+            Memory::default(),
+        );
+        let limits = Limits {
+            max_steps: Some(3),
+            ..Limits::default()
+        };
+
+        let mut output = Vec::new();
+        let reason = trace(&mut emulator, &limits, &TraceFilter::default(), &mut output).unwrap();
+        assert_eq!(reason, StopReason::MaxSteps);
+
+        let events: Vec<TraceEvent> = read_trace(output.as_slice())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].registers_before, events[1].registers_after);
+    }
+
+    #[test]
+    fn test_jump_not_taken() {
+        let (events, exit_code) = run(b"AB", &TraceFilter::default());
+
+        assert_eq!(exit_code, 0);
+        assert_eq!(events[10].jump_taken, Some(false));
+        assert_eq!(events[11].jump_taken, None);
+    }
+
+    #[test]
+    fn test_syscalls_only() {
+        let filter = TraceFilter {
+            syscalls_only: true,
+            ..TraceFilter::default()
+        };
+        let (events, _) = run(b"AA", &filter);
+
+        let indices: Vec<u8> = events.iter().map(|event| event.index).collect();
+        assert_eq!(indices, [3, 12]);
+    }
+
+    #[test]
+    fn test_targets() {
+        let filter = TraceFilter {
+            targets: vec!["mem[0x41]".parse().unwrap(), "f".parse().unwrap()],
+            ..TraceFilter::default()
+        };
+        let (events, _) = run(b"AA", &filter);
+
+        let indices: Vec<u8> = events.iter().map(|event| event.index).collect();
+        assert_eq!(indices, [3, 7, 9, 10]);
+    }
+
+    #[test]
+    fn test_tracer_stops_on_fault() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file("IMM a = 1\nSTK b NONE\nIMM a = 2\n".to_string()).unwrap(),
+            Memory::default(),
+        );
+
+        let events: Vec<_> = Tracer::new(&mut emulator).collect();
+
+        assert_eq!(events.len(), 2);
+        assert!(events[1].is_err());
+    }
+
+    #[test]
+    fn test_record_fault() {
+        let mut emulator = Emulator::new(
+            Constants::default(),
+            parse_asm_file("IMM a = 1\nSTK b NONE\nIMM a = 2\n".to_string()).unwrap(),
+            Memory::default(),
+        );
+
+        let (events, reason) = record(&mut emulator, &Limits::default());
+
+        assert_eq!(events.len(), 1);
+        assert!(reason.unwrap_err().downcast_ref::<Fault>().is_some());
+    }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use serde::{Deserialize, Serialize};
 
 /// Instruction encoding specification that varies from level to level.
@@ -144,6 +146,22 @@ impl Default for Syscall {
     }
 }
 
+impl Syscall {
+    /// Returns the name of the built-in syscall with number `number`, e.g. `"WRITE"`.
+    pub fn name(&self, number: u8) -> Option<&'static str> {
+        [
+            (self.OPEN, "OPEN"),
+            (self.READ_CODE, "READ_CODE"),
+            (self.READ_MEMORY, "READ_MEMORY"),
+            (self.WRITE, "WRITE"),
+            (self.SLEEP, "SLEEP"),
+            (self.EXIT, "EXIT"),
+        ]
+        .into_iter()
+        .find_map(|(syscall, name)| (syscall == number).then_some(name))
+    }
+}
+
 /// The constants associated with each flag.
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -172,6 +190,43 @@ impl Default for Flag {
     }
 }
 
+impl Flag {
+    /// Returns the flags that `CMP` assigns when comparing `a` to `b`.
+    pub fn compare(&self, a: u8, b: u8) -> u8 {
+        let mut flags = match a.cmp(&b) {
+            Ordering::Less => self.L | self.N,
+            Ordering::Greater => self.G | self.N,
+            Ordering::Equal => self.E,
+        };
+
+        if (a == 0) && (b == 0) {
+            flags |= self.Z;
+        }
+
+        flags
+    }
+
+    /// Returns the names of the flags set in `flags`, e.g. `"L N"`, or `"none"` if no flag is set.
+    pub fn names(&self, flags: u8) -> String {
+        let names: Vec<&str> = [
+            (self.L, "L"),
+            (self.G, "G"),
+            (self.E, "E"),
+            (self.N, "N"),
+            (self.Z, "Z"),
+        ]
+        .into_iter()
+        .filter(|&(flag, _)| flags & flag != 0)
+        .map(|(_, name)| name)
+        .collect();
+
+        match names.is_empty() {
+            true => "none".to_string(),
+            false => names.join(" "),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -185,4 +240,30 @@ mod tests {
 
         assert_eq!(consts, Constants::default())
     }
+
+    #[test]
+    fn test_syscall_name() {
+        let syscall = Syscall::default();
+
+        assert_eq!(syscall.name(0x8), Some("WRITE"));
+        assert_eq!(syscall.name(0x3), None);
+    }
+
+    #[test]
+    fn test_flag_compare() {
+        let flag = Flag::default();
+
+        assert_eq!(flag.compare(1, 2), flag.L | flag.N);
+        assert_eq!(flag.compare(2, 1), flag.G | flag.N);
+        assert_eq!(flag.compare(0, 0), flag.E | flag.Z);
+    }
+
+    #[test]
+    fn test_flag_names() {
+        let flag = Flag::default();
+
+        assert_eq!(flag.names(flag.compare(1, 2)), "L N");
+        assert_eq!(flag.names(flag.E | flag.Z), "E Z");
+        assert_eq!(flag.names(0), "none");
+    }
 }
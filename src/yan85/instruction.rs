@@ -1,4 +1,5 @@
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use crate::yan85::register::Register;
@@ -16,7 +17,7 @@ pub type MutRegister = Register;
 pub type PointerRegister = Register;
 
 /// Yan85 instruction.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Instruction {
     /// Immediate instruction that assigns an integer to a register.
     IMM(Register, u8),
@@ -45,6 +46,22 @@ pub enum Instruction {
     SYS(SysCall, Register),
 }
 
+impl Instruction {
+    /// Returns the instruction's mnemonic, e.g. `"IMM"`.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::IMM(..) => "IMM",
+            Instruction::ADD(..) => "ADD",
+            Instruction::STK(..) => "STK",
+            Instruction::STM(..) => "STM",
+            Instruction::LDM(..) => "LDM",
+            Instruction::CMP(..) => "CMP",
+            Instruction::JMP(..) => "JMP",
+            Instruction::SYS(..) => "SYS",
+        }
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
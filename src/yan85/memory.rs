@@ -1,7 +1,10 @@
 use std::ops::{Index, IndexMut, Range, RangeFrom};
 
+use serde::{Deserialize, Serialize};
+
 /// The 256-byte Yan85 memory.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "Vec<u8>", try_from = "Vec<u8>")]
 pub struct Memory([u8; 256]);
 
 impl Default for Memory {
@@ -10,12 +13,56 @@ impl Default for Memory {
     }
 }
 
+impl Memory {
+    /// Returns `len` bytes starting at `start` as a hexdump, 16 bytes per line, stopping at the end
+    /// of memory.
+    pub fn hexdump(&self, start: u8, len: u8) -> String {
+        let bytes = &self[start..][..(len as usize).min(256 - start as usize)];
+
+        let mut dump = String::new();
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| match b {
+                    0x20..=0x7e => b as char,
+                    _ => '.',
+                })
+                .collect();
+
+            let address = start as usize + row * 16;
+            dump += &format!("{address:#04x}: {:<47}  |{ascii}|\n", hex.join(" "));
+        }
+
+        dump
+    }
+}
+
 impl From<[u8; 256]> for Memory {
     fn from(value: [u8; 256]) -> Self {
         Self(value)
     }
 }
 
+impl From<Memory> for Vec<u8> {
+    fn from(value: Memory) -> Self {
+        value.0.to_vec()
+    }
+}
+
+impl TryFrom<Vec<u8>> for Memory {
+    type Error = String;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let len = value.len();
+        let bytes = value
+            .try_into()
+            .map_err(|_| format!("expected 256 bytes of memory, found {len}"))?;
+
+        Ok(Self(bytes))
+    }
+}
+
 impl Index<u8> for Memory {
     type Output = u8;
 
@@ -1,10 +1,12 @@
 use anyhow::{anyhow, Result};
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use crate::yan85::constants::Constants;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 /// Yan85 registers.
 pub enum Register {
@@ -24,6 +26,7 @@ pub enum Register {
     /// instructions.
     F,
     /// "Null" pseudo-register. Valid exclusively in STK instructions.
+    #[serde(rename = "NONE")]
     None,
 }
 
@@ -45,6 +48,20 @@ impl Register {
         }
     }
 
+    /// Returns the register's name in assembly, e.g. `"a"` or `"NONE"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Register::A => "a",
+            Register::B => "b",
+            Register::C => "c",
+            Register::D => "d",
+            Register::S => "s",
+            Register::I => "i",
+            Register::F => "f",
+            Register::None => "NONE",
+        }
+    }
+
     /// Converts the register to an 8-bit integer using the given encoding constants.
     pub fn to_u8(self, constants: Constants) -> u8 {
         let r = constants.register;
@@ -65,14 +82,8 @@ impl Register {
 impl fmt::Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Register::A => write!(f, "{}", "a".red()),
-            Register::B => write!(f, "{}", "b".red()),
-            Register::C => write!(f, "{}", "c".red()),
-            Register::D => write!(f, "{}", "d".red()),
-            Register::S => write!(f, "{}", "s".red()),
-            Register::I => write!(f, "{}", "i".red()),
-            Register::F => write!(f, "{}", "f".red()),
-            Register::None => write!(f, "{}", "NONE".black()),
+            Register::None => write!(f, "{}", self.name().black()),
+            _ => write!(f, "{}", self.name().red()),
         }
     }
 }
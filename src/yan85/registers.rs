@@ -1,11 +1,105 @@
-use std::ops::{Index, IndexMut};
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::yan85::register::Register;
 
 /// The seven Yan85 registers, including [Register::S], [Register::I], and [Register::F].
-#[derive(Default)]
+///
+/// Indexing by [Register::None] panics; [Registers::get] and [Registers::get_mut] return `None`
+/// for it instead.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "RegisterValues", into = "RegisterValues")]
 pub struct Registers([u8; 7]);
 
+/// The serialized form of [`Registers`], with each register named.
+#[derive(Serialize, Deserialize)]
+struct RegisterValues {
+    /// Register a.
+    a: u8,
+    /// Register b.
+    b: u8,
+    /// Register c.
+    c: u8,
+    /// Register d.
+    d: u8,
+    /// Register s.
+    s: u8,
+    /// Register i.
+    i: u8,
+    /// Register f.
+    f: u8,
+}
+
+impl Registers {
+    /// The registers that hold values, i.e. every register except [Register::None].
+    pub const ALL: [Register; 7] = [
+        Register::A,
+        Register::B,
+        Register::C,
+        Register::D,
+        Register::S,
+        Register::I,
+        Register::F,
+    ];
+
+    /// Returns the register that holds a value named `name`, e.g. `"a"`.
+    pub fn by_name(name: &str) -> Result<Register> {
+        Self::ALL
+            .into_iter()
+            .find(|register| register.name() == name)
+            .ok_or_else(|| anyhow!("Unknown register: {name}"))
+    }
+
+    /// Returns the value of `register`, or `None` for [Register::None].
+    pub fn get(&self, register: Register) -> Option<u8> {
+        self.0.get(register as usize).copied()
+    }
+
+    /// Returns a mutable reference to `register`, or `None` for [Register::None].
+    pub fn get_mut(&mut self, register: Register) -> Option<&mut u8> {
+        self.0.get_mut(register as usize)
+    }
+}
+
+impl From<RegisterValues> for Registers {
+    fn from(
+        RegisterValues {
+            a,
+            b,
+            c,
+            d,
+            s,
+            i,
+            f,
+        }: RegisterValues,
+    ) -> Self {
+        Self([a, b, c, d, s, i, f])
+    }
+}
+
+impl From<Registers> for RegisterValues {
+    fn from(Registers([a, b, c, d, s, i, f]): Registers) -> Self {
+        Self {
+            a,
+            b,
+            c,
+            d,
+            s,
+            i,
+            f,
+        }
+    }
+}
+
+/// # Panics
+///
+/// Panics if the index is [Register::None], which holds no value. Use [Registers::get] for
+/// registers that may be [Register::None].
 impl Index<Register> for Registers {
     type Output = u8;
 
@@ -14,8 +108,70 @@ impl Index<Register> for Registers {
     }
 }
 
+/// # Panics
+///
+/// Panics if the index is [Register::None], which holds no value. Use [Registers::get_mut] for
+/// registers that may be [Register::None].
 impl IndexMut<Register> for Registers {
     fn index_mut(&mut self, index: Register) -> &mut Self::Output {
         &mut self.0[index as usize]
     }
 }
+
+/// A value of type `T` for each of the seven registers that hold values, e.g. what an analysis
+/// knows about each one.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RegisterMap<T>([T; 7]);
+
+impl<T> RegisterMap<T> {
+    /// Constructs a map with the value `f` returns for each register in [Registers::ALL].
+    pub fn from_fn(mut f: impl FnMut(Register) -> T) -> Self {
+        Self(Registers::ALL.map(&mut f))
+    }
+
+    /// Returns the value for `register`, or `None` for [Register::None].
+    pub fn get(&self, register: Register) -> Option<&T> {
+        self.0.get(register as usize)
+    }
+
+    /// Returns a mutable reference to the value for `register`, or `None` for [Register::None].
+    pub fn get_mut(&mut self, register: Register) -> Option<&mut T> {
+        self.0.get_mut(register as usize)
+    }
+}
+
+/// # Panics
+///
+/// Panics if the index is [Register::None], which holds no value. Use [RegisterMap::get] for
+/// registers that may be [Register::None].
+impl<T> Index<Register> for RegisterMap<T> {
+    type Output = T;
+
+    fn index(&self, index: Register) -> &Self::Output {
+        &self.0[index as usize]
+    }
+}
+
+/// # Panics
+///
+/// Panics if the index is [Register::None], which holds no value. Use [RegisterMap::get_mut] for
+/// registers that may be [Register::None].
+impl<T> IndexMut<Register> for RegisterMap<T> {
+    fn index_mut(&mut self, index: Register) -> &mut Self::Output {
+        &mut self.0[index as usize]
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, register) in Self::ALL.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{register}={:#04x}", self[register])?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
 use std::ops::{Index, IndexMut, Range};
 
+use serde::{Deserialize, Serialize};
+
 /// The 256-byte Yan85 stack.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "Vec<u8>", try_from = "Vec<u8>")]
 pub struct Stack([u8; 256]);
 
 impl Default for Stack {
@@ -9,6 +13,31 @@ impl Default for Stack {
     }
 }
 
+impl From<[u8; 256]> for Stack {
+    fn from(value: [u8; 256]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Stack> for Vec<u8> {
+    fn from(value: Stack) -> Self {
+        value.0.to_vec()
+    }
+}
+
+impl TryFrom<Vec<u8>> for Stack {
+    type Error = String;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        let len = value.len();
+        let bytes = value
+            .try_into()
+            .map_err(|_| format!("expected 256 bytes of stack, found {len}"))?;
+
+        Ok(Self(bytes))
+    }
+}
+
 impl Index<u8> for Stack {
     type Output = u8;
 